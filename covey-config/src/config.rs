@@ -1,6 +1,6 @@
 //! Types for the user config.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, num::NonZeroU16, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -11,12 +11,389 @@ use crate::{
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "each bool is a separate top-level setting in the config file"
+)]
 pub struct GlobalConfig {
     #[serde(default)]
     pub plugins: KeyedList<PluginConfig>,
+    /// Delay between each simulated key press when typing text.
+    ///
+    /// Some applications drop input that is typed too quickly.
+    #[serde(default = "default_type_text_delay_ms")]
+    pub type_text_delay_ms: u32,
+    /// Whether to clear the query after activating a list item.
+    ///
+    /// If the activation sets the input, the query is never cleared.
+    #[serde(default)]
+    pub clear_query_on_activate: bool,
+    #[serde(default)]
+    pub appearance: Appearance,
+    /// A shell command to transform every query before it is sent
+    /// to plugins.
+    ///
+    /// The command is given the query on stdin, and should print the
+    /// transformed query to stdout. The original query is used if the
+    /// command fails or takes too long.
+    #[serde(default)]
+    pub query_transform: Option<String>,
+    #[serde(default)]
+    pub activation_feedback: ActivationFeedback,
+    /// Maximum time that any plugin can take to respond to a request,
+    /// in milliseconds.
+    ///
+    /// This can be overridden for each plugin. If this is missing,
+    /// a default of 10 seconds is used.
+    #[serde(default)]
+    pub timeout_ms: Option<u32>,
+    /// Maximum number of plugin queries that can run at the same time.
+    ///
+    /// Other queries wait until one finishes. If this is missing, there
+    /// is no limit.
+    #[serde(default)]
+    pub max_concurrent_queries: Option<NonZeroU16>,
+    /// Whether plugins should record the queries that the user settles on.
+    ///
+    /// Plugins can read these back to rank results by what was searched
+    /// for before.
+    #[serde(default)]
+    pub log_queries: bool,
+    /// Whether plugins should record the text that their items copy.
+    ///
+    /// Plugins like clipboard managers can read these back. Copied text
+    /// can be private, so this is off by default.
+    #[serde(default)]
+    pub clipboard_history: bool,
+    /// Shell used to run shell commands returned by plugins.
+    #[serde(default)]
+    pub shell: Shell,
+    /// Whether to send events to a local socket, so that other tools
+    /// can react to covey being shown, hidden or activated.
+    #[serde(default)]
+    pub event_socket: bool,
+    /// Minimum fuzzy match score, from 0 to 1, that results ranked by
+    /// plugins must reach to be shown.
+    ///
+    /// Plugins can override this. If this is missing, weak matches
+    /// are not hidden.
+    #[serde(default)]
+    pub min_score: Option<f32>,
+    /// Bonuses and penalties of the fuzzy matching used by plugins,
+    /// which change which results are ranked first.
+    #[serde(default)]
+    pub match_scoring: MatchScoring,
+    /// How plugins fuzzy match the query against their results, which
+    /// trades the quality of the ranking for speed.
+    #[serde(default)]
+    pub match_algorithm: MatchAlgorithm,
+    /// How plugins combine how often and how recently a result was
+    /// activated, to rank the results that were used before higher.
+    #[serde(default)]
+    pub frecency: FrecencyStrategy,
+    /// Whether plugins should show recently activated items when their
+    /// query is empty, instead of an empty list.
+    #[serde(default)]
+    pub show_recent: bool,
+    /// Number of activated items that each plugin remembers, to rank
+    /// results and show recent items.
+    ///
+    /// The least recently used items beyond this are forgotten. If this
+    /// is missing, every activation is kept.
+    #[serde(default)]
+    pub max_history_entries: Option<u32>,
+    /// Maximum size of a message sent between covey and a plugin,
+    /// in bytes.
+    ///
+    /// A plugin that returns more results than fit in this fails with
+    /// an error. If this is missing, a default of 4 MiB is used.
+    #[serde(default)]
+    pub max_message_bytes: Option<u32>,
+    /// Commands run when a list item is clicked with mouse buttons other
+    /// than the primary button.
+    #[serde(default)]
+    pub mouse_buttons: MouseButtons,
+    /// Maximum number of results to show, so that plugins with many
+    /// results don't slow down the menu.
+    ///
+    /// Only the highest ranked results are shown.
+    #[serde(default = "default_max_displayed_results")]
+    pub max_displayed_results: u16,
+    /// Shorthands for the first word of a query, like `gh` for a
+    /// plugin's prefix.
+    ///
+    /// The first word is replaced once it is followed by a space.
+    /// Expansions are not expanded again.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Whether to replace an alias in the search input with what it
+    /// expands to.
+    #[serde(default)]
+    pub show_expanded_aliases: bool,
+    /// Time to wait before showing the results of a new query, in
+    /// milliseconds.
+    ///
+    /// Results that are replaced within this time are never shown, which
+    /// avoids flicker with fast plugins. Results that take longer are
+    /// shown immediately. If this is 0, results are never delayed.
+    #[serde(default)]
+    pub results_delay_ms: u16,
+    /// Maximum number of characters of a result's description to show.
+    ///
+    /// Longer descriptions end with an ellipsis, and the full description
+    /// is shown when hovering over it. If this is missing, descriptions
+    /// are never truncated.
+    #[serde(default)]
+    pub max_description_chars: Option<u16>,
+    /// Prefix that searches covey's own commands, like reloading the
+    /// config.
+    ///
+    /// If this is missing, the commands aren't shown.
+    #[serde(default)]
+    pub commands_prefix: Option<String>,
+    /// What pressing escape does, after leaving any contexts.
+    #[serde(default)]
+    pub escape: EscapeAction,
+    /// What pressing enter does when a query has no results, like
+    /// searching the web for it.
+    ///
+    /// If this is missing, nothing happens.
+    #[serde(default)]
+    pub no_results_action: Option<NoResultsAction>,
+    /// Whether to start without showing the menu, so that covey can be
+    /// started when logging in.
+    ///
+    /// The menu is shown when covey is launched again or its tray icon
+    /// is clicked.
+    #[serde(default)]
+    pub start_hidden: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+fn default_type_text_delay_ms() -> u32 {
+    12
+}
+
+fn default_max_displayed_results() -> u16 {
+    200
+}
+
+/// A shell binary and the arguments that make it run a command.
+///
+/// The command is passed as the last argument.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+pub struct Shell {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Default for Shell {
+    /// `sh -c` on Unix or `cmd /C` on Windows.
+    fn default() -> Self {
+        let (program, arg) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        Self {
+            program: program.to_owned(),
+            args: vec![arg.to_owned()],
+        }
+    }
+}
+
+/// The command that each mouse button runs on a list item.
+///
+/// A missing command means that the button does nothing.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(default)] // missing buttons keep their default command
+pub struct MouseButtons {
+    pub middle: Option<String>,
+    pub back: Option<String>,
+    pub forward: Option<String>,
+}
+
+impl Default for MouseButtons {
+    /// Middle-click runs `alt-activate`, other buttons do nothing.
+    fn default() -> Self {
+        Self {
+            middle: Some("alt-activate".to_owned()),
+            back: None,
+            forward: None,
+        }
+    }
+}
+
+/// Bonuses and penalties of fuzzy matching.
+///
+/// Bonuses can be at most 500, and the penalty at most 100.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(default)] // missing values keep their default
+pub struct MatchScoring {
+    /// Added for each matched character that follows the previous one,
+    /// multiplied by the length of the run.
+    pub consecutive_bonus: u32,
+    /// Added for each matched character at the start of a word.
+    pub word_start_bonus: u32,
+    /// Subtracted for each character between two matched characters.
+    pub gap_penalty: u32,
+}
+
+impl Default for MatchScoring {
+    fn default() -> Self {
+        Self {
+            consecutive_bonus: 8,
+            word_start_bonus: 72,
+            gap_penalty: 4,
+        }
+    }
+}
+
+/// A way to fuzzy match a query against a result.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchAlgorithm {
+    /// Rewards consecutive matches and matches at the start of words.
+    #[default]
+    Skim,
+    /// The letters of the query in order, preferring closer matches.
+    /// This is faster than skim.
+    Subsequence,
+    /// Similarity of the whole result to the query, which tolerates typos.
+    JaroWinkler,
+}
+
+/// A way to score how often and how recently a result was activated.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum FrecencyStrategy {
+    /// The number of activations divided by the time since the last one.
+    #[default]
+    Classic,
+    /// The number of activations, halving every week since the last one.
+    ExponentialDecay,
+    /// Only the number of activations.
+    Frequency,
+    /// Only the time since the last activation.
+    Recency,
+}
+
+/// Feedback given when a list item is successfully activated.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum ActivationFeedback {
+    #[default]
+    None,
+    /// Briefly flash the menu.
+    Flash,
+    /// Play a sound file.
+    Sound(PathBuf),
+}
+
+/// What pressing escape does outside of any context.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum EscapeAction {
+    /// Close the menu.
+    #[default]
+    Close,
+    /// Clear the query, and only close the menu if it is already empty.
+    Clear,
+}
+
+/// An action run with a query that has no results.
+///
+/// `{query}` is replaced with the query, including any plugin prefix.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum NoResultsAction {
+    /// Run a command with the `shell`, like `{query}` to run the query
+    /// itself.
+    RunShell(String),
+    /// Open a URL, like a web search. The query is percent-encoded.
+    OpenUrl(String),
+}
+
+/// Layout of the results list.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+pub struct Appearance {
+    /// Width and height of list item icons, in pixels.
+    ///
+    /// If this is missing, icons are sized to fit the title and description.
+    #[serde(default)]
+    pub icon_size: Option<NonZeroU16>,
+    /// Space between each list item, in pixels.
+    #[serde(default = "default_row_spacing")]
+    pub row_spacing: u16,
+    #[serde(default = "default_show_descriptions")]
+    pub show_descriptions: bool,
+    /// How titles and descriptions that are too long are shown.
+    #[serde(default)]
+    pub text_overflow: TextOverflow,
+    /// Maximum number of lines to wrap titles and descriptions to,
+    /// if they are wrapped. Text past this is cut off with an ellipsis.
+    ///
+    /// If this is missing, text can wrap onto any number of lines.
+    #[serde(default)]
+    pub max_lines: Option<NonZeroU16>,
+    /// Hide descriptions and use less space around each item, to fit
+    /// more items.
+    ///
+    /// This can also be toggled by pressing F6 in the menu.
+    #[serde(default)]
+    pub compact: bool,
+    /// Name of the icon theme to find named icons in, like `Papirus`.
+    ///
+    /// Icons that aren't in this theme are taken from the default theme.
+    /// If this is missing, only the default theme is used.
+    #[serde(default)]
+    pub icon_theme: Option<String>,
+}
+
+/// How text that is too long to fit on one line is shown.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "kebab-case")]
+pub enum TextOverflow {
+    /// Wrap onto multiple lines.
+    #[default]
+    Wrap,
+    /// Show a single line, cut off with an ellipsis.
+    Ellipsize,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            icon_size: None,
+            row_spacing: default_row_spacing(),
+            show_descriptions: default_show_descriptions(),
+            text_overflow: TextOverflow::default(),
+            max_lines: None,
+            compact: false,
+            icon_theme: None,
+        }
+    }
+}
+
+fn default_row_spacing() -> u16 {
+    16
+}
+
+fn default_show_descriptions() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
 pub struct PluginConfig {
     pub id: Key,
@@ -25,6 +402,92 @@ pub struct PluginConfig {
     pub config: serde_json::Map<String, serde_json::Value>,
     #[serde(default)]
     pub commands: HashMap<Key, Hotkey>,
+    /// Maximum time that this plugin can take to respond to a request,
+    /// in milliseconds. Overrides the global `timeout_ms`.
+    #[serde(default)]
+    pub timeout_ms: Option<u32>,
+    /// Timeouts for specific requests, overriding this plugin's `timeout_ms`.
+    #[serde(default)]
+    pub timeouts: RequestTimeouts,
+    /// Time to wait for the query to stop changing before querying this
+    /// plugin, in milliseconds.
+    ///
+    /// This is useful for plugins that make network requests. Other
+    /// plugins are queried immediately.
+    #[serde(default)]
+    pub debounce_ms: Option<u32>,
+    /// Command that runs the plugin, which is searched for in the `PATH`
+    /// if it isn't a path.
+    ///
+    /// If this is missing, the binary in the plugin's data directory
+    /// is run.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Whether the `command` is a script that prints results as lines of
+    /// JSON, instead of a plugin server.
+    #[serde(default)]
+    pub script: bool,
+    /// Number of threads that the plugin runs its requests on.
+    ///
+    /// Only plugins made with the Rust plugin library use this. If this
+    /// is missing, there is one thread per CPU core.
+    #[serde(default)]
+    pub worker_threads: Option<NonZeroU16>,
+    /// Resources that the plugin's process can use before it is stopped.
+    ///
+    /// Limits are only applied on Linux.
+    #[serde(default)]
+    pub limits: ResourceLimits,
+    /// Regex that a query (without the prefix) must match to be sent to
+    /// the plugin. Overrides the `query-pattern` in the plugin's manifest.
+    #[serde(default)]
+    pub query_pattern: Option<String>,
+    /// Whether the prefix is shown as a chip before the search bar while
+    /// this plugin is active, instead of as editable text.
+    ///
+    /// Pressing backspace at the start of the query removes the whole
+    /// prefix.
+    #[serde(default)]
+    pub prefix_chip: bool,
+    /// Whether activations of this plugin's items are recorded, to rank
+    /// them by frequency and show recent items. Overrides the
+    /// `track-activations` in the plugin's manifest.
+    #[serde(default)]
+    pub track_activations: Option<bool>,
+    /// Whether an empty query (without the prefix) is sent to this
+    /// plugin. Overrides the `handles-empty-query` in the plugin's
+    /// manifest.
+    #[serde(default)]
+    pub handles_empty_query: Option<bool>,
+}
+
+/// Timeouts for each kind of request to a plugin, in milliseconds.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+pub struct RequestTimeouts {
+    #[serde(default)]
+    pub query_ms: Option<u32>,
+    #[serde(default)]
+    pub activate_ms: Option<u32>,
+    /// Time to wait for the plugin to start and print its port.
+    ///
+    /// Unlike the other timeouts, this doesn't fall back to the plugin's
+    /// or global `timeout_ms`. If this is missing, a default of 10
+    /// seconds is used.
+    #[serde(default)]
+    pub spawn_ms: Option<u32>,
+}
+
+/// Limits on the resources of a plugin's process.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+pub struct ResourceLimits {
+    /// Maximum memory that the plugin can allocate, in megabytes.
+    #[serde(default)]
+    pub memory_mb: Option<u32>,
+    /// Maximum CPU time that the plugin can use, in seconds.
+    #[serde(default)]
+    pub cpu_seconds: Option<u32>,
 }
 
 impl Keyed for PluginConfig {
@@ -32,3 +495,66 @@ impl Keyed for PluginConfig {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+
+    use super::{Appearance, GlobalConfig, TextOverflow};
+
+    #[test]
+    fn appearance() -> Result<(), toml::de::Error> {
+        let config: GlobalConfig = toml::from_str(
+            "
+            [appearance]
+            icon_size = 24
+            show_descriptions = false
+            icon_theme = \"Papirus\"
+            ",
+        )?;
+        assert_eq!(config.appearance, Appearance {
+            icon_size: NonZeroU16::new(24),
+            row_spacing: 16,
+            show_descriptions: false,
+            text_overflow: TextOverflow::Wrap,
+            max_lines: None,
+            compact: false,
+            icon_theme: Some("Papirus".to_string()),
+        });
+
+        let config: GlobalConfig = toml::from_str("")?;
+        assert_eq!(config.appearance, Appearance::default());
+        Ok(())
+    }
+
+    #[test]
+    fn text_overflow() -> Result<(), toml::de::Error> {
+        let config: GlobalConfig = toml::from_str(
+            "
+            [appearance]
+            text_overflow = \"ellipsize\"
+            ",
+        )?;
+        assert_eq!(config.appearance.text_overflow, TextOverflow::Ellipsize);
+
+        let config: GlobalConfig = toml::from_str("appearance.max_lines = 2")?;
+        assert_eq!(config.appearance.text_overflow, TextOverflow::Wrap);
+        assert_eq!(config.appearance.max_lines, NonZeroU16::new(2));
+        Ok(())
+    }
+
+    #[test]
+    fn compact() -> Result<(), toml::de::Error> {
+        let config: GlobalConfig = toml::from_str("appearance.compact = true")?;
+        assert!(config.appearance.compact);
+        // other options are kept
+        assert!(config.appearance.show_descriptions);
+        assert_eq!(config.appearance.row_spacing, 16);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_icon_size_errors() {
+        assert!(toml::from_str::<GlobalConfig>("appearance.icon_size = 0").is_err());
+    }
+}
@@ -11,6 +11,8 @@
 //! This is the user-defined configuration for the app that follows the plugin
 //! manifest's schema.
 //!
+//! [`protocol`] has the constants that covey and plugins must agree on.
+//!
 //! [`Serialize`]: serde::Serialize
 //! [`Deserialize`]: serde::Deserialize
 
@@ -20,3 +22,4 @@ pub mod generate;
 pub mod hotkey;
 pub mod keyed_list;
 pub mod manifest;
+pub mod protocol;
@@ -45,9 +45,40 @@ pub struct PluginManifest {
     /// a single list item has should have different hotkeys.
     #[serde(default = "default_commands")]
     pub commands: KeyedList<Command>,
+    /// Regex that a query (without the prefix) must match to be sent to
+    /// the plugin, like `^\d` for a calculator.
+    ///
+    /// Other queries skip the plugin without asking it.
+    pub query_pattern: Option<String>,
+    /// Whether activations are recorded to rank items by frequency and
+    /// show recent items. Defaults to true.
+    ///
+    /// Plugins that list sensitive items can turn this off.
+    pub track_activations: Option<bool>,
+    /// Whether an empty query (without the prefix) is sent to the plugin,
+    /// so that it can show default items. Defaults to true.
+    ///
+    /// Plugins that have nothing to show without a query can turn this
+    /// off, so that empty queries skip the plugin without asking it.
+    pub handles_empty_query: Option<bool>,
 }
 
 impl PluginManifest {
+    /// A manifest with only a name and the default commands.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: None,
+            repository: None,
+            authors: vec![],
+            schema: KeyedList::default(),
+            commands: default_commands(),
+            query_pattern: None,
+            track_activations: None,
+            handles_empty_query: None,
+        }
+    }
+
     pub fn try_from_toml(s: &str) -> Result<Self, toml::de::Error> {
         toml::from_str(s)
     }
@@ -364,6 +395,8 @@ mod tests {
         let input = r#"
             name = "test"
             description = "my description"
+            query-pattern = '^\d'
+            track-activations = false
 
             [[schema]]
             id = "first-option"
@@ -384,6 +417,9 @@ mod tests {
             }])
             .unwrap(),
             commands: default_commands(),
+            query_pattern: Some(r"^\d".to_string()),
+            track_activations: Some(false),
+            handles_empty_query: None,
         });
 
         Ok(())
@@ -459,6 +495,9 @@ mod tests {
             }])
             .unwrap(),
             commands: default_commands(),
+            query_pattern: None,
+            track_activations: None,
+            handles_empty_query: None,
         })
     }
 }
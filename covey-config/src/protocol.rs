@@ -0,0 +1,16 @@
+//! Constants that covey and plugins must agree on.
+
+/// Version of the protocol defined in `covey-plugin/proto/plugin.proto`.
+///
+/// This is sent to covey in the handshake, and covey refuses to use
+/// plugins with a different version. It must be incremented on every
+/// breaking change to the protocol.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Maximum size of a message between covey and a plugin, if the user
+/// doesn't configure one.
+pub const DEFAULT_MAX_MESSAGE_BYTES: u32 = 4 * 1024 * 1024;
+
+/// Environment variable that covey sets to tell a plugin the maximum
+/// message size.
+pub const MAX_MESSAGE_BYTES_VAR: &str = "COVEY_MAX_MESSAGE_BYTES";
@@ -1,16 +1,107 @@
-use crate::{proto, Input};
+use std::path::PathBuf;
+
+use crate::{proto, store, Form, Input, ListItem};
 
 #[derive(Debug, Clone)]
+#[expect(
+    clippy::large_enum_variant,
+    reason = "actions are short-lived, so boxing the item isn't worth it"
+)]
 pub enum Action {
+    /// Close covey. This is always run after every other action.
     Close,
     RunCommand(String, Vec<String>),
     RunShell(String),
     Copy(String),
     SetInput(Input),
+    /// Open a URL with the system's default handler.
+    OpenUrl(String),
+    /// Close covey, then type the text into the focused application
+    /// by simulating key presses.
+    ///
+    /// Unlike [`Action::Copy`], this does not change the clipboard.
+    ///
+    /// This is currently only supported on Linux, and requires either
+    /// `wtype` (Wayland) or `xdotool` (X11) to be installed.
+    TypeText(String),
+    /// Navigate into a sub-context, like a folder, by setting the input.
+    ///
+    /// The `label` is shown as a breadcrumb while in the context.
+    /// Pressing escape returns to the previous query instead of closing
+    /// covey.
+    PushContext {
+        label: String,
+        input: Input,
+    },
+    /// Replace the shown item that has this `id` (see
+    /// [`ListItem::with_id`]), keeping the rest of the list and the
+    /// selection.
+    ///
+    /// This is useful for items that change when activated, like
+    /// toggling a star. Covey stays open and the query is kept.
+    UpdateItem {
+        id: String,
+        item: ListItem,
+    },
+    /// Focus the search input, optionally selecting all of its text so
+    /// that the user can quickly type a new query.
+    ///
+    /// Covey stays open and the query is kept. Use this after
+    /// [`Action::SetInput`] to change the query too.
+    FocusEntry {
+        select_all: bool,
+    },
+    /// Write the contents to a file, creating any missing parent
+    /// directories.
+    ///
+    /// The path is relative to the plugin's data directory, and can't be
+    /// absolute or contain `..`. If `append` is true, the contents are added to the end of the file
+    /// instead of replacing it.
+    WriteFile {
+        path: PathBuf,
+        contents: String,
+        append: bool,
+    },
+    /// Pin the activated item if it isn't pinned, or unpin it if it is.
+    ///
+    /// Pinned items are sorted first by [`rank`](crate::rank::rank).
+    /// This is run by the plugin, so it does nothing as a list's default
+    /// action or an item's description action. See [`sql::pin`].
+    ///
+    /// [`sql::pin`]: crate::sql::pin
+    TogglePin,
+    /// Ask the user to fill in a form, one field at a time.
+    ///
+    /// Covey stays open while the form is filled in. The values are
+    /// given to [`Plugin::submit_form`](crate::Plugin::submit_form).
+    ShowForm(Form),
+    /// Show the content in a small separate window, so that a result
+    /// can be peeked at while covey stays open.
+    ///
+    /// Each action opens a new window, so several results can be peeked
+    /// at side by side.
+    OpenPreviewWindow {
+        content: String,
+    },
+    /// Empty the search input and the list, keeping covey open.
+    ///
+    /// Unlike setting the input to an empty string, this doesn't query
+    /// again. This is useful for quickly adding several things, like
+    /// notes.
+    ClearInput,
+    /// Copy a field of the activated item: `"title"`, `"description"`
+    /// or a key of its [`metadata`](ListItem::metadata).
+    ///
+    /// The field is looked up by covey when the item is activated, so the
+    /// values don't need to be kept by the plugin. Covey shows an error
+    /// if the item doesn't have the field.
+    CopyField(String),
 }
 
 impl Action {
-    pub(crate) fn into_proto(self) -> proto::Action {
+    /// Returns [`None`] for actions that are run by the plugin, which
+    /// aren't sent to covey.
+    pub(crate) fn into_proto(self) -> Option<proto::Action> {
         use proto::action::Action as PrAction;
 
         let inner_action = match self {
@@ -19,10 +110,64 @@ impl Action {
             Self::RunShell(str) => PrAction::RunShell(str),
             Self::Copy(str) => PrAction::Copy(str),
             Self::SetInput(input) => PrAction::SetInput(input.into_proto()),
+            Self::OpenUrl(url) => PrAction::OpenUrl(url),
+            Self::TypeText(text) => PrAction::TypeText(text),
+            Self::PushContext { label, input } => PrAction::PushContext(proto::Context {
+                label,
+                input: input.into_proto(),
+            }),
+            Self::UpdateItem { id, item } => PrAction::UpdateItem(Box::new(proto::UpdateItem {
+                key: id,
+                item: Box::new(store::store_updated_item(item)),
+            })),
+            Self::FocusEntry { select_all } => {
+                PrAction::FocusEntry(proto::FocusEntry { select_all })
+            }
+            Self::WriteFile {
+                path,
+                contents,
+                append,
+            } => PrAction::WriteFile(proto::WriteFile {
+                path: path.to_string_lossy().into_owned(),
+                contents,
+                append,
+            }),
+            Self::TogglePin => return None,
+            Self::ShowForm(form) => PrAction::Form(form.into_proto()),
+            Self::OpenPreviewWindow { content } => PrAction::OpenPreviewWindow(content),
+            Self::ClearInput => PrAction::ClearInput(()),
+            Self::CopyField(field) => PrAction::CopyField(field),
         };
 
-        proto::Action {
+        Some(proto::Action {
             action: Some(inner_action),
+        })
+    }
+}
+
+/// How the actions from an activation are run.
+///
+/// [`Action::Close`] is always run after every other action, so that
+/// it can be returned in any order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionPolicy {
+    /// Start every action without waiting for the commands of earlier
+    /// actions to exit.
+    #[default]
+    Parallel,
+    /// Wait for the command of each action to exit before starting the
+    /// next action.
+    ///
+    /// Use this when an action depends on an earlier one, like copying
+    /// a file that a command creates.
+    Sequential,
+}
+
+impl ExecutionPolicy {
+    pub(crate) fn into_proto(self) -> proto::ExecutionPolicy {
+        match self {
+            Self::Parallel => proto::ExecutionPolicy::Parallel,
+            Self::Sequential => proto::ExecutionPolicy::Sequential,
         }
     }
 }
@@ -35,19 +180,41 @@ impl Action {
 /// - [`Input`] -> `vec![Action::SetInput(Input)]`
 pub struct Actions {
     pub(crate) list: Vec<Action>,
+    pub(crate) execution: ExecutionPolicy,
+}
+
+impl Actions {
+    /// Sets how the actions are run. They are run in parallel by default.
+    #[must_use = "builder method consumes self"]
+    pub fn with_execution(mut self, execution: ExecutionPolicy) -> Self {
+        self.execution = execution;
+        self
+    }
+
+    pub(crate) fn into_proto(self) -> proto::ActivationResponse {
+        proto::ActivationResponse {
+            actions: self
+                .list
+                .into_iter()
+                .filter_map(Action::into_proto)
+                .collect(),
+            execution: Some(self.execution.into_proto().into()),
+        }
+    }
 }
 
 impl<T: IntoIterator<Item = Action>> From<T> for Actions {
     fn from(value: T) -> Self {
         Self {
             list: value.into_iter().collect(),
+            execution: ExecutionPolicy::default(),
         }
     }
 }
 
 impl From<Action> for Actions {
     fn from(value: Action) -> Self {
-        Self { list: vec![value] }
+        Self::from(vec![value])
     }
 }
 
@@ -56,3 +223,22 @@ impl From<Input> for Actions {
         Self::from(Action::SetInput(value))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Action;
+    use crate::proto::action::Action as PrAction;
+
+    #[test]
+    fn type_text_is_not_copy() {
+        let text = "some snippet".to_owned();
+        assert_eq!(
+            Action::TypeText(text.clone()).into_proto().unwrap().action,
+            Some(PrAction::TypeText(text.clone()))
+        );
+        assert_eq!(
+            Action::Copy(text.clone()).into_proto().unwrap().action,
+            Some(PrAction::Copy(text))
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Applications installed on the system.
+//!
+//! On Linux, these are the `.desktop` entries in the `applications`
+//! folder of every XDG data directory. On macOS, these are the `.app`
+//! bundles in the `Applications` folders. Other platforms have no
+//! applications.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use parking_lot::Mutex;
+
+/// An application that can be launched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppInfo {
+    pub name: String,
+    /// Command that launches the app, to be run with a shell.
+    ///
+    /// Field codes like `%U` are removed.
+    pub exec: String,
+    /// Name of the app's icon, or a path to it.
+    pub icon: Option<String>,
+    /// Path to the file that describes the app.
+    pub path: PathBuf,
+}
+
+/// Every app file that apps were read from, with its modification time.
+type FileTimes = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// Applications from the last call to [`list_applications`].
+static CACHE: Mutex<Option<(FileTimes, Arc<[AppInfo]>)>> = Mutex::new(None);
+
+/// Lists the applications installed on the system, sorted by name.
+///
+/// The applications are cached until an app file is added, removed or
+/// changed in one of the directories.
+pub fn list_applications() -> Vec<AppInfo> {
+    list_applications_cached(&app_dirs())
+}
+
+fn list_applications_cached(dirs: &[PathBuf]) -> Vec<AppInfo> {
+    let files = app_files(dirs);
+    let modified: FileTimes = files
+        .iter()
+        .map(|(_, path)| (path.clone(), modified_time(path)))
+        .collect();
+
+    let mut cache = CACHE.lock();
+    if let Some((cached_modified, apps)) = &*cache {
+        if *cached_modified == modified {
+            return apps.to_vec();
+        }
+    }
+
+    let apps: Arc<[AppInfo]> = read_apps(&files).into();
+    *cache = Some((modified, Arc::clone(&apps)));
+    apps.to_vec()
+}
+
+/// Lists the applications in the directories, without caching.
+///
+/// If there are apps with the same ID in multiple directories, the one
+/// in the earliest directory is used.
+pub fn list_applications_in(dirs: &[PathBuf]) -> Vec<AppInfo> {
+    read_apps(&app_files(dirs))
+}
+
+/// Reads the apps from files found by [`app_files`].
+fn read_apps(files: &[(PathBuf, PathBuf)]) -> Vec<AppInfo> {
+    let mut seen_ids = HashSet::new();
+    let mut apps = vec![];
+    for (id, path) in files {
+        if !seen_ids.insert(id) {
+            continue;
+        }
+        // hidden apps still take up their ID, so they hide apps
+        // in later directories too
+        if let Some(app) = read_app(path) {
+            apps.push(app);
+        }
+    }
+    apps.sort_by_cached_key(|app| app.name.to_lowercase());
+    apps
+}
+
+/// Finds the app files in every directory, with their ID: their path
+/// relative to the directory.
+fn app_files(dirs: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let mut app_files = vec![];
+    for dir in dirs {
+        let mut files = vec![];
+        find_files(dir, &mut files);
+        app_files.extend(files.into_iter().map(|path| {
+            let id = path.strip_prefix(dir).unwrap_or(&path).to_owned();
+            (id, path)
+        }));
+    }
+    app_files
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Recursively finds the app files in the directory.
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    entries.sort();
+    for path in entries {
+        if is_app_file(&path) {
+            files.push(path);
+        } else if path.is_dir() {
+            find_files(&path, files);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn app_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join("Applications"));
+    }
+    dirs.push(PathBuf::from("/Applications"));
+    dirs.push(PathBuf::from("/System/Applications"));
+    dirs
+}
+
+#[cfg(target_os = "macos")]
+fn is_app_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "app")
+}
+
+#[cfg(target_os = "macos")]
+fn read_app(path: &Path) -> Option<AppInfo> {
+    Some(AppInfo {
+        name: path.file_stem()?.to_string_lossy().into_owned(),
+        exec: format!(
+            "open -a '{}'",
+            path.display().to_string().replace('\'', r"'\''")
+        ),
+        icon: None,
+        path: path.to_owned(),
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn app_dirs() -> Vec<PathBuf> {
+    if cfg!(not(unix)) {
+        return vec![];
+    }
+
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn is_app_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "desktop")
+}
+
+#[cfg(not(target_os = "macos"))]
+fn read_app(path: &Path) -> Option<AppInfo> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    parse_desktop_entry(&contents, path)
+}
+
+/// Parses the `[Desktop Entry]` group of a `.desktop` file.
+///
+/// Returns [`None`] if the entry isn't an application that should be
+/// shown.
+#[cfg(not(target_os = "macos"))]
+fn parse_desktop_entry(contents: &str, path: &Path) -> Option<AppInfo> {
+    let mut in_entry = false;
+    let mut name = None;
+    let mut exec = None;
+    let mut icon = None;
+    let mut is_app = false;
+
+    for line in contents.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        if !in_entry || line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        // localized keys like `Name[fr]` are ignored
+        match (key.trim(), value.trim()) {
+            ("Type", value) => is_app = value == "Application",
+            ("Name", value) => name = Some(value.to_owned()),
+            ("Exec", value) => exec = Some(remove_field_codes(value)),
+            ("Icon", value) if !value.is_empty() => icon = Some(value.to_owned()),
+            ("NoDisplay" | "Hidden", "true") => return None,
+            _ => {}
+        }
+    }
+
+    is_app.then_some(())?;
+    Some(AppInfo {
+        name: name?,
+        exec: exec?,
+        icon,
+        path: path.to_owned(),
+    })
+}
+
+/// Removes field codes like `%f` from an `Exec` value, which would be
+/// replaced by files or URLs when launched with them.
+///
+/// Whitespace between arguments is collapsed, but quoted arguments are
+/// kept as they are.
+#[cfg(not(target_os = "macos"))]
+fn remove_field_codes(exec: &str) -> String {
+    let mut command = String::with_capacity(exec.len());
+    let mut in_quotes = false;
+    let mut chars = exec.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                // all other field codes are removed
+                if chars.next() == Some('%') {
+                    command.push('%');
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !command.is_empty() && !command.ends_with(' ') {
+                    command.push(' ');
+                }
+            }
+            '\\' if in_quotes => {
+                command.push(c);
+                command.extend(chars.next());
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                command.push(c);
+            }
+            c => command.push(c),
+        }
+    }
+    command.truncate(command.trim_end().len());
+    command
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{list_applications_cached, list_applications_in, remove_field_codes, AppInfo};
+
+    #[test]
+    fn field_codes_are_removed() {
+        assert_eq!(remove_field_codes("firefox %u"), "firefox");
+        assert_eq!(remove_field_codes("app --name %c %F --x"), "app --name --x");
+        assert_eq!(remove_field_codes("printf 100%%"), "printf 100%");
+    }
+
+    #[test]
+    fn quoted_arguments_are_kept() {
+        assert_eq!(
+            remove_field_codes(r#"sh  -c "echo  a   b" %u"#),
+            r#"sh -c "echo  a   b""#
+        );
+        assert_eq!(
+            remove_field_codes(r#"say "\"hi  there\"" %f"#),
+            r#"say "\"hi  there\"""#
+        );
+    }
+
+    #[test]
+    fn cache_notices_nested_and_changed_files() {
+        let root = std::env::temp_dir().join(format!("covey-apps-cache-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        let write = |name: &str, app: &str, modified: SystemTime| {
+            let path = root.join(name);
+            std::fs::write(
+                &path,
+                format!("[Desktop Entry]\nType=Application\nName={app}\nExec={app}\n"),
+            )
+            .unwrap();
+            // timestamps can be too coarse to notice quick changes
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        let names = || -> Vec<String> {
+            list_applications_cached(std::slice::from_ref(&root))
+                .into_iter()
+                .map(|app| app.name)
+                .collect()
+        };
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        write("nested/editor.desktop", "editor", start);
+        let first = names();
+        write(
+            "nested/editor.desktop",
+            "writer",
+            start + Duration::from_secs(1),
+        );
+        let edited = names();
+        write("nested/browser.desktop", "browser", start);
+        let added = names();
+        _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(first, ["editor"]);
+        assert_eq!(edited, ["writer"]);
+        assert_eq!(added, ["browser", "writer"]);
+    }
+
+    #[test]
+    fn desktop_entries_are_read() {
+        let root = std::env::temp_dir().join(format!("covey-apps-test-{}", std::process::id()));
+        let user = root.join("user");
+        let system = root.join("system");
+        std::fs::create_dir_all(user.join("nested")).unwrap();
+        std::fs::create_dir_all(&system).unwrap();
+
+        let write = |path: std::path::PathBuf, contents: &str| {
+            std::fs::write(path, contents).unwrap();
+        };
+        write(
+            system.join("firefox.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Firefox\nName[fr]=Navigateur\nExec=firefox %u\nIcon=firefox\n\n[Desktop Action new-window]\nName=New Window\nExec=firefox --new-window\n",
+        );
+        write(
+            system.join("hidden.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Hidden\nExec=hidden\nNoDisplay=true\n",
+        );
+        write(
+            system.join("link.desktop"),
+            "[Desktop Entry]\nType=Link\nName=Link\nURL=https://example.com\n",
+        );
+        write(system.join("notes.txt"), "not an app");
+        // overrides the system entry with the same ID
+        write(
+            user.join("hidden.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Shown\nExec=shown\n",
+        );
+        write(
+            user.join("nested/editor.desktop"),
+            "[Desktop Entry]\nType=Application\nName=editor\nExec=editor %F\n",
+        );
+
+        let apps = list_applications_in(&[user.clone(), system.clone()]);
+        _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(
+            apps,
+            [
+                AppInfo {
+                    name: "editor".to_owned(),
+                    exec: "editor".to_owned(),
+                    icon: None,
+                    path: user.join("nested/editor.desktop"),
+                },
+                AppInfo {
+                    name: "Firefox".to_owned(),
+                    exec: "firefox".to_owned(),
+                    icon: Some("firefox".to_owned()),
+                    path: system.join("firefox.desktop"),
+                },
+                AppInfo {
+                    name: "Shown".to_owned(),
+                    exec: "shown".to_owned(),
+                    icon: None,
+                    path: user.join("hidden.desktop"),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use crate::proto;
+
+/// Prompts that the user fills in one at a time, shown with
+/// [`Action::ShowForm`](crate::Action::ShowForm).
+///
+/// Each field is filled in with the search input, with its label shown
+/// as a breadcrumb. Once every field is filled in, the values are given
+/// to [`Plugin::submit_form`](crate::Plugin::submit_form) with the form's
+/// `id`. Pressing escape cancels the form.
+#[derive(Debug, Clone)]
+pub struct Form {
+    pub(crate) id: String,
+    pub(crate) fields: Vec<FormField>,
+}
+
+impl Form {
+    /// A form without any fields, which is submitted with this `id`.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            fields: vec![],
+        }
+    }
+
+    /// Adds a field after the existing fields.
+    #[must_use = "builder method consumes self"]
+    pub fn with_field(mut self, field: FormField) -> Self {
+        self.fields.push(field);
+        self
+    }
+
+    pub(crate) fn into_proto(self) -> proto::Form {
+        proto::Form {
+            id: self.id,
+            fields: self.fields.into_iter().map(FormField::into_proto).collect(),
+        }
+    }
+}
+
+/// A single prompt of a [`Form`].
+#[derive(Debug, Clone)]
+pub struct FormField {
+    label: String,
+    placeholder: Option<String>,
+}
+
+impl FormField {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            placeholder: None,
+        }
+    }
+
+    /// Text shown in the empty search input, like an example value.
+    #[must_use = "builder method consumes self"]
+    pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = Some(placeholder.into());
+        self
+    }
+
+    fn into_proto(self) -> proto::FormField {
+        proto::FormField {
+            label: self.label,
+            placeholder: self.placeholder,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use az::SaturatingAs as _;
+
 use crate::proto;
 
 #[derive(Debug, Clone)]
@@ -5,6 +7,11 @@ pub struct Input {
     pub query: String,
     pub range_lb: u16,
     pub range_ub: u16,
+    /// Index of the list item to select once the results of the new
+    /// query are shown.
+    ///
+    /// If this is past the end of the list, the last item is selected.
+    pub select_item: Option<u32>,
 }
 
 impl Input {
@@ -16,13 +23,38 @@ impl Input {
             query: query.into(),
             range_lb: range.lower_bound,
             range_ub: range.upper_bound,
+            select_item: None,
         }
     }
 
+    /// Completes what the user typed, selecting the inserted text so
+    /// that the user can type over it.
+    ///
+    /// If the completion doesn't start with `typed`, all of it is
+    /// selected.
+    pub fn complete(typed: &str, completion: impl Into<String>) -> Self {
+        let completion = completion.into();
+        let start = if completion.starts_with(typed) {
+            typed.chars().count().saturating_as()
+        } else {
+            0
+        };
+        Self::new(completion).select(SelectionRange::between(start, u16::MAX))
+    }
+
+    /// Sets the selection of the input, in characters.
     #[must_use = "builder method consumes self"]
     pub fn select(mut self, sel: SelectionRange) -> Self {
         self.range_lb = sel.lower_bound;
-        self.range_ub = sel.lower_bound;
+        self.range_ub = sel.upper_bound;
+        self
+    }
+
+    /// Selects the list item at this index once the results of the new
+    /// query are shown.
+    #[must_use = "builder method consumes self"]
+    pub fn select_item(mut self, index: u32) -> Self {
+        self.select_item = Some(index);
         self
     }
 
@@ -32,6 +64,7 @@ impl Input {
             query: self.query,
             range_lb: u32::from(self.range_lb),
             range_ub: u32::from(self.range_ub),
+            select_item: self.select_item,
         }
     }
 }
@@ -51,6 +84,17 @@ impl SelectionRange {
         }
     }
 
+    /// Selects from the start index up to, but not including, the
+    /// end index.
+    ///
+    /// The bounds are swapped if the end is before the start.
+    pub fn between(start: u16, end: u16) -> Self {
+        Self {
+            lower_bound: start.min(end),
+            upper_bound: start.max(end),
+        }
+    }
+
     /// Selects the entire query.
     pub fn all() -> Self {
         Self {
@@ -68,3 +112,31 @@ impl SelectionRange {
         Self::at(u16::MAX)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Input, SelectionRange};
+
+    #[test]
+    fn select_sets_both_bounds() {
+        let input = Input::new("hello world").select(SelectionRange::between(6, 11));
+        assert_eq!((input.range_lb, input.range_ub), (6, 11));
+
+        let input = Input::new("hello").select(SelectionRange::between(4, 1));
+        assert_eq!((input.range_lb, input.range_ub), (1, 4));
+    }
+
+    #[test]
+    fn completion_selects_inserted_suffix() {
+        let input = Input::complete("fire", "firefox");
+        assert_eq!(input.query, "firefox");
+        assert_eq!((input.range_lb, input.range_ub), (4, u16::MAX));
+
+        // counted in characters, not bytes
+        let input = Input::complete("café", "café au lait");
+        assert_eq!(input.range_lb, 4);
+
+        let input = Input::complete("xyz", "firefox");
+        assert_eq!((input.range_lb, input.range_ub), (0, u16::MAX));
+    }
+}
@@ -1,15 +1,25 @@
+pub mod apps;
 pub mod manifest;
 pub mod rank;
+pub mod settings;
 pub mod sql;
 
 mod list;
-pub use list::{Icon, List, ListItem, ListStyle};
+pub use list::{Icon, List, ListItem, ListStyle, MatchField, SelectedItem, TextSpan};
 mod action;
-pub use action::{Action, Actions};
+pub use action::{Action, Actions, ExecutionPolicy};
+mod form;
+pub use form::{Form, FormField};
 mod input;
 pub use input::{Input, SelectionRange};
+mod log;
+pub use log::{log, LogLevel};
+mod metadata;
+pub use metadata::Metadata;
 mod plugin;
 pub use plugin::Plugin;
+mod selection;
+pub use selection::primary_selection;
 mod server;
 pub use server::run_server as main;
 mod plugin_lock;
@@ -20,6 +30,10 @@ mod proto {
     tonic::include_proto!("plugin");
 }
 
+pub use covey_config::protocol::{
+    DEFAULT_MAX_MESSAGE_BYTES, MAX_MESSAGE_BYTES_VAR, PROTOCOL_VERSION,
+};
+
 pub use anyhow::{self, Result};
 
 /// Clones variables into an async closure (by calling [`ToOwned::to_owned`]).
@@ -1,8 +1,16 @@
-use std::{collections::HashMap, future::Future, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::Result;
 
-use crate::{proto, Action, Actions};
+use crate::{proto, sql::RecentItem, Action, Actions, Input};
 
 pub struct List {
     pub(crate) items: Vec<ListItem>,
@@ -12,11 +20,129 @@ pub struct List {
     /// the user. Plugins should only set one if the content makes the most
     /// sense with one of these styles.
     pub(crate) style: Option<ListStyle>,
+    /// Whether this plugin handles the query.
+    pub(crate) handled: bool,
+    /// Actions to run when the first item is activated.
+    pub(crate) default_action: Option<Actions>,
+    /// Token passed to [`Plugin::query_page`] to get more results.
+    ///
+    /// [`Plugin::query_page`]: crate::Plugin::query_page
+    pub(crate) next_page: Option<String>,
+    /// A status line shown above the results.
+    pub(crate) header: Option<String>,
+    /// A corrected query, suggested with "Did you mean ...?".
+    pub(crate) correction: Option<Input>,
+    /// Key of [`ListItem::metadata`] that covey sorts the items by,
+    /// and whether it is descending.
+    pub(crate) sort_key: Option<(String, bool)>,
+    /// Time after which the plugin is queried again to replace the
+    /// results.
+    pub(crate) refresh: Option<Duration>,
 }
 
 impl List {
+    /// A list of results for the query.
+    ///
+    /// An empty list still counts as handling the query, so no other
+    /// plugins will be queried. Use [`List::passthrough`] to let other
+    /// plugins handle it instead.
     pub fn new(items: Vec<ListItem>) -> Self {
-        Self { items, style: None }
+        Self {
+            items,
+            style: None,
+            handled: true,
+            default_action: None,
+            next_page: None,
+            header: None,
+            correction: None,
+            sort_key: None,
+            refresh: None,
+        }
+    }
+
+    /// Declines to handle the query.
+    ///
+    /// The next plugin with a matching prefix will be queried instead,
+    /// such as a fallback plugin with an empty prefix.
+    pub fn passthrough() -> Self {
+        Self {
+            items: vec![],
+            style: None,
+            handled: false,
+            default_action: None,
+            next_page: None,
+            header: None,
+            correction: None,
+            sort_key: None,
+            refresh: None,
+        }
+    }
+
+    /// Runs these actions when the first item is activated, instead of
+    /// the first item's `activate` command.
+    ///
+    /// This is useful for "I'm feeling lucky" behaviour, where pressing
+    /// enter straight after typing should do something with the top
+    /// result. Other commands and items are activated as usual.
+    #[must_use = "builder method consumes self"]
+    pub fn with_default_action(mut self, actions: impl Into<Actions>) -> Self {
+        self.default_action = Some(actions.into());
+        self
+    }
+
+    /// Lets the user load more results, by calling
+    /// [`Plugin::query_page`] with this `token`.
+    ///
+    /// This is useful for plugins that wrap paginated APIs. The token
+    /// can be anything, like a page number or a cursor from the API.
+    ///
+    /// [`Plugin::query_page`]: crate::Plugin::query_page
+    #[must_use = "builder method consumes self"]
+    pub fn with_next_page(mut self, token: impl Into<String>) -> Self {
+        self.next_page = Some(token.into());
+        self
+    }
+
+    /// Shows a status line above all of the results, like
+    /// "Showing 5 of 230 issues".
+    #[must_use = "builder method consumes self"]
+    pub fn with_header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    /// Suggests a correction of a misspelled query, shown to the user as
+    /// "Did you mean ...?".
+    ///
+    /// Choosing the correction sets the input to it, which queries the
+    /// plugin again. The input should not include the plugin's prefix.
+    #[must_use = "builder method consumes self"]
+    pub fn with_correction(mut self, correction: Input) -> Self {
+        self.correction = Some(correction);
+        self
+    }
+
+    /// Sorts the items by a value in their [`ListItem::metadata`], like
+    /// files by their size, instead of keeping their order.
+    ///
+    /// Values are compared as numbers if they all are numbers. Items
+    /// without the key are sorted last.
+    #[must_use = "builder method consumes self"]
+    pub fn with_sort_key(mut self, key: impl Into<String>, descending: bool) -> Self {
+        self.sort_key = Some((key.into(), descending));
+        self
+    }
+
+    /// Queries the plugin again once `interval` has passed, replacing
+    /// these results with the new ones, like for a list of running
+    /// processes.
+    ///
+    /// Results are only refreshed while the query is unchanged and the
+    /// menu is shown.
+    #[must_use = "builder method consumes self"]
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh = Some(interval);
+        self
     }
 
     #[must_use = "builder method consumes self"]
@@ -58,10 +184,81 @@ impl ListStyle {
 
 // This should only be converted into a proto::ListItem via the ListItemStore.
 #[derive(Clone)]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "each flag is a separate option set by a builder method"
+)]
 pub struct ListItem {
     pub title: String,
     pub description: String,
     pub icon: Option<Icon>,
+    /// Whether URLs in the description should be shown as clickable links.
+    ///
+    /// This is off by default so that arbitrary text is not mistaken for
+    /// a link.
+    pub linkify_description: bool,
+    /// Extra words that this item should match in [`rank`](crate::rank),
+    /// like synonyms of the title.
+    ///
+    /// These are not shown to the user.
+    pub keywords: Vec<String>,
+    /// Other text that this item should match in [`rank`](crate::rank),
+    /// each with its own weight, like a contact's email address.
+    ///
+    /// These are not shown to the user.
+    pub match_fields: Vec<MatchField>,
+    /// Nested items, like the files in a folder.
+    ///
+    /// These are hidden until the user expands this item.
+    pub children: Vec<ListItem>,
+    /// Styled text to show instead of the title.
+    ///
+    /// If this is empty, the title is shown. The title is still used
+    /// for searching.
+    pub title_spans: Vec<TextSpan>,
+    /// Whether this item is an error message.
+    ///
+    /// Error items are shown with an error style and can't be
+    /// selected or activated.
+    pub is_error: bool,
+    /// When this item happened, in seconds since the unix epoch.
+    ///
+    /// This is shown relative to the current time, like "5m ago".
+    pub timestamp: Option<i64>,
+    /// An ID to refer to this item in [`Action::UpdateItem`].
+    pub id: Option<String>,
+    /// A key that activates this item when pressed, like `"1"`.
+    ///
+    /// This is shown next to the item. If multiple items have the same
+    /// shortcut, only the first can be activated by it.
+    pub shortcut: Option<String>,
+    /// Action to run when the description is clicked, instead of
+    /// activating the item.
+    pub description_action: Option<Box<Action>>,
+    /// Action to run when the item is activated, without asking the
+    /// plugin.
+    pub inline_action: Option<Box<Action>>,
+    /// Whether the icon is a monochrome symbolic icon, which is
+    /// recolored to match the theme.
+    ///
+    /// Only needed for names that don't end in `-symbolic`.
+    pub symbolic_icon: bool,
+    /// Question that the user must confirm before this item is
+    /// activated, like "Delete 3 files?".
+    pub confirmation_prompt: Option<String>,
+    /// Whether the query exactly matches this item, like the name of a
+    /// command.
+    pub exact_match: bool,
+    /// Whether this item is always sorted above unpinned items by
+    /// [`rank`](crate::rank::rank), like items pinned with [`sql::pin`].
+    ///
+    /// [`sql::pin`]: crate::sql::pin
+    pub pinned: bool,
+    /// Values that the list can be sorted by with
+    /// [`List::with_sort_key`], like a file's size.
+    ///
+    /// These are not shown to the user.
+    pub metadata: HashMap<String, String>,
     /// Key is the command's ID.
     pub(crate) commands: ListItemCallbacks,
 }
@@ -73,16 +270,190 @@ impl ListItem {
             title: title.clone(),
             icon: None,
             description: String::new(),
+            linkify_description: false,
+            keywords: vec![],
+            match_fields: vec![],
+            children: vec![],
+            title_spans: vec![],
+            is_error: false,
+            timestamp: None,
+            id: None,
+            shortcut: None,
+            description_action: None,
+            inline_action: None,
+            symbolic_icon: false,
+            confirmation_prompt: None,
+            exact_match: false,
+            pinned: false,
+            metadata: HashMap::new(),
             commands: ListItemCallbacks::new(title),
         }
     }
 
+    /// An item for a file, titled with its path relative to `base`.
+    ///
+    /// Paths in the home directory that aren't in `base` are shown
+    /// starting with `~`, and other paths are shown in full. The absolute
+    /// path is kept in the [`FILE_PATH_KEY`](Self::FILE_PATH_KEY)
+    /// metadata, so the file can still be opened when it is activated.
+    ///
+    /// Relative paths are relative to `base`.
+    pub fn file(path: impl AsRef<Path>, base: impl AsRef<Path>) -> Self {
+        let base = base.as_ref();
+        let path = base.join(path);
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        Self::new(display_path(&path, base, home.as_deref()))
+            .with_metadata(Self::FILE_PATH_KEY, path.display())
+    }
+
+    /// The [`metadata`](Self::metadata) key of the absolute path of an
+    /// item made with [`ListItem::file`].
+    pub const FILE_PATH_KEY: &'static str = "path";
+
+    /// An item that shows an error, like "API rate limited".
+    ///
+    /// Use this to show a problem inline instead of failing the
+    /// whole query. Error items can't be activated.
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            is_error: true,
+            ..Self::new(message)
+        }
+    }
+
+    /// Sets an ID to refer to this item in later actions, like
+    /// [`Action::UpdateItem`].
+    #[must_use = "builder method consumes self"]
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
     #[must_use = "builder method consumes self"]
     pub fn with_description(mut self, desc: impl Into<String>) -> Self {
         self.description = desc.into();
         self
     }
 
+    /// Makes the description clickable, running this action instead of
+    /// activating the item.
+    ///
+    /// For example, the title could open an app while the description
+    /// opens its website.
+    #[must_use = "builder method consumes self"]
+    pub fn with_description_action(mut self, action: Action) -> Self {
+        self.description_action = Some(Box::new(action));
+        self
+    }
+
+    /// Runs this action when the item is activated, instead of calling
+    /// the plugin's `activate` command.
+    ///
+    /// This is quicker for simple items, like links that open a URL.
+    /// Other commands of the item still call the plugin. Since the plugin
+    /// isn't called, the activation isn't added to the item's history,
+    /// and [`Action::TogglePin`] does nothing.
+    #[must_use = "builder method consumes self"]
+    pub fn with_inline_action(mut self, action: Action) -> Self {
+        self.inline_action = Some(Box::new(action));
+        self
+    }
+
+    /// Shows URLs in the description as clickable links, which will
+    /// open the URL when clicked.
+    #[must_use = "builder method consumes self"]
+    pub fn with_linkified_description(mut self) -> Self {
+        self.linkify_description = true;
+        self
+    }
+
+    /// Sets extra words that this item should match, like synonyms
+    /// of the title.
+    #[must_use = "builder method consumes self"]
+    pub fn with_keywords(mut self, keywords: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.keywords = keywords.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds text that this item should match, with a weight relative to
+    /// the title's weight.
+    ///
+    /// For example, a contacts plugin could match email addresses with a
+    /// weight of `0.8`.
+    #[must_use = "builder method consumes self"]
+    pub fn with_match_field(mut self, text: impl Into<String>, weight: f32) -> Self {
+        self.match_fields.push(MatchField::new(text, weight));
+        self
+    }
+
+    /// Sets nested items that can be shown by expanding this item.
+    #[must_use = "builder method consumes self"]
+    pub fn with_children(mut self, children: Vec<ListItem>) -> Self {
+        self.children = children;
+        self
+    }
+
+    /// Shows styled text instead of the title, like highlighted code.
+    #[must_use = "builder method consumes self"]
+    pub fn with_title_spans(mut self, spans: Vec<TextSpan>) -> Self {
+        self.title_spans = spans;
+        self
+    }
+
+    /// Shows when this item happened, like when an email was received.
+    ///
+    /// The `unix_seconds` are shown relative to the current time,
+    /// like "5m ago".
+    #[must_use = "builder method consumes self"]
+    pub fn with_timestamp(mut self, unix_seconds: i64) -> Self {
+        self.timestamp = Some(unix_seconds);
+        self
+    }
+
+    /// Asks the user to confirm before this item is activated, like for
+    /// items that delete something.
+    ///
+    /// The `prompt` is the question to ask, like "Delete 3 files?".
+    #[must_use = "builder method consumes self"]
+    pub fn with_confirmation(mut self, prompt: impl Into<String>) -> Self {
+        self.confirmation_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Marks the query as exactly matching this item, like when the
+    /// query is the name of a command.
+    ///
+    /// The first exact match is selected and highlighted, so that it can
+    /// be activated straight away.
+    #[must_use = "builder method consumes self"]
+    pub fn as_exact_match(mut self) -> Self {
+        self.exact_match = true;
+        self
+    }
+
+    /// Sorts this item above unpinned items when ranked, regardless of
+    /// how well it matches.
+    #[must_use = "builder method consumes self"]
+    pub fn as_pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
+    /// Adds a value that the list can be sorted by with
+    /// [`List::with_sort_key`].
+    #[must_use = "builder method consumes self"]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl fmt::Display) -> Self {
+        self.metadata.insert(key.into(), value.to_string());
+        self
+    }
+
+    /// Sets a key that activates this item when pressed, like `"1"`.
+    #[must_use = "builder method consumes self"]
+    pub fn with_shortcut(mut self, key: impl Into<String>) -> Self {
+        self.shortcut = Some(key.into());
+        self
+    }
+
     #[must_use = "builder method consumes self"]
     pub fn with_icon(mut self, icon: Option<Icon>) -> Self {
         self.icon = icon;
@@ -95,12 +466,37 @@ impl ListItem {
         self
     }
 
+    /// Uses a named monochrome icon that is recolored to match the theme.
+    ///
+    /// Icons with names ending in `-symbolic` are recolored anyways, so
+    /// this is only needed for icons that are named differently.
+    #[must_use = "builder method consumes self"]
+    pub fn with_symbolic_icon_name(mut self, name: impl Into<String>) -> Self {
+        self.icon = Some(Icon::Name(name.into()));
+        self.symbolic_icon = true;
+        self
+    }
+
     #[must_use = "builder method consumes self"]
     pub fn with_icon_text(mut self, text: impl Into<String>) -> Self {
         self.icon = Some(Icon::Text(text.into()));
         self
     }
 
+    /// Uses an encoded image as the icon, like a PNG or SVG.
+    ///
+    /// The `mime` type should be one of `image/png`, `image/jpeg`,
+    /// `image/gif`, `image/webp` or `image/svg+xml`. Images larger than
+    /// 1 MiB are not shown.
+    #[must_use = "builder method consumes self"]
+    pub fn with_icon_bytes(mut self, mime: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.icon = Some(Icon::Bytes {
+            mime: mime.into(),
+            data: data.into(),
+        });
+        self
+    }
+
     /// Adds a command that can be called.
     ///
     /// This should not be used directly, use the extension trait generated
@@ -112,10 +508,103 @@ impl ListItem {
     }
 }
 
+impl fmt::Debug for ListItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListItem")
+            .field("title", &self.title)
+            .field("description", &self.description)
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Shows `path` relative to `base`, or to the home directory with `~`.
+fn display_path(path: &Path, base: &Path, home: Option<&Path>) -> String {
+    if home != Some(base) {
+        if let Ok(rest) = path.strip_prefix(base) {
+            return if rest.as_os_str().is_empty() {
+                ".".to_owned()
+            } else {
+                rest.display().to_string()
+            };
+        }
+    }
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_owned(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Text that a [`ListItem`] should match in [`rank`](crate::rank), other
+/// than its title.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchField {
+    pub text: String,
+    /// How much a match counts for, like [`Weights::title`].
+    ///
+    /// [`Weights::title`]: crate::rank::Weights::title
+    pub weight: f32,
+}
+
+impl MatchField {
+    pub fn new(text: impl Into<String>, weight: f32) -> Self {
+        Self {
+            text: text.into(),
+            weight,
+        }
+    }
+}
+
+/// A section of text with its own style.
+#[derive(Debug, Clone, Default)]
+pub struct TextSpan {
+    pub text: String,
+    /// A CSS hex color, like `#ff8800`.
+    ///
+    /// Other kinds of colors are ignored.
+    pub color: Option<String>,
+    pub bold: bool,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub(crate) fn into_proto(self) -> proto::TextSpan {
+        proto::TextSpan {
+            text: self.text,
+            color: self.color,
+            bold: Some(self.bold),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Icon {
     Name(String),
     Text(String),
+    /// An encoded image with its MIME type, like `image/png`.
+    Bytes {
+        mime: String,
+        data: Vec<u8>,
+    },
 }
 
 impl Icon {
@@ -124,6 +613,7 @@ impl Icon {
         match self {
             Self::Name(name) => Proto::Name(name),
             Self::Text(text) => Proto::Text(text),
+            Self::Bytes { mime, data } => Proto::Bytes(proto::IconBytes { mime, data }),
         }
     }
 }
@@ -135,7 +625,16 @@ type ActivationFunction = Arc<dyn Fn() -> DynFuture<Result<Actions>> + Send + Sy
 pub(crate) struct ListItemCallbacks {
     /// Key is the command's ID.
     commands: HashMap<&'static str, ActivationFunction>,
-    item_title: String,
+    pub(crate) item_title: String,
+    /// The [`ListItem::id`] of the item.
+    pub(crate) item_id: Option<String>,
+    /// The item to save when it is activated, if recent items are shown.
+    pub(crate) recent: Option<RecentItem>,
+    /// Query that must be run again to find this item's commands.
+    ///
+    /// This is set for items that were loaded from the recent items,
+    /// which have no commands of their own.
+    pub(crate) requery: Option<String>,
 }
 
 impl ListItemCallbacks {
@@ -143,6 +642,9 @@ impl ListItemCallbacks {
         Self {
             commands: HashMap::default(),
             item_title: title,
+            item_id: None,
+            recent: None,
+            requery: None,
         }
     }
 
@@ -151,16 +653,86 @@ impl ListItemCallbacks {
     }
 
     /// Calls a command by name, returning an empty vec if the command is not found.
-    pub(crate) async fn call_command(&self, name: &str) -> Result<Vec<Action>> {
+    pub(crate) async fn call_command(&self, name: &str) -> Result<Actions> {
         if let Some(cmd) = self.commands.get(name) {
-            crate::sql::increment_frequency_table(&self.item_title).await?;
-            cmd().await.map(|actions| actions.list)
+            crate::sql::increment_frequency_table(&self.item_title);
+            if let Some(recent) = &self.recent {
+                crate::sql::remember_item(&self.item_title, recent.clone());
+            }
+            cmd().await
         } else {
-            Ok(vec![])
+            Ok(Actions::from([]))
         }
     }
 
     pub(crate) fn ids(&self) -> impl Iterator<Item = &'static str> + use<'_> {
         self.commands.keys().copied()
     }
+
+    pub(crate) fn selected_item(&self) -> SelectedItem {
+        SelectedItem {
+            title: self.item_title.clone(),
+            id: self.item_id.clone(),
+        }
+    }
+}
+
+/// An item that the user selected, given to
+/// [`Plugin::preview`](crate::Plugin::preview).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectedItem {
+    pub title: String,
+    /// The [`ListItem::id`] of the item, if it had one.
+    pub id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{display_path, ListItem};
+
+    #[test]
+    fn file_paths_are_shown_from_home() {
+        let home = Some(Path::new("/home/user"));
+        let show = |path: &str, base: &str| display_path(Path::new(path), Path::new(base), home);
+
+        assert_eq!(
+            show("/home/user/docs/notes.md", "/home/user"),
+            "~/docs/notes.md"
+        );
+        assert_eq!(show("/home/user/docs/notes.md", "/tmp"), "~/docs/notes.md");
+        assert_eq!(show("/home/user", "/home/user"), "~");
+        assert_eq!(show("/etc/hosts", "/home/user"), "/etc/hosts");
+    }
+
+    #[test]
+    fn file_paths_are_shown_from_base() {
+        let home = Some(Path::new("/home/user"));
+        let show = |path: &str, base: &str| display_path(Path::new(path), Path::new(base), home);
+
+        assert_eq!(
+            show("/home/user/code/covey/src/lib.rs", "/home/user/code/covey"),
+            "src/lib.rs"
+        );
+        assert_eq!(show("/srv/www/index.html", "/srv/www"), "index.html");
+        assert_eq!(show("/srv/www", "/srv/www"), ".");
+    }
+
+    #[test]
+    fn file_items_keep_absolute_path() {
+        let item = ListItem::file("src/lib.rs", "/srv/covey");
+        assert_eq!(item.title, "src/lib.rs");
+        assert_eq!(
+            item.metadata[ListItem::FILE_PATH_KEY],
+            "/srv/covey/src/lib.rs"
+        );
+
+        let item = ListItem::file("/srv/covey/Cargo.toml", "/srv/covey");
+        assert_eq!(item.title, "Cargo.toml");
+        assert_eq!(
+            item.metadata[ListItem::FILE_PATH_KEY],
+            "/srv/covey/Cargo.toml"
+        );
+    }
 }
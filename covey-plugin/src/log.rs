@@ -0,0 +1,108 @@
+//! Logging to covey's own logs.
+
+use std::sync::LazyLock;
+
+use parking_lot::Mutex;
+use tokio::sync::broadcast::{self, error::SendError};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+
+use crate::proto;
+
+/// How important a log message is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn into_proto(self) -> proto::LogLevel {
+        match self {
+            Self::Trace => proto::LogLevel::Trace,
+            Self::Debug => proto::LogLevel::Debug,
+            Self::Info => proto::LogLevel::Info,
+            Self::Warn => proto::LogLevel::Warn,
+            Self::Error => proto::LogLevel::Error,
+        }
+    }
+}
+
+/// Maximum number of messages that are kept until covey reads them.
+/// The oldest messages are dropped first.
+const MAX_BUFFERED_LOGS: usize = 1024;
+
+/// Messages that haven't been sent to covey yet.
+struct Logs {
+    sender: broadcast::Sender<proto::LogMessage>,
+    /// Taken when covey starts reading the logs.
+    receiver: Mutex<Option<broadcast::Receiver<proto::LogMessage>>>,
+}
+
+static LOGS: LazyLock<Logs> = LazyLock::new(|| {
+    let (sender, receiver) = broadcast::channel(MAX_BUFFERED_LOGS);
+    Logs {
+        sender,
+        receiver: Mutex::new(Some(receiver)),
+    }
+});
+
+/// Writes a message to covey's logs, tagged with this plugin's ID.
+///
+/// Use this instead of printing to stdout, which covey reads the
+/// plugin's port from. The most recent messages logged before covey
+/// starts reading them are kept until it does. If covey stopped reading
+/// them, the message is printed to stderr instead.
+pub fn log(level: LogLevel, message: impl Into<String>) {
+    let message = proto::LogMessage {
+        level: level.into_proto().into(),
+        message: message.into(),
+    };
+    if let Err(SendError(message)) = LOGS.sender.send(message) {
+        eprintln!("{}", message.message);
+    }
+}
+
+/// Takes the messages that are sent to covey, or [`None`] if they are
+/// already being read.
+pub(crate) fn take_receiver() -> Option<broadcast::Receiver<proto::LogMessage>> {
+    LOGS.receiver.lock().take()
+}
+
+/// The message to send to covey, or a warning if messages were dropped
+/// because covey didn't read them in time.
+pub(crate) fn or_dropped(
+    message: Result<proto::LogMessage, BroadcastStreamRecvError>,
+) -> proto::LogMessage {
+    message.unwrap_or_else(
+        |BroadcastStreamRecvError::Lagged(count)| proto::LogMessage {
+            level: proto::LogLevel::Warn.into(),
+            message: format!("{count} log messages were dropped"),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::{log, take_receiver, LogLevel, MAX_BUFFERED_LOGS};
+    use crate::proto;
+
+    #[test]
+    fn recent_logs_are_kept_until_read() {
+        log(LogLevel::Info, "dropped");
+        for i in 0..MAX_BUFFERED_LOGS {
+            log(LogLevel::Warn, format!("cache is stale {i}"));
+        }
+        let mut receiver = take_receiver().unwrap();
+        assert!(take_receiver().is_none());
+
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(1)));
+        let message = receiver.try_recv().unwrap();
+        assert_eq!(message.level(), proto::LogLevel::Warn);
+        assert_eq!(message.message, "cache is stale 0");
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{proto, Icon};
+
+/// Information about a plugin to show to the user.
+///
+/// Anything that isn't set falls back to the plugin's manifest.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    /// Name of the plugin, like "Calculator".
+    pub display_name: Option<String>,
+    pub icon: Option<Icon>,
+    /// A short description of what the plugin does.
+    pub description: Option<String>,
+    pub author: Option<String>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_display_name(mut self, name: impl Into<String>) -> Self {
+        self.display_name = Some(name.into());
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_icon(mut self, icon: Option<Icon>) -> Self {
+        self.icon = icon;
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_icon_name(mut self, name: impl Into<String>) -> Self {
+        self.icon = Some(Icon::Name(name.into()));
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_icon_text(mut self, text: impl Into<String>) -> Self {
+        self.icon = Some(Icon::Text(text.into()));
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub(crate) fn into_proto(self) -> proto::MetadataResponse {
+        use proto::metadata_response::Icon as Proto;
+
+        proto::MetadataResponse {
+            display_name: self.display_name,
+            icon: self.icon.map(|icon| match icon {
+                Icon::Name(name) => Proto::Name(name),
+                Icon::Text(text) => Proto::Text(text),
+                Icon::Bytes { mime, data } => Proto::Bytes(proto::IconBytes { mime, data }),
+            }),
+            description: self.description,
+            author: self.author,
+        }
+    }
+}
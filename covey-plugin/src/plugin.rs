@@ -1,8 +1,14 @@
 use std::future::Future;
 
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    StreamExt as _,
+};
+
 use crate::{
-    manifest::ManifestDeserialization, plugin_lock::PluginLock, proto, sql, store, Action, List,
-    Result,
+    list::ListItemCallbacks, log, manifest::ManifestDeserialization, plugin_lock::PluginLock,
+    proto, rank, selection, settings::Setting, sql, sql::Migration, store, Action, Actions, List,
+    LogLevel, Metadata, Result, SelectedItem, PROTOCOL_VERSION,
 };
 
 pub trait Plugin: Sized + Send + Sync + 'static {
@@ -14,6 +20,114 @@ pub trait Plugin: Sized + Send + Sync + 'static {
     fn new(config: Self::Config) -> impl Future<Output = Result<Self>> + Send;
 
     fn query(&self, query: String) -> impl Future<Output = Result<List>> + Send;
+
+    /// Queries to suggest to the user, like a search engine's dropdown.
+    ///
+    /// Suggestions are shown separately from the results of
+    /// [`Plugin::query`]. Selecting one sets the query to it without
+    /// activating anything, so suggestions should not include the
+    /// plugin's prefix.
+    ///
+    /// This is called after every query. There are no suggestions
+    /// by default.
+    fn suggestions(&self, _query: String) -> impl Future<Output = Result<Vec<String>>> + Send {
+        async { Ok(vec![]) }
+    }
+
+    /// More results of a query, after a list returned with
+    /// [`List::with_next_page`].
+    ///
+    /// This is called with the `token` of the previous page when the user
+    /// scrolls to the end of the list. Return another list with a token
+    /// to allow loading more pages. There are no more pages by default.
+    fn query_page(
+        &self,
+        _query: String,
+        _token: String,
+    ) -> impl Future<Output = Result<List>> + Send {
+        async { Ok(List::new(vec![])) }
+    }
+
+    /// The full content of a selected item, shown next to the list,
+    /// like the whole text of a clipboard entry.
+    ///
+    /// This is called once the selection settles on one of this
+    /// plugin's items. Nothing is previewed by default.
+    fn preview(&self, _item: SelectedItem) -> impl Future<Output = Result<Option<String>>> + Send {
+        async { Ok(None) }
+    }
+
+    /// Text to show in the search bar when this plugin is active but
+    /// the query is empty, like "Search packages...".
+    ///
+    /// This is only called once after the plugin is initialised. If this
+    /// is empty, the default placeholder is shown.
+    fn placeholder(&self) -> String {
+        String::new()
+    }
+
+    /// Information about this plugin to show to the user, like its
+    /// name and icon.
+    ///
+    /// Anything not provided falls back to the plugin's manifest.
+    fn metadata() -> Metadata {
+        Metadata::default()
+    }
+
+    /// Options that the user can configure, shown as a form in covey's
+    /// settings.
+    ///
+    /// If this is empty, the `schema` in the plugin's manifest is shown.
+    /// The user's values are given to [`Plugin::new`].
+    fn settings_schema() -> Vec<Setting> {
+        vec![]
+    }
+
+    /// Called when the menu is shown.
+    ///
+    /// Use this to start any work that keeps the plugin's results live,
+    /// like a clock. This does not block the menu from showing.
+    fn on_show(&self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Called when the menu is hidden.
+    ///
+    /// Use this to stop any work started in [`Plugin::on_show`].
+    fn on_hide(&self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Changes to this plugin's database schema.
+    ///
+    /// Migrations that haven't been applied yet are run when the plugin
+    /// is initialised, before [`Plugin::new`] is called.
+    /// See [`Migration`] for more details.
+    fn migrations() -> Vec<Migration> {
+        vec![]
+    }
+
+    /// Called with the values of a form shown with [`Action::ShowForm`],
+    /// once the user has filled in every field.
+    ///
+    /// The `id` is the id of the [`Form`](crate::Form), and there is one
+    /// value for each of its fields, in order. Nothing is done by default.
+    fn submit_form(
+        &self,
+        _id: String,
+        _values: Vec<String>,
+    ) -> impl Future<Output = Result<Actions>> + Send {
+        async { Ok(Actions::from(vec![])) }
+    }
+
+    /// Whether this plugin reads the text selected in other apps with
+    /// [`primary_selection`](crate::primary_selection).
+    ///
+    /// Covey only reads the selection for plugins that use it. This is
+    /// `false` by default.
+    fn uses_primary_selection() -> bool {
+        false
+    }
 }
 
 type TonicResult<T> = Result<tonic::Response<T>, tonic::Status>;
@@ -23,36 +137,200 @@ impl<T> proto::plugin_server::Plugin for PluginLock<T>
 where
     T: Plugin,
 {
+    async fn handshake(
+        &self,
+        _request: tonic::Request<proto::HandshakeRequest>,
+    ) -> TonicResult<proto::HandshakeResponse> {
+        Ok(tonic::Response::new(proto::HandshakeResponse {
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: [
+                Some(proto::Capability::Streaming),
+                Some(proto::Capability::Subscribe),
+                T::uses_primary_selection().then_some(proto::Capability::PrimarySelection),
+                Some(proto::Capability::Logging),
+            ]
+            .into_iter()
+            .flatten()
+            .map(Into::into)
+            .collect(),
+        }))
+    }
+
     async fn initialise(
         &self,
         request: tonic::Request<proto::InitialiseRequest>,
-    ) -> TonicResult<()> {
+    ) -> TonicResult<proto::InitialiseResponse> {
         let request = request.into_inner();
         let mut guard = self.write().await;
-        sql::init(&request.sqlite_url)
+        sql::init(&request.sqlite_url, T::migrations())
             .await
             .map_err(into_tonic_status)?;
+        sql::set_query_log(request.log_queries == Some(true));
+        if request.show_recent == Some(true) {
+            sql::enable_recent_items();
+        }
+        if request.clipboard_history == Some(true) {
+            sql::enable_clipboard_history();
+        }
+        if let Some(max_entries) = request.max_history_entries {
+            sql::enable_pruning(max_entries);
+        }
+        if request.track_activations == Some(false) {
+            sql::disable_tracking();
+        }
+        // unset settings are reset, in case they were removed from the
+        // config before it was reloaded
+        rank::set_default_min_score(request.min_score);
+        rank::set_default_algorithm(request.match_algorithm.and_then(|algorithm| {
+            algorithm
+                .parse()
+                .inspect_err(|e| {
+                    log(
+                        LogLevel::Warn,
+                        format!("ignoring match algorithm config: {e}"),
+                    )
+                })
+                .ok()
+        }));
+        rank::set_default_frecency(request.frecency.and_then(|frecency| {
+            frecency
+                .parse()
+                .inspect_err(|e| log(LogLevel::Warn, format!("ignoring frecency config: {e}")))
+                .ok()
+        }));
+        rank::set_default_score_config(request.match_scoring.and_then(|scoring| {
+            rank::ScoreConfig::new(
+                scoring.consecutive_bonus,
+                scoring.word_start_bonus,
+                scoring.gap_penalty,
+            )
+            .inspect_err(|e| {
+                log(
+                    LogLevel::Warn,
+                    format!("ignoring match scoring config: {e:#}"),
+                );
+            })
+            .ok()
+        }));
         let config = ManifestDeserialization::try_from_input(&request.json)
             .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
 
         let plugin = T::new(config).await.map_err(into_tonic_status)?;
+        let response = proto::InitialiseResponse {
+            placeholder: Some(plugin.placeholder()),
+        };
         *guard = Some(plugin);
 
-        Ok(tonic::Response::new(()))
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn metadata(&self, _request: tonic::Request<()>) -> TonicResult<proto::MetadataResponse> {
+        Ok(tonic::Response::new(T::metadata().into_proto()))
+    }
+
+    async fn settings_schema(
+        &self,
+        _request: tonic::Request<()>,
+    ) -> TonicResult<proto::SettingsSchemaResponse> {
+        Ok(tonic::Response::new(proto::SettingsSchemaResponse {
+            fields: T::settings_schema()
+                .into_iter()
+                .map(Setting::into_proto)
+                .collect(),
+        }))
     }
 
     async fn query(
         &self,
         request: tonic::Request<proto::QueryRequest>,
     ) -> TonicResult<proto::QueryResponse> {
+        let proto::QueryRequest {
+            query,
+            primary_selection,
+            ..
+        } = request.into_inner();
+        selection::set_primary_selection(primary_selection);
+        sql::log_query(&query);
+        let list = self
+            .force_read()
+            .await
+            .query(query.clone())
+            .await
+            .map_err(into_tonic_status)?;
+
+        if query.is_empty() && list.items.is_empty() && sql::shows_recent_items() {
+            let recent = sql::recent_items(RECENT_ITEM_LIMIT)
+                .await
+                .map_err(into_tonic_status)?;
+            return Ok(tonic::Response::new(store::store_recent_items(recent)));
+        }
+
+        Ok(tonic::Response::new(store::store_query_result(
+            &query, list,
+        )))
+    }
+
+    async fn query_page(
+        &self,
+        request: tonic::Request<proto::QueryPageRequest>,
+    ) -> TonicResult<proto::QueryResponse> {
+        let proto::QueryPageRequest { query, token } = request.into_inner();
         let list = self
             .force_read()
             .await
-            .query(request.into_inner().query)
+            .query_page(query, token)
+            .await
+            .map_err(into_tonic_status)?;
+
+        Ok(tonic::Response::new(store::store_page_result(list)))
+    }
+
+    async fn suggestions(
+        &self,
+        request: tonic::Request<proto::QueryRequest>,
+    ) -> TonicResult<proto::SuggestionsResponse> {
+        let suggestions = self
+            .force_read()
+            .await
+            .suggestions(request.into_inner().query)
             .await
             .map_err(into_tonic_status)?;
 
-        Ok(tonic::Response::new(store::store_query_result(list)))
+        Ok(tonic::Response::new(proto::SuggestionsResponse {
+            suggestions,
+        }))
+    }
+
+    async fn on_show(&self, _request: tonic::Request<()>) -> TonicResult<()> {
+        if let Some(plugin) = &*self.read().await {
+            plugin.on_show().await;
+        }
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn on_hide(&self, _request: tonic::Request<()>) -> TonicResult<()> {
+        if let Some(plugin) = &*self.read().await {
+            plugin.on_hide().await;
+        }
+        Ok(tonic::Response::new(()))
+    }
+
+    async fn preview(
+        &self,
+        request: tonic::Request<proto::PreviewRequest>,
+    ) -> TonicResult<proto::PreviewResponse> {
+        let id = request.into_inner().selection_id;
+        let item = store::selected_item_of(id).ok_or(tonic::Status::data_loss(format!(
+            "failed to fetch list item with id {id}"
+        )))?;
+        let text = self
+            .force_read()
+            .await
+            .preview(item)
+            .await
+            .map_err(into_tonic_status)?;
+
+        Ok(tonic::Response::new(proto::PreviewResponse { text }))
     }
 
     async fn activate(
@@ -61,21 +339,123 @@ where
     ) -> TonicResult<proto::ActivationResponse> {
         let request = request.into_inner();
         let id = request.selection_id;
-        let callbacks = store::fetch_callbacks_of(id).ok_or(tonic::Status::data_loss(format!(
-            "failed to fetch callback of list item with id {id}"
-        )))?;
+        let mut callbacks = store::fetch_callbacks_of(id).ok_or(tonic::Status::data_loss(
+            format!("failed to fetch callback of list item with id {id}"),
+        ))?;
+        if let Some(query) = callbacks.requery.take() {
+            callbacks = self.find_recent_item(query, &callbacks.item_title).await?;
+        }
 
-        let response = callbacks
+        let actions = callbacks
             .call_command(&request.command_name)
             .await
-            .map(|a| proto::ActivationResponse {
-                actions: a.into_iter().map(Action::into_proto).collect(),
-            });
+            .map_err(into_tonic_status)?;
+        // pins are stored by the plugin, so covey doesn't need to know
+        if actions
+            .list
+            .iter()
+            .any(|action| matches!(action, Action::TogglePin))
+        {
+            crate::sql::toggle_pin(&callbacks.item_title)
+                .await
+                .map_err(into_tonic_status)?;
+        }
+        for action in &actions.list {
+            if let Action::Copy(text) = action {
+                // the text is still copied if it can't be recorded
+                if let Err(e) = crate::sql::record_copy(text).await {
+                    log(LogLevel::Warn, format!("failed to record copy: {e:#}"));
+                }
+            }
+        }
+
+        Ok(tonic::Response::new(actions.into_proto()))
+    }
+
+    async fn submit_form(
+        &self,
+        request: tonic::Request<proto::SubmitFormRequest>,
+    ) -> TonicResult<proto::ActivationResponse> {
+        let proto::SubmitFormRequest { id, values } = request.into_inner();
+        let actions = self
+            .force_read()
+            .await
+            .submit_form(id, values)
+            .await
+            .map_err(into_tonic_status)?;
+        Ok(tonic::Response::new(actions.into_proto()))
+    }
+
+    async fn reorder_pins(
+        &self,
+        request: tonic::Request<proto::ReorderPinsRequest>,
+    ) -> TonicResult<()> {
+        let mut titles = vec![];
+        for id in request.into_inner().selection_ids {
+            let item = store::selected_item_of(id).ok_or(tonic::Status::data_loss(format!(
+                "failed to fetch list item with id {id}"
+            )))?;
+            titles.push(item.title);
+        }
+        let titles: Vec<_> = titles.iter().map(String::as_str).collect();
+        crate::sql::reorder_pins(&titles)
+            .await
+            .map_err(into_tonic_status)?;
+        Ok(tonic::Response::new(()))
+    }
+
+    type LogsStream = LogStream;
+
+    async fn logs(&self, _request: tonic::Request<()>) -> TonicResult<LogStream> {
+        let receiver = log::take_receiver()
+            .ok_or_else(|| tonic::Status::failed_precondition("logs are already being read"))?;
+        Ok(tonic::Response::new(
+            BroadcastStream::new(receiver)
+                .map(log::or_dropped as _)
+                .map(Ok),
+        ))
+    }
+}
+
+/// Messages logged with [`crate::log`], sent until the plugin exits.
+type LogStream = tokio_stream::adapters::Map<
+    tokio_stream::adapters::Map<
+        BroadcastStream<proto::LogMessage>,
+        fn(Result<proto::LogMessage, BroadcastStreamRecvError>) -> proto::LogMessage,
+    >,
+    fn(proto::LogMessage) -> Result<proto::LogMessage, tonic::Status>,
+>;
+
+/// Maximum number of recent items shown when the query is empty.
+const RECENT_ITEM_LIMIT: usize = 10;
+
+impl<T: Plugin> PluginLock<T> {
+    /// Runs the query of a recent item again to find its commands.
+    async fn find_recent_item(
+        &self,
+        query: String,
+        title: &str,
+    ) -> Result<ListItemCallbacks, tonic::Status> {
+        let list = self
+            .force_read()
+            .await
+            .query(query.clone())
+            .await
+            .map_err(into_tonic_status)?;
+        let response = store::store_query_result(&query, list);
 
-        match response {
-            Ok(response) => Ok(tonic::Response::new(response)),
-            Err(err) => Err(into_tonic_status(err)),
+        let mut items: Vec<_> = response.items.iter().collect();
+        while let Some(item) = items.pop() {
+            if item.title == title {
+                return store::fetch_callbacks_of(item.id).ok_or(tonic::Status::data_loss(
+                    format!("failed to fetch callback of recent item {title:?}"),
+                ));
+            }
+            items.extend(&item.children);
         }
+        Err(tonic::Status::not_found(format!(
+            "recent item {title:?} is no longer a result of {query:?}"
+        )))
     }
 }
 
@@ -91,3 +471,195 @@ fn into_tonic_status(e: anyhow::Error) -> tonic::Status {
             .join("\n"),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::{
+        plugin_lock::PluginLock,
+        proto::{self, plugin_server::Plugin as _},
+        List, ListItem, Result, SelectedItem,
+    };
+
+    struct Packages {
+        shown: AtomicBool,
+    }
+
+    impl super::Plugin for Packages {
+        type Config = ();
+
+        async fn new((): ()) -> Result<Self> {
+            Ok(Self {
+                shown: AtomicBool::new(false),
+            })
+        }
+
+        async fn query(&self, query: String) -> Result<List> {
+            Ok(List::new(vec![
+                ListItem::new(format!("{query}-git")).with_id("git")
+            ]))
+        }
+
+        async fn suggestions(&self, query: String) -> Result<Vec<String>> {
+            Ok(vec![format!("{query}-git"), format!("{query}-bin")])
+        }
+
+        async fn query_page(&self, query: String, token: String) -> Result<List> {
+            let list = List::new(vec![ListItem::new(format!("{query} page {token}"))]);
+            Ok(if token == "1" {
+                list.with_next_page("2")
+            } else {
+                list
+            })
+        }
+
+        async fn preview(&self, item: SelectedItem) -> Result<Option<String>> {
+            Ok(item.id.map(|id| format!("{} ({id})", item.title)))
+        }
+
+        fn placeholder(&self) -> String {
+            String::from("Search packages...")
+        }
+
+        fn metadata() -> crate::Metadata {
+            crate::Metadata::new()
+                .with_display_name("Packages")
+                .with_icon_name("package")
+        }
+
+        async fn on_show(&self) {
+            self.shown.store(true, Ordering::Relaxed);
+        }
+
+        async fn on_hide(&self) {
+            self.shown.store(false, Ordering::Relaxed);
+        }
+    }
+
+    #[expect(
+        clippy::await_holding_lock,
+        reason = "no other task on the test's thread takes the lock"
+    )]
+    async fn initialise(lock: &PluginLock<Packages>) -> proto::InitialiseResponse {
+        // initialising resets the user's defaults
+        let _defaults = crate::rank::tests::DEFAULTS.lock();
+        lock.initialise(tonic::Request::new(proto::InitialiseRequest {
+            json: String::from("{}"),
+            sqlite_url: String::from("sqlite::memory:"),
+            log_queries: None,
+            min_score: None,
+            show_recent: None,
+            max_history_entries: None,
+            track_activations: None,
+            match_scoring: None,
+            clipboard_history: None,
+            frecency: None,
+            match_algorithm: None,
+        }))
+        .await
+        .unwrap()
+        .into_inner()
+    }
+
+    #[tokio::test]
+    async fn initialise_sends_placeholder() {
+        let lock = PluginLock::<Packages>::new_empty();
+        let response = initialise(&lock).await;
+
+        assert_eq!(response.placeholder.as_deref(), Some("Search packages..."));
+    }
+
+    #[tokio::test]
+    async fn sends_metadata() {
+        let lock = PluginLock::<Packages>::new_empty();
+        let response = lock
+            .metadata(tonic::Request::new(()))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.display_name.as_deref(), Some("Packages"));
+        assert_eq!(
+            response.icon,
+            Some(proto::metadata_response::Icon::Name(String::from(
+                "package"
+            )))
+        );
+        assert_eq!(response.author, None);
+    }
+
+    #[tokio::test]
+    async fn sends_suggestions() {
+        let lock = PluginLock::<Packages>::new_empty();
+        initialise(&lock).await;
+        let response = lock
+            .suggestions(tonic::Request::new(proto::QueryRequest {
+                query: String::from("neovim"),
+                request_id: None,
+                primary_selection: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert_eq!(response.suggestions, ["neovim-git", "neovim-bin"]);
+    }
+
+    #[tokio::test]
+    async fn sends_next_page() {
+        let lock = PluginLock::<Packages>::new_empty();
+        initialise(&lock).await;
+        let page = |token: &str| {
+            lock.query_page(tonic::Request::new(proto::QueryPageRequest {
+                query: String::from("neovim"),
+                token: token.to_owned(),
+            }))
+        };
+
+        let response = page("1").await.unwrap().into_inner();
+        assert_eq!(response.items[0].title, "neovim page 1");
+        assert_eq!(response.next_page_token.as_deref(), Some("2"));
+        let response = page("2").await.unwrap().into_inner();
+        assert_eq!(response.items[0].title, "neovim page 2");
+        assert_eq!(response.next_page_token, None);
+    }
+
+    #[tokio::test]
+    async fn sends_preview() {
+        let lock = PluginLock::<Packages>::new_empty();
+        initialise(&lock).await;
+        let response = lock
+            .query(tonic::Request::new(proto::QueryRequest {
+                query: String::from("neovim"),
+                request_id: None,
+                primary_selection: None,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let preview = lock
+            .preview(tonic::Request::new(proto::PreviewRequest {
+                selection_id: response.items[0].id,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(preview.text.as_deref(), Some("neovim-git (git)"));
+    }
+
+    #[tokio::test]
+    async fn show_and_hide() {
+        let lock = PluginLock::<Packages>::new_empty();
+        // ignored before initialising
+        lock.on_show(tonic::Request::new(())).await.unwrap();
+
+        initialise(&lock).await;
+        let shown = || lock.force_read();
+        lock.on_show(tonic::Request::new(())).await.unwrap();
+        assert!(shown().await.shown.load(Ordering::Relaxed));
+        lock.on_hide(tonic::Request::new(())).await.unwrap();
+        assert!(!shown().await.shown.load(Ordering::Relaxed));
+    }
+}
@@ -15,6 +15,11 @@ impl<T: Plugin> PluginLock<T> {
         self.0.write().await
     }
 
+    /// Reads the plugin, which is [`None`] if it is not initialised.
+    pub(crate) async fn read(&self) -> RwLockReadGuard<'_, Option<T>> {
+        self.0.read().await
+    }
+
     /// Reads the plugin, panicking if it is not initialised when
     /// the result is dereferenced.
     pub(crate) async fn force_read(&self) -> PluginReadGuard<'_, T> {
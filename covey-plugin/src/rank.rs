@@ -1,12 +1,35 @@
 //! Wrappers to rank items based on their query.
 
+mod algorithm;
+mod frecency;
+mod tokens;
+
 use std::collections::HashMap;
 
+use algorithm::skim_scale;
+pub use algorithm::{normalized_score, score_with, Algorithm, ParseAlgorithmError, ScoreConfig};
+pub(crate) use algorithm::{set_default_algorithm, set_default_score_config};
 use az::SaturatingAs;
+pub(crate) use frecency::set_default_frecency;
+pub use frecency::{ExponentialDecay, Frecency, FrecencyStrategy, ParseFrecencyStrategyError};
+use parking_lot::Mutex;
 use time::OffsetDateTime;
+pub use tokens::{score_tokens, score_tokens_with};
+use tokens::{score_tokens_with_config, token_count};
 
 use crate::{sql, ListItem};
 
+/// Minimum score from the user's config, used by [`Weights`] unless the
+/// plugin sets one.
+///
+/// Replaced each time the plugin is initialised, so that reloading the
+/// config takes effect.
+static DEFAULT_MIN_SCORE: Mutex<Option<f32>> = Mutex::new(None);
+
+pub(crate) fn set_default_min_score(min_score: Option<f32>) {
+    *DEFAULT_MIN_SCORE.lock() = min_score.map(|min_score| min_score.clamp(0.0, 1.0));
+}
+
 async fn activations() -> Option<HashMap<String, (u64, OffsetDateTime)>> {
     let a = sqlx::query_as::<_, (String, i64, time::OffsetDateTime)>(
         "
@@ -24,43 +47,114 @@ async fn activations() -> Option<HashMap<String, (u64, OffsetDateTime)>> {
     )
 }
 
+/// Sorts the items that match the query, best match first.
+///
+/// Pinned items (see [`ListItem::pinned`] and [`sql::pin`]) are sorted
+/// above every unpinned item, but are still hidden if they don't match.
+/// Items pinned with [`sql::pin`] are sorted in the order of
+/// [`sql::reorder_pins`], below the items pinned by the plugin, and are
+/// returned with [`ListItem::pinned`] set.
 pub async fn rank<'iter>(
     query: &str,
     items: impl IntoIterator<Item = &'iter ListItem>,
     weights: Weights,
 ) -> Vec<ListItem> {
-    let should_track_history = weights.frequency != 0.0 || weights.recency != 0.0;
+    let should_track_history =
+        sql::tracks_activations() && (weights.frequency != 0.0 || weights.recency != 0.0);
     let activations = if should_track_history {
         self::activations().await.unwrap_or_else(HashMap::new)
     } else {
         HashMap::new()
     };
+    let pins = sql::pinned_titles().await;
 
-    let now = OffsetDateTime::now_utc();
+    rank_with(
+        query,
+        items,
+        &weights,
+        &activations,
+        &pins,
+        OffsetDateTime::now_utc(),
+    )
+}
 
+/// Ranks the items with the history and pins from the database.
+fn rank_with<'iter>(
+    query: &str,
+    items: impl IntoIterator<Item = &'iter ListItem>,
+    weights: &Weights,
+    activations: &HashMap<String, (u64, OffsetDateTime)>,
+    pins: &[String],
+    now: OffsetDateTime,
+) -> Vec<ListItem> {
+    // fuzzy matching scores are scaled so that they are weighted against
+    // the usage history the same as raw skim scores
+    let fuzzy_scale = skim_scale(weights.score_config, query);
+    // the scores of each word are summed, so they are divided by the
+    // number of words to compare them with the minimum score and weigh
+    // them against the usage history like a single word
     #[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+    let tokens = token_count(query) as f32;
+
+    #[expect(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        reason = "precision isn't needed"
+    )]
     let mut scored: Vec<_> = items
         .into_iter()
         .filter_map(|item| {
+            let score = |text: &str| {
+                score_tokens_with_config(weights.algorithm, weights.score_config, query, text)
+                    .map(|score| score / tokens)
+            };
             macro_rules! score {
                 ($field:ident) => {
                     (weights.$field != 0.0)
-                        .then(|| sublime_fuzzy::best_match(&query, &item.$field))
+                        .then(|| score(&item.$field))
                         .flatten()
-                        .map(|m| m.score() as f32 * weights.$field)
                         .unwrap_or(0.0)
                 };
             }
 
-            let title_score = score!(title);
-            let desc_score = score!(description);
+            let title_match = score!(title);
+            let desc_match = score!(description);
+            let keyword_match = if weights.keywords == 0.0 {
+                0.0
+            } else {
+                item.keywords
+                    .iter()
+                    .filter_map(|keyword| score(keyword))
+                    .fold(0.0, f32::max)
+            };
+            // the match of the field with the best weighted score
+            let (field_match, field_weight) = item
+                .match_fields
+                .iter()
+                .filter(|field| field.weight != 0.0)
+                .filter_map(|field| Some((score(&field.text)?, field.weight)))
+                .max_by(|(s1, w1), (s2, w2)| (s1 * w1).total_cmp(&(s2 * w2)))
+                .unwrap_or((0.0, 0.0));
 
-            let (freq, elapsed_secs) =
+            let best_match = title_match
+                .max(desc_match)
+                .max(keyword_match)
+                .max(field_match);
+            if !query.is_empty() && best_match < weights.min_score {
+                return None;
+            }
+
+            let title_score = title_match * fuzzy_scale * weights.title;
+            let desc_score = desc_match * fuzzy_scale * weights.description;
+            let keyword_score = keyword_match * fuzzy_scale * weights.keywords;
+            let field_score = field_match * fuzzy_scale * field_weight;
+
+            let (frecency, elapsed_secs) =
                 activations
                     .get(&item.title)
-                    .map_or((0, u64::MAX), |(freq, time)| {
+                    .map_or((0.0, u64::MAX), |(freq, time)| {
                         (
-                            *freq,
+                            weights.frecency.score(*freq, *time, now) as f32,
                             (now.unix_timestamp() - time.unix_timestamp()).saturating_as::<u64>(),
                         )
                     });
@@ -69,27 +163,48 @@ pub async fn rank<'iter>(
             // between (0, 1]
             let recency = 1.0 / elapsed_min.saturating_add(20) as f32;
 
-            let fuzzy_score = title_score + desc_score;
-            // factor in recency and fuzzy matching score for the frequency
-            let freq_score =
-                freq as f32 * weights.frequency * recency * (fuzzy_score / 500.0 + 0.1);
+            let fuzzy_score = title_score + desc_score + keyword_score + field_score;
+            // factor in the fuzzy matching score for the frecency
+            let freq_score = frecency * weights.frequency * (fuzzy_score / 500.0 + 0.1);
             let recency_score = recency * weights.recency;
 
             let total_score = fuzzy_score + freq_score + recency_score;
-            let should_show = query.is_empty() || fuzzy_score > 1.0;
-            should_show.then_some((total_score, item))
+            let should_show = query.is_empty() || fuzzy_score > 0.0;
+            // items pinned by the plugin go before the stored pins
+            let pin_order = if item.pinned {
+                Some(0)
+            } else {
+                pins.iter()
+                    .position(|pin| *pin == item.title)
+                    .map(|position| position + 1)
+            };
+            should_show.then_some((pin_order, total_score, item))
         })
         .collect();
-    // sort reversed
-    scored.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
-    scored.into_iter().map(|(_, item)| item).cloned().collect()
+    // pinned first, then highest score first
+    scored.sort_by(|(p1, s1, _), (p2, s2, _)| {
+        let pin_order = |pin: &Option<usize>| pin.unwrap_or(usize::MAX);
+        pin_order(p1).cmp(&pin_order(p2)).then(s2.total_cmp(s1))
+    });
+    scored
+        .into_iter()
+        .map(|(pin_order, _, item)| ListItem {
+            pinned: pin_order.is_some(),
+            ..item.clone()
+        })
+        .collect()
 }
 
 pub struct Weights {
     title: f32,
     description: f32,
+    keywords: f32,
     frequency: f32,
     recency: f32,
+    algorithm: Algorithm,
+    score_config: ScoreConfig,
+    frecency: Box<dyn Frecency + Send + Sync>,
+    min_score: f32,
 }
 
 impl Weights {
@@ -103,8 +218,13 @@ impl Weights {
         Self {
             title: 1.0,
             description: 0.0,
+            keywords: 0.5,
             frequency: 0.0,
             recency: 0.0,
+            algorithm: Algorithm::user_default(),
+            score_config: ScoreConfig::user_default(),
+            frecency: Box::new(FrecencyStrategy::user_default()),
+            min_score: DEFAULT_MIN_SCORE.lock().unwrap_or(0.0),
         }
     }
 
@@ -120,6 +240,16 @@ impl Weights {
         self
     }
 
+    /// Weight of the best matching [keyword](ListItem::keywords).
+    ///
+    /// This defaults to half of the title's weight, so that items
+    /// matching on their title are ranked higher.
+    #[must_use = "builder method consumes self"]
+    pub fn keywords(mut self, keywords: f32) -> Self {
+        self.keywords = keywords;
+        self
+    }
+
     #[must_use = "builder method consumes self"]
     pub fn frequency(mut self, frequency: f32) -> Self {
         self.frequency = frequency;
@@ -131,4 +261,293 @@ impl Weights {
         self.recency = recency;
         self
     }
+
+    /// Sets the algorithm used to fuzzy match the query against the
+    /// title and description.
+    ///
+    /// Each word of the query is matched separately (see
+    /// [`score_tokens_with`]).
+    ///
+    /// Defaults to the user's `match_algorithm` config, or
+    /// [`Algorithm::Skim`] if they haven't set one.
+    #[must_use = "builder method consumes self"]
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Sets the bonuses and penalties of [`Algorithm::Skim`].
+    ///
+    /// Defaults to the user's `match_scoring` config.
+    #[must_use = "builder method consumes self"]
+    pub fn score_config(mut self, score_config: ScoreConfig) -> Self {
+        self.score_config = score_config;
+        self
+    }
+
+    /// Sets how the frequency and last use of an item are combined.
+    ///
+    /// The resulting score is multiplied by the frequency weight.
+    /// Defaults to the user's `frecency` config, or
+    /// [`FrecencyStrategy::Classic`] if they haven't set one.
+    #[must_use = "builder method consumes self"]
+    pub fn frecency(mut self, frecency: impl Frecency + Send + Sync + 'static) -> Self {
+        self.frecency = Box::new(frecency);
+        self
+    }
+
+    /// Hides items whose best fuzzy match is below this score, between
+    /// 0 and 1.
+    ///
+    /// The best match is taken from the title, description, keywords
+    /// and [match fields](ListItem::match_fields) that have a non-zero
+    /// weight. Nothing is hidden if the query is
+    /// empty.
+    ///
+    /// Defaults to the user's `min_score` config, or 0 if they haven't
+    /// set one.
+    #[must_use = "builder method consumes self"]
+    pub fn min_score(mut self, min_score: f32) -> Self {
+        self.min_score = min_score.clamp(0.0, 1.0);
+        self
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use time::{Duration, OffsetDateTime};
+
+    use parking_lot::Mutex;
+
+    use super::{
+        rank, rank_with, set_default_algorithm, set_default_min_score, Algorithm, Weights,
+    };
+    use crate::ListItem;
+
+    /// Held by tests that set the user's defaults, so that other tests
+    /// don't see them.
+    pub(crate) static DEFAULTS: Mutex<()> = Mutex::new(());
+
+    /// Creates weights without seeing the defaults set by other tests.
+    fn weights(new: fn() -> Weights) -> Weights {
+        let _defaults = DEFAULTS.lock();
+        new()
+    }
+
+    fn titles(items: &[ListItem]) -> Vec<&str> {
+        items.iter().map(|item| item.title.as_str()).collect()
+    }
+
+    #[test]
+    #[expect(clippy::float_cmp, reason = "the values are copied, not computed")]
+    fn defaults_are_replaced_when_reinitialised() {
+        let _defaults = DEFAULTS.lock();
+        set_default_min_score(Some(0.8));
+        assert_eq!(Weights::without_history().min_score, 0.8);
+        // removed from the config before it was reloaded
+        set_default_min_score(None);
+        assert_eq!(Weights::without_history().min_score, 0.0);
+    }
+
+    #[test]
+    fn configured_algorithm_is_used() {
+        let _defaults = DEFAULTS.lock();
+        let items = [ListItem::new("Firefox")];
+        let ranked = || {
+            rank_with(
+                "firefxo",
+                &items,
+                &Weights::without_history(),
+                &HashMap::new(),
+                &[],
+                OffsetDateTime::now_utc(),
+            )
+        };
+
+        set_default_algorithm(Some(Algorithm::JaroWinkler));
+        assert_eq!(Weights::without_history().algorithm, Algorithm::JaroWinkler);
+        // only jaro-winkler tolerates the typo
+        assert_eq!(titles(&ranked()), ["Firefox"]);
+        set_default_algorithm(None);
+        assert!(ranked().is_empty());
+    }
+
+    #[tokio::test]
+    async fn matches_keywords() {
+        let items = [
+            ListItem::new("Settings").with_keywords(["preferences", "options"]),
+            ListItem::new("Terminal"),
+        ];
+
+        let ranked = rank("prefs", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Settings"]);
+    }
+
+    #[tokio::test]
+    async fn title_ranks_above_keyword() {
+        let items = [
+            ListItem::new("System Settings").with_keywords(["terminal"]),
+            ListItem::new("Terminal"),
+        ];
+
+        let ranked = rank("terminal", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Terminal", "System Settings"]);
+    }
+
+    #[tokio::test]
+    async fn matches_weighted_fields() {
+        let items = [
+            ListItem::new("Bob Tables"),
+            ListItem::new("Alice").with_match_field("bobby@example.com", 2.0),
+            ListItem::new("Carol").with_match_field("carol@example.com", 2.0),
+        ];
+
+        let ranked = rank("bob", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Alice", "Bob Tables"]);
+
+        // a low weight ranks the field's match below a title match
+        let items = [
+            ListItem::new("Alice").with_match_field("bobby@example.com", 0.1),
+            ListItem::new("Bobby"),
+        ];
+        let ranked = rank("bobby", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Bobby", "Alice"]);
+    }
+
+    #[tokio::test]
+    async fn matches_every_word() {
+        let items = [
+            ListItem::new("Open Terminal Here"),
+            ListItem::new("Terminal Open"),
+        ];
+
+        let ranked = rank("open term", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Open Terminal Here"]);
+    }
+
+    #[tokio::test]
+    async fn min_score_hides_weak_matches() {
+        let items = [
+            ListItem::new("Firefox"),
+            ListItem::new("Fast Image Resizer"),
+        ];
+
+        let ranked = rank("fire", &items, weights(Weights::without_history)).await;
+        assert_eq!(titles(&ranked), ["Firefox", "Fast Image Resizer"]);
+
+        let ranked = rank(
+            "fire",
+            &items,
+            weights(Weights::without_history).min_score(0.8),
+        )
+        .await;
+        assert_eq!(titles(&ranked), ["Firefox"]);
+    }
+
+    #[tokio::test]
+    async fn pinned_items_sort_first() {
+        let items = [
+            ListItem::new("Firefox"),
+            ListItem::new("Fast Image Resizer"),
+            ListItem::new("Fire Alarm").as_pinned(),
+        ];
+        let pins = ["Fast Image Resizer".to_owned()];
+
+        let ranked = rank_with(
+            "fire",
+            &items,
+            &weights(Weights::without_history),
+            &HashMap::new(),
+            &pins,
+            OffsetDateTime::now_utc(),
+        );
+        assert_eq!(
+            titles(&ranked),
+            ["Fire Alarm", "Fast Image Resizer", "Firefox"]
+        );
+
+        // pinned items are still hidden if they don't match
+        let ranked = rank_with(
+            "zzz",
+            &items,
+            &weights(Weights::without_history),
+            &HashMap::new(),
+            &pins,
+            OffsetDateTime::now_utc(),
+        );
+        assert!(ranked.is_empty());
+    }
+
+    #[test]
+    fn history_is_weighted_like_raw_skim_scores() {
+        let now = OffsetDateTime::now_utc();
+        let items = [
+            ListItem::new("Firefox"),
+            ListItem::new("Fierce Fox"),
+            ListItem::new("Wildfire"),
+        ];
+        let activations = HashMap::from([
+            ("Fierce Fox".to_owned(), (100, now - Duration::hours(1))),
+            ("Wildfire".to_owned(), (3, now - Duration::minutes(5))),
+        ]);
+
+        // the ranking that used raw skim scores before they were normalised
+        #[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+        let mut expected: Vec<_> = items
+            .iter()
+            .map(|item| {
+                let fuzzy = sublime_fuzzy::best_match("fire", &item.title)
+                    .map_or(0.0, |m| m.score() as f32);
+                let (freq, elapsed_secs) = activations
+                    .get(&item.title)
+                    .map_or((0, i64::MAX), |(freq, time)| {
+                        (*freq, (now - *time).whole_seconds())
+                    });
+                let recency = 1.0 / (elapsed_secs / 1000).saturating_add(20) as f32;
+                let freq_score = freq as f32 * 50.0 * recency * (fuzzy / 500.0 + 0.1);
+                (fuzzy + freq_score + recency * 500.0, item.title.as_str())
+            })
+            .collect();
+        expected.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+        let expected: Vec<_> = expected.into_iter().map(|(_, title)| title).collect();
+
+        let ranked = rank_with(
+            "fire",
+            &items,
+            &weights(Weights::with_history),
+            &activations,
+            &[],
+            now,
+        );
+        assert_eq!(titles(&ranked), expected);
+        assert_eq!(titles(&ranked), ["Firefox", "Wildfire", "Fierce Fox"]);
+    }
+
+    #[test]
+    fn pins_are_sorted_in_order() {
+        let items = [
+            ListItem::new("Firefox"),
+            ListItem::new("Fire Alarm"),
+            ListItem::new("Fireplace"),
+        ];
+        let ranked_with_pins = |pins: &[&str]| {
+            rank_with(
+                "fire",
+                &items,
+                &weights(Weights::without_history),
+                &HashMap::new(),
+                &pins.iter().map(|&pin| pin.to_owned()).collect::<Vec<_>>(),
+                OffsetDateTime::now_utc(),
+            )
+        };
+
+        let ranked = ranked_with_pins(&["Fireplace", "Firefox"]);
+        assert_eq!(titles(&ranked), ["Fireplace", "Firefox", "Fire Alarm"]);
+        assert!(ranked[0].pinned && ranked[1].pinned && !ranked[2].pinned);
+
+        let ranked = ranked_with_pins(&["Firefox", "Fireplace"]);
+        assert_eq!(titles(&ranked), ["Firefox", "Fireplace", "Fire Alarm"]);
+    }
 }
@@ -0,0 +1,413 @@
+//! Fuzzy matching of a query against some text.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{ensure, Result};
+use az::SaturatingAs as _;
+use parking_lot::Mutex;
+
+/// A way to fuzzy match a query against some text.
+///
+/// All algorithms are case insensitive and return a score between
+/// 0 and 1, where 1 is the best possible match. Text that doesn't match
+/// the query at all has no score.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Algorithm {
+    /// Skim-style matching, which rewards consecutive matches and matches
+    /// at the start of words.
+    ///
+    /// The characters of the query must appear in order in the text.
+    /// This gives the best quality results.
+    #[default]
+    Skim,
+    /// Simple subsequence matching.
+    ///
+    /// The characters of the query must appear in order in the text.
+    /// Matches are better when they are closer together and cover more
+    /// of the text. This is faster than [`Algorithm::Skim`].
+    Subsequence,
+    /// Jaro-Winkler similarity, which rewards matching prefixes.
+    ///
+    /// This compares the query to the whole text so it tolerates typos,
+    /// but works poorly for short queries against long text. Text with a
+    /// similarity below [`Algorithm::JARO_WINKLER_THRESHOLD`] does not match.
+    JaroWinkler,
+}
+
+/// Algorithm from the user's config, used unless the plugin sets one.
+///
+/// Replaced each time the plugin is initialised, so that reloading the
+/// config takes effect.
+static DEFAULT_ALGORITHM: Mutex<Option<Algorithm>> = Mutex::new(None);
+
+pub(crate) fn set_default_algorithm(algorithm: Option<Algorithm>) {
+    *DEFAULT_ALGORITHM.lock() = algorithm;
+}
+
+impl Algorithm {
+    /// The minimum similarity for [`Algorithm::JaroWinkler`] to consider
+    /// the text as a match.
+    pub const JARO_WINKLER_THRESHOLD: f32 = 0.7;
+
+    /// The user's algorithm, or [`Algorithm::default`].
+    pub(crate) fn user_default() -> Self {
+        DEFAULT_ALGORITHM.lock().unwrap_or_default()
+    }
+}
+
+/// Bonuses and penalties of [`Algorithm::Skim`], which change how much
+/// consecutive matches are preferred over matches at the start of words.
+///
+/// Defaults to the user's `match_scoring` config, or
+/// [`ScoreConfig::default`] if they haven't set one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreConfig {
+    consecutive_bonus: u32,
+    word_start_bonus: u32,
+    gap_penalty: u32,
+}
+
+/// Scoring from the user's config, used unless the plugin sets one.
+///
+/// Replaced each time the plugin is initialised, so that reloading the
+/// config takes effect.
+static DEFAULT_SCORE_CONFIG: Mutex<Option<ScoreConfig>> = Mutex::new(None);
+
+pub(crate) fn set_default_score_config(config: Option<ScoreConfig>) {
+    *DEFAULT_SCORE_CONFIG.lock() = config;
+}
+
+impl ScoreConfig {
+    /// The largest bonus that can be given.
+    pub const MAX_BONUS: u32 = 500;
+    /// The largest penalty that can be given.
+    pub const MAX_PENALTY: u32 = 100;
+
+    /// Scoring with these bonuses and penalty.
+    ///
+    /// - `consecutive_bonus` is added for each character that directly
+    ///   follows the previous match, multiplied by the length of the run.
+    /// - `word_start_bonus` is added for each character at the start
+    ///   of a word.
+    /// - `gap_penalty` is subtracted for each character between two
+    ///   matched characters.
+    ///
+    /// # Errors
+    /// If a bonus is over [`ScoreConfig::MAX_BONUS`] or the penalty is
+    /// over [`ScoreConfig::MAX_PENALTY`].
+    pub fn new(consecutive_bonus: u32, word_start_bonus: u32, gap_penalty: u32) -> Result<Self> {
+        for (name, bonus) in [
+            ("consecutive bonus", consecutive_bonus),
+            ("word start bonus", word_start_bonus),
+        ] {
+            ensure!(
+                bonus <= Self::MAX_BONUS,
+                "{name} of {bonus} is over the maximum of {}",
+                Self::MAX_BONUS
+            );
+        }
+        ensure!(
+            gap_penalty <= Self::MAX_PENALTY,
+            "gap penalty of {gap_penalty} is over the maximum of {}",
+            Self::MAX_PENALTY
+        );
+        Ok(Self {
+            consecutive_bonus,
+            word_start_bonus,
+            gap_penalty,
+        })
+    }
+
+    /// The user's scoring, or [`ScoreConfig::default`].
+    pub(crate) fn user_default() -> Self {
+        DEFAULT_SCORE_CONFIG.lock().unwrap_or_default()
+    }
+
+    fn to_scoring(self) -> sublime_fuzzy::Scoring {
+        sublime_fuzzy::Scoring {
+            bonus_consecutive: self.consecutive_bonus.saturating_as(),
+            bonus_word_start: self.word_start_bonus.saturating_as(),
+            penalty_distance: self.gap_penalty.saturating_as(),
+            ..sublime_fuzzy::Scoring::default()
+        }
+    }
+}
+
+impl Default for ScoreConfig {
+    /// A consecutive bonus of 8, word start bonus of 72 and gap
+    /// penalty of 4.
+    fn default() -> Self {
+        Self {
+            consecutive_bonus: 8,
+            word_start_bonus: 72,
+            gap_penalty: 4,
+        }
+    }
+}
+
+/// Scores how well `text` matches the `query` with the given algorithm.
+///
+/// Returns a score between 0 and 1, or [`None`] if the text does not match.
+/// An empty query does not match anything.
+///
+/// Scores from every algorithm are on the same scale, so they can be
+/// compared or combined. Text that is the same as the query (ignoring case)
+/// always scores 1. Each algorithm normalises its score as follows:
+/// - [`Algorithm::Skim`]: the raw skim score divided by the score of
+///   the query matched against itself.
+/// - [`Algorithm::Subsequence`]: the average of the query length divided
+///   by the length of the matched span, and the query length divided by
+///   the text length.
+/// - [`Algorithm::JaroWinkler`]: the Jaro-Winkler similarity, which is
+///   already between 0 and 1.
+///
+/// [`Algorithm::Skim`] uses the user's [`ScoreConfig`].
+pub fn score_with(algorithm: Algorithm, query: &str, text: &str) -> Option<f32> {
+    score_with_config(algorithm, ScoreConfig::user_default(), query, text)
+}
+
+/// [`score_with`], with the scoring used by [`Algorithm::Skim`].
+pub(crate) fn score_with_config(
+    algorithm: Algorithm,
+    config: ScoreConfig,
+    query: &str,
+    text: &str,
+) -> Option<f32> {
+    if query.is_empty() {
+        return None;
+    }
+
+    match algorithm {
+        Algorithm::Skim => skim(config, query, text),
+        Algorithm::Subsequence => subsequence(query, text),
+        Algorithm::JaroWinkler => jaro_winkler(query, text),
+    }
+}
+
+/// Scores how well `candidate` matches the `query`, from 0 to 1.
+///
+/// Unlike [`score_with`], this also accounts for how much of the
+/// candidate the query covers, so scores of candidates from different
+/// sources can be compared. The score is
+///
+/// ```text
+/// clamp(raw / perfect, 0, 1) * query length / candidate length
+/// ```
+///
+/// where `raw` is the skim score of the candidate, `perfect` is the skim
+/// score of the query matched against itself, and lengths are counted
+/// in characters. A candidate that is the same as the query (ignoring
+/// case) scores 1.0, and one that doesn't match scores 0.0, as does
+/// every candidate for an empty query.
+///
+/// This uses the user's [`ScoreConfig`].
+#[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+pub fn normalized_score(query: &str, candidate: &str) -> f64 {
+    if query.is_empty() {
+        return 0.0;
+    }
+    let Some(score) = skim(ScoreConfig::user_default(), query, candidate) else {
+        return 0.0;
+    };
+    let query_len = query.chars().flat_map(char::to_lowercase).count() as f64;
+    let candidate_len = candidate.chars().flat_map(char::to_lowercase).count() as f64;
+    (f64::from(score) * query_len / candidate_len).clamp(0.0, 1.0)
+}
+
+#[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+fn skim(config: ScoreConfig, query: &str, text: &str) -> Option<f32> {
+    let query = query.to_lowercase();
+    let scoring = config.to_scoring();
+    let best_match = |text: &str| {
+        sublime_fuzzy::FuzzySearch::new(&query, text)
+            .score_with(&scoring)
+            .best_match()
+    };
+    let score = best_match(&text.to_lowercase())?.score();
+    // normalise by the score of a perfect match
+    let best = best_match(&query)?.score();
+    Some((score as f32 / best.max(1) as f32).clamp(0.0, 1.0))
+}
+
+/// The raw skim score of the query matched against itself.
+///
+/// Multiplying a normalised score by this gives roughly the raw skim
+/// score, which [`rank`](super::rank) has always weighted the usage
+/// history against.
+#[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+pub(crate) fn skim_scale(config: ScoreConfig, query: &str) -> f32 {
+    let query = query.to_lowercase();
+    sublime_fuzzy::FuzzySearch::new(&query, &query)
+        .score_with(&config.to_scoring())
+        .best_match()
+        .map_or(1.0, |m| m.score().max(1) as f32)
+}
+
+#[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+fn subsequence(query: &str, text: &str) -> Option<f32> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+
+    // greedily find the first match, then the shortest span ending there
+    let mut query_chars = query.iter().peekable();
+    let end = text.iter().position(|c| {
+        query_chars.next_if_eq(&c);
+        query_chars.peek().is_none()
+    })?;
+    let mut query_chars = query.iter().rev().peekable();
+    let start = text[..=end].iter().rposition(|c| {
+        query_chars.next_if_eq(&c);
+        query_chars.peek().is_none()
+    })?;
+
+    let span = (end - start + 1) as f32;
+    let len = query.len() as f32;
+    Some(f32::midpoint(len / span, len / text.len() as f32))
+}
+
+#[expect(clippy::cast_possible_truncation, reason = "precision isn't needed")]
+fn jaro_winkler(query: &str, text: &str) -> Option<f32> {
+    let similarity = strsim::jaro_winkler(&query.to_lowercase(), &text.to_lowercase()) as f32;
+    (similarity >= Algorithm::JARO_WINKLER_THRESHOLD).then_some(similarity)
+}
+
+impl FromStr for Algorithm {
+    type Err = ParseAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "skim" => Self::Skim,
+            "subsequence" => Self::Subsequence,
+            "jaro-winkler" => Self::JaroWinkler,
+            _ => return Err(ParseAlgorithmError(s.to_owned())),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseAlgorithmError(String);
+
+impl fmt::Display for ParseAlgorithmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown matching algorithm {:?}: expected one of \"skim\", \"subsequence\" or \"jaro-winkler\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseAlgorithmError {}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalized_score, score_with, score_with_config, Algorithm, ScoreConfig};
+
+    const TEXTS: [&str; 5] = [
+        "Firefox",
+        "File Manager",
+        "Settings: Firewall",
+        "Fierce Fox",
+        "Terminal",
+    ];
+
+    /// Returns the matching texts ordered from highest to lowest score.
+    fn order(algorithm: Algorithm, query: &str) -> Vec<&'static str> {
+        let mut scored: Vec<_> = TEXTS
+            .into_iter()
+            .filter_map(|text| Some((score_with(algorithm, query, text)?, text)))
+            .collect();
+        scored.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+        scored.into_iter().map(|(_, text)| text).collect()
+    }
+
+    #[test]
+    fn skim() {
+        assert_eq!(
+            order(Algorithm::Skim, "fire"),
+            ["Firefox", "Settings: Firewall", "Fierce Fox"]
+        );
+    }
+
+    #[test]
+    fn subsequence() {
+        assert_eq!(
+            order(Algorithm::Subsequence, "fire"),
+            ["Firefox", "Settings: Firewall", "Fierce Fox"]
+        );
+        assert_eq!(
+            order(Algorithm::Subsequence, "fe"),
+            [
+                "Fierce Fox",
+                "Firefox",
+                "File Manager",
+                "Settings: Firewall"
+            ]
+        );
+    }
+
+    #[test]
+    fn jaro_winkler() {
+        assert_eq!(
+            order(Algorithm::JaroWinkler, "firefxo"),
+            ["Firefox", "Fierce Fox"]
+        );
+    }
+
+    #[test]
+    fn scores_are_normalised() {
+        for algorithm in [
+            Algorithm::Skim,
+            Algorithm::Subsequence,
+            Algorithm::JaroWinkler,
+        ] {
+            assert_eq!(score_with(algorithm, "", "Firefox"), None);
+            assert_eq!(score_with(algorithm, "xyz", "Firefox"), None);
+            for query in ["fire", "firefox", "f", "fm"] {
+                for text in TEXTS {
+                    if let Some(score) = score_with(algorithm, query, text) {
+                        assert!((0.0..=1.0).contains(&score), "{algorithm:?} gave {score}");
+                    }
+                }
+            }
+            let exact = score_with(algorithm, "firefox", "Firefox").unwrap();
+            assert!((exact - 1.0).abs() < 1e-6, "{algorithm:?} gave {exact}");
+        }
+    }
+
+    #[test]
+    fn consecutive_bonus_changes_order() {
+        // word starts are matched in the first, consecutive letters in the second
+        let scores = |config| {
+            let score = |text| score_with_config(Algorithm::Skim, config, "ab", text).unwrap();
+            (score("Alpha Beta"), score("Crab"))
+        };
+
+        let (word_starts, consecutive) = scores(ScoreConfig::default());
+        assert!(word_starts > consecutive);
+        let (word_starts, consecutive) = scores(ScoreConfig::new(400, 72, 4).unwrap());
+        assert!(word_starts < consecutive);
+    }
+
+    #[test]
+    fn score_config_is_limited() {
+        assert!(ScoreConfig::new(ScoreConfig::MAX_BONUS, 0, ScoreConfig::MAX_PENALTY).is_ok());
+        assert!(ScoreConfig::new(ScoreConfig::MAX_BONUS + 1, 72, 4).is_err());
+        assert!(ScoreConfig::new(8, ScoreConfig::MAX_BONUS + 1, 4).is_err());
+        assert!(ScoreConfig::new(8, 72, ScoreConfig::MAX_PENALTY + 1).is_err());
+    }
+
+    #[test]
+    fn normalized_scores() {
+        assert!((normalized_score("firefox", "Firefox") - 1.0).abs() < 1e-9);
+        assert!((normalized_score("Ünïcode", "üNÏCODE") - 1.0).abs() < 1e-9);
+        assert!(normalized_score("xyz", "Firefox").abs() < 1e-9);
+        assert!(normalized_score("", "Firefox").abs() < 1e-9);
+
+        // covering more of the candidate scores higher
+        let firefox = normalized_score("fire", "Firefox");
+        let firewall = normalized_score("fire", "Settings: Firewall");
+        assert!(0.0 < firewall && firewall < firefox && firefox < 1.0);
+    }
+}
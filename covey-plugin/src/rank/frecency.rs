@@ -0,0 +1,169 @@
+//! Scoring of list items based on their usage history.
+
+use std::{fmt, str::FromStr, time::Duration};
+
+use parking_lot::Mutex;
+use time::OffsetDateTime;
+
+/// A way to combine how often and how recently an item was used into
+/// a single score.
+///
+/// Higher scores are ranked higher. Items that have never been activated
+/// are not scored.
+pub trait Frecency {
+    fn score(&self, frequency: u64, last_use: OffsetDateTime, now: OffsetDateTime) -> f64;
+}
+
+/// The frequency, decayed exponentially by the time since the last use.
+///
+/// After every `half_life`, the score halves.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialDecay {
+    pub half_life: Duration,
+}
+
+impl Default for ExponentialDecay {
+    fn default() -> Self {
+        Self {
+            half_life: Duration::from_hours(24 * 7),
+        }
+    }
+}
+
+impl Frecency for ExponentialDecay {
+    #[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+    fn score(&self, frequency: u64, last_use: OffsetDateTime, now: OffsetDateTime) -> f64 {
+        let elapsed = (now - last_use).as_seconds_f64().max(0.0);
+        let half_lives = elapsed / self.half_life.as_secs_f64().max(1.0);
+        frequency as f64 * 0.5_f64.powf(half_lives)
+    }
+}
+
+/// Built-in frecency strategies.
+///
+/// This can be parsed from a string (with [`FromStr`]) so that it can be
+/// selected in a plugin's configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FrecencyStrategy {
+    /// The frequency divided by the time since the last use, plus a
+    /// constant so that recent items don't dominate.
+    #[default]
+    Classic,
+    /// See [`ExponentialDecay`] with the default half life of a week.
+    ExponentialDecay,
+    /// Only consider the number of activations, ignoring when they happened.
+    Frequency,
+    /// Only consider the time since the last activation, decaying with
+    /// a half life of an hour.
+    Recency,
+}
+
+/// Strategy from the user's config, used unless the plugin sets one.
+///
+/// Replaced each time the plugin is initialised, so that reloading the
+/// config takes effect.
+static DEFAULT_STRATEGY: Mutex<Option<FrecencyStrategy>> = Mutex::new(None);
+
+pub(crate) fn set_default_frecency(strategy: Option<FrecencyStrategy>) {
+    *DEFAULT_STRATEGY.lock() = strategy;
+}
+
+impl FrecencyStrategy {
+    /// The user's strategy, or [`FrecencyStrategy::default`].
+    pub(crate) fn user_default() -> Self {
+        DEFAULT_STRATEGY.lock().unwrap_or_default()
+    }
+}
+
+impl Frecency for FrecencyStrategy {
+    #[expect(clippy::cast_precision_loss, reason = "precision isn't needed")]
+    fn score(&self, frequency: u64, last_use: OffsetDateTime, now: OffsetDateTime) -> f64 {
+        match self {
+            Self::Classic => {
+                let elapsed = (now - last_use).whole_seconds().max(0) as f64;
+                frequency as f64 / ((elapsed / 1000.0).floor() + 20.0)
+            }
+            Self::ExponentialDecay => ExponentialDecay::default().score(frequency, last_use, now),
+            Self::Frequency => frequency as f64,
+            Self::Recency => ExponentialDecay {
+                half_life: Duration::from_hours(1),
+            }
+            .score(1, last_use, now),
+        }
+    }
+}
+
+impl FromStr for FrecencyStrategy {
+    type Err = ParseFrecencyStrategyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "classic" => Self::Classic,
+            "exponential-decay" => Self::ExponentialDecay,
+            "frequency" => Self::Frequency,
+            "recency" => Self::Recency,
+            _ => return Err(ParseFrecencyStrategyError(s.to_owned())),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseFrecencyStrategyError(String);
+
+impl fmt::Display for ParseFrecencyStrategyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown frecency strategy {:?}: expected one of \"classic\", \"exponential-decay\", \"frequency\" or \"recency\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseFrecencyStrategyError {}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::{Frecency, FrecencyStrategy};
+
+    /// Returns the titles ordered from highest to lowest score.
+    fn order(strategy: FrecencyStrategy) -> Vec<&'static str> {
+        let now = OffsetDateTime::now_utc();
+        let history = [
+            ("old favourite", 40, now - Duration::days(30)),
+            ("just used", 2, now - Duration::minutes(1)),
+        ];
+
+        let mut scored: Vec<_> = history
+            .into_iter()
+            .map(|(title, freq, last_use)| (strategy.score(freq, last_use, now), title))
+            .collect();
+        scored.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+        scored.into_iter().map(|(_, title)| title).collect()
+    }
+
+    #[test]
+    fn frequency_and_recency_order_differently() {
+        assert_eq!(
+            order(FrecencyStrategy::Frequency),
+            ["old favourite", "just used"]
+        );
+        assert_eq!(
+            order(FrecencyStrategy::Recency),
+            ["just used", "old favourite"]
+        );
+    }
+
+    #[test]
+    fn decay_halves_every_half_life() {
+        let now = OffsetDateTime::now_utc();
+        let score = super::ExponentialDecay {
+            half_life: std::time::Duration::from_mins(1),
+        }
+        .score(8, now - Duration::minutes(2), now);
+        assert!((score - 2.0).abs() < 1e-9);
+    }
+}
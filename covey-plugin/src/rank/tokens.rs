@@ -0,0 +1,176 @@
+//! Matching queries with multiple words.
+
+use super::{algorithm::score_with_config, Algorithm, ScoreConfig};
+
+/// A part of the query that must match separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    /// A single word, which is fuzzy matched.
+    Word(&'a str),
+    /// Text in quotes, which must appear exactly (ignoring case).
+    Phrase(&'a str),
+}
+
+/// Splits the query into whitespace-separated words and quoted phrases.
+///
+/// An unclosed quote continues to the end of the query.
+fn tokenize(query: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = query;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            if end > 0 {
+                tokens.push(Token::Phrase(&quoted[..end]));
+            }
+            rest = quoted.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..end]));
+            rest = &rest[end..];
+        }
+    }
+
+    tokens
+}
+
+/// Scores how well `text` matches every word of the `query`, with the
+/// default [`Algorithm`].
+///
+/// See [`score_tokens_with`] for details.
+pub fn score_tokens(query: &str, text: &str) -> Option<f32> {
+    score_tokens_with(Algorithm::default(), query, text)
+}
+
+/// Scores how well `text` matches every word of the `query`.
+///
+/// The query is split by whitespace, and text in quotes is kept together
+/// as a phrase. Every word must fuzzy match, and every phrase must appear
+/// exactly (ignoring case), in the same order as the query. For example,
+/// `foo bar` matches "foo something bar" but not "barfoo".
+///
+/// Returns the sum of the scores of each word and phrase, which are each
+/// between 0 and 1. A query with a single word is scored the same as
+/// [`score_with`].
+///
+/// Words are matched against the rest of the text after the previous
+/// word, so this works best with [`Algorithm::Skim`] or
+/// [`Algorithm::Subsequence`].
+///
+/// [`Algorithm::Skim`] uses the user's [`ScoreConfig`].
+pub fn score_tokens_with(algorithm: Algorithm, query: &str, text: &str) -> Option<f32> {
+    score_tokens_with_config(algorithm, ScoreConfig::user_default(), query, text)
+}
+
+/// The number of words and phrases in the query, or 1 if it has none.
+pub(crate) fn token_count(query: &str) -> usize {
+    tokenize(query).len().max(1)
+}
+
+/// [`score_tokens_with`], with the scoring used by [`Algorithm::Skim`].
+pub(crate) fn score_tokens_with_config(
+    algorithm: Algorithm,
+    config: ScoreConfig,
+    query: &str,
+    text: &str,
+) -> Option<f32> {
+    let tokens = tokenize(query);
+    if let [Token::Word(word)] = tokens[..] {
+        return score_with_config(algorithm, config, word, text);
+    }
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let text = text.to_lowercase();
+    let mut rest = text.as_str();
+    let mut total = 0.0;
+
+    for token in &tokens {
+        let (score, end) = match token {
+            Token::Word(word) => {
+                let end = subsequence_end(&word.to_lowercase(), rest)?;
+                (score_with_config(algorithm, config, word, rest)?, end)
+            }
+            Token::Phrase(phrase) => {
+                let phrase = phrase.to_lowercase();
+                let start = rest.find(&phrase)?;
+                (1.0, start + phrase.len())
+            }
+        };
+        total += score;
+        rest = &rest[end..];
+    }
+
+    Some(total)
+}
+
+/// Finds the byte index just after the first place that `word` appears
+/// as a subsequence of `text`.
+fn subsequence_end(word: &str, text: &str) -> Option<usize> {
+    let mut chars = word.chars().peekable();
+    text.char_indices().find_map(|(i, c)| {
+        chars.next_if_eq(&c);
+        chars.peek().is_none().then_some(i + c.len_utf8())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{score_tokens, token_count, tokenize, Token};
+    use crate::rank::{score_with, Algorithm};
+
+    #[test]
+    fn tokenizes_phrases() {
+        assert_eq!(
+            tokenize(r#"  foo "bar baz"  qux "unclosed  "#),
+            [
+                Token::Word("foo"),
+                Token::Phrase("bar baz"),
+                Token::Word("qux"),
+                Token::Phrase("unclosed  "),
+            ]
+        );
+        assert!(tokenize(r#"  "" "#).is_empty());
+    }
+
+    #[test]
+    fn words_match_in_order() {
+        assert_eq!(score_tokens("foo bar", "barfoo"), None);
+        assert!(score_tokens("foo bar", "foo something bar").is_some());
+        assert!(score_tokens("FOO bar", "Foo Something Bar").is_some());
+    }
+
+    #[test]
+    fn phrases_match_exactly() {
+        assert!(score_tokens(r#""foo bar""#, "a foo bar b").is_some());
+        assert_eq!(score_tokens(r#""foo bar""#, "foo x bar"), None);
+    }
+
+    #[test]
+    fn scores_are_summed() {
+        assert_eq!(score_tokens("foo bar", "foo bar"), Some(2.0));
+        assert_eq!(token_count(r#"foo "bar baz""#), 2);
+        assert_eq!(token_count(""), 1);
+
+        // words after the first are matched against the rest of the text
+        let bar = score_with(Algorithm::default(), "bar", " xbxaxr").unwrap();
+        assert!(bar < 1.0);
+        assert_eq!(score_tokens(r#""foo" bar"#, "foo xbxaxr"), Some(1.0 + bar));
+    }
+
+    #[test]
+    fn single_word_is_unchanged() {
+        assert_eq!(
+            score_tokens("fire", "Firefox"),
+            score_with(Algorithm::default(), "fire", "Firefox")
+        );
+        assert_eq!(score_tokens("  ", "Firefox"), None);
+    }
+}
@@ -0,0 +1,46 @@
+//! The text the user has selected in other apps.
+
+use std::sync::Mutex;
+
+use anyhow::bail;
+
+use crate::Result;
+
+/// The primary selection sent with the latest query, if any.
+static PRIMARY_SELECTION: Mutex<Option<String>> = Mutex::new(None);
+
+/// The text that was selected in other apps when the menu was shown.
+///
+/// This is the primary selection on Linux, falling back to the clipboard
+/// where there is none (like on macOS). It is empty if nothing is selected.
+///
+/// # Errors
+/// If the plugin doesn't return `true` from
+/// [`Plugin::uses_primary_selection`](crate::Plugin::uses_primary_selection),
+/// as covey doesn't send the selection to it.
+pub fn primary_selection() -> Result<String> {
+    match &*PRIMARY_SELECTION.lock().unwrap() {
+        Some(selection) => Ok(selection.clone()),
+        None => bail!("the primary selection was not sent to this plugin"),
+    }
+}
+
+/// Stores the selection sent with a query.
+pub(crate) fn set_primary_selection(selection: Option<String>) {
+    *PRIMARY_SELECTION.lock().unwrap() = selection;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_must_be_sent() {
+        set_primary_selection(None);
+        assert!(primary_selection().is_err());
+        set_primary_selection(Some(String::new()));
+        assert_eq!(primary_selection().unwrap(), "");
+        set_primary_selection(Some(String::from("hello")));
+        assert_eq!(primary_selection().unwrap(), "hello");
+    }
+}
@@ -1,12 +1,51 @@
-use std::process;
+use std::{num::NonZeroUsize, process};
 
-use tokio::net::TcpListener;
+use tokio::{
+    net::TcpListener,
+    runtime::{Builder, Runtime},
+};
 use tonic::transport::Server;
 
-use crate::{plugin_lock::PluginLock, proto::plugin_server::PluginServer, Plugin};
+use crate::{
+    plugin_lock::PluginLock, proto::plugin_server::PluginServer, sql, Plugin,
+    DEFAULT_MAX_MESSAGE_BYTES, MAX_MESSAGE_BYTES_VAR,
+};
+
+/// Reads the maximum message size that covey passes to the plugin.
+fn max_message_bytes() -> usize {
+    std::env::var(MAX_MESSAGE_BYTES_VAR)
+        .ok()
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES as usize)
+}
+
+/// Builds the runtime that the plugin runs on.
+///
+/// covey passes the number of worker threads if the user configured it.
+/// `COVEY_THREAD_NAME` can also be set to name the threads, which is
+/// useful for profiling. Tokio's defaults are used for anything missing.
+fn build_runtime() -> std::io::Result<Runtime> {
+    let worker_threads = std::env::var("COVEY_WORKER_THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok());
+    let thread_name = std::env::var("COVEY_THREAD_NAME").ok();
+    runtime_builder(worker_threads, thread_name).build()
+}
+
+fn runtime_builder(worker_threads: Option<NonZeroUsize>, thread_name: Option<String>) -> Builder {
+    let mut builder = Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(threads) = worker_threads {
+        builder.worker_threads(threads.get());
+    }
+    if let Some(name) = thread_name {
+        builder.thread_name(name);
+    }
+    builder
+}
 
 pub fn run_server<T: Plugin>() -> ! {
-    let result = tokio::runtime::Runtime::new()
+    let result = build_runtime()
         .map_err(|e| anyhow::anyhow!(e))
         .and_then(|rt| {
             rt.block_on(async {
@@ -18,11 +57,22 @@ pub fn run_server<T: Plugin>() -> ! {
                 // print port for covey to read
                 println!("{port}");
 
+                let max_message_bytes = max_message_bytes();
                 Server::builder()
-                    .add_service(PluginServer::new(PluginLock::<T>::new_empty()))
-                    .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+                    .add_service(
+                        PluginServer::new(PluginLock::<T>::new_empty())
+                            .max_decoding_message_size(max_message_bytes)
+                            .max_encoding_message_size(max_message_bytes),
+                    )
+                    .serve_with_incoming_shutdown(
+                        tokio_stream::wrappers::TcpListenerStream::new(listener),
+                        shutdown_signal(),
+                    )
                     .await?;
 
+                // make sure every activation is recorded before exiting
+                sql::flush().await;
+
                 Ok(())
             })
         });
@@ -36,6 +86,28 @@ pub fn run_server<T: Plugin>() -> ! {
     }
 }
 
+/// Resolves when the process is asked to stop.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => _ = tokio::signal::ctrl_c().await,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        _ = tokio::signal::ctrl_c().await;
+    }
+}
+
 fn print_error(e: &anyhow::Error) {
     let err_string = e
         .chain()
@@ -44,3 +116,25 @@ fn print_error(e: &anyhow::Error) {
         .join("\n");
     eprintln!("{err_string}");
 }
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::runtime_builder;
+
+    #[test]
+    fn configured_worker_threads() {
+        let runtime = runtime_builder(NonZeroUsize::new(3), Some("plugin-worker".to_owned()))
+            .build()
+            .unwrap();
+        assert_eq!(runtime.metrics().num_workers(), 3);
+
+        let name = runtime.block_on(async {
+            tokio::spawn(async { std::thread::current().name().map(ToOwned::to_owned) })
+                .await
+                .unwrap()
+        });
+        assert_eq!(name.as_deref(), Some("plugin-worker"));
+    }
+}
@@ -0,0 +1,79 @@
+//! Options that the user can configure in covey's settings.
+
+pub use covey_config::manifest::{
+    SchemaBool, SchemaFilePath, SchemaFolderPath, SchemaInt, SchemaList, SchemaMap, SchemaStruct,
+    SchemaText, SchemaType,
+};
+
+use crate::proto;
+
+/// An option in the plugin's configuration, shown as a field in the
+/// settings.
+#[derive(Debug, Clone)]
+pub struct Setting {
+    /// Key of the option in the plugin's configuration.
+    pub id: String,
+    /// Label shown next to the field.
+    pub title: String,
+    pub description: Option<String>,
+    /// The kind of value and its default.
+    ///
+    /// If there is no default, the option is required.
+    pub r#type: SchemaType,
+}
+
+impl Setting {
+    pub fn new(id: impl Into<String>, title: impl Into<String>, r#type: SchemaType) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            description: None,
+            r#type,
+        }
+    }
+
+    #[must_use = "builder method consumes self"]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub(crate) fn into_proto(self) -> proto::SettingsField {
+        proto::SettingsField {
+            id: self.id,
+            title: self.title,
+            description: self.description,
+            type_json: serde_json::to_string(&self.r#type).expect("schema types should serialize"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SchemaInt, SchemaType, Setting};
+
+    #[test]
+    fn type_is_sent_as_json() {
+        let setting = Setting::new(
+            "max-results",
+            "Maximum results",
+            SchemaType::Int(SchemaInt {
+                min: 1,
+                max: 100,
+                default: Some(10),
+            }),
+        )
+        .with_description("Results past this are hidden")
+        .into_proto();
+
+        assert_eq!(setting.id, "max-results");
+        assert_eq!(
+            setting.description.as_deref(),
+            Some("Results past this are hidden")
+        );
+        assert_eq!(
+            setting.type_json,
+            r#"{"int":{"min":1,"max":100,"default":10}}"#
+        );
+    }
+}
@@ -1,21 +1,55 @@
-use std::sync::OnceLock;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
 
-use anyhow::Result;
-use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
+use anyhow::{bail, Context, Result};
+use az::SaturatingAs;
+use sqlx::{migrate::MigrateDatabase, Sqlite, SqliteConnection, SqlitePool};
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::rank::{Frecency as _, FrecencyStrategy};
 
 static POOL: OnceLock<SqlitePool> = OnceLock::new();
+static WRITER: OnceLock<BatchWriter> = OnceLock::new();
+static QUERY_LOGGER: OnceLock<QueryLogger> = OnceLock::new();
+static SHOW_RECENT: AtomicBool = AtomicBool::new(false);
+static CLIPBOARD_HISTORY: AtomicBool = AtomicBool::new(false);
+
+/// How long a query must stay unchanged before it is logged.
+const QUERY_LOG_DEBOUNCE: Duration = Duration::from_secs(1);
+/// How often the activation history is pruned, after pruning it once
+/// on startup.
+const PRUNE_INTERVAL: Duration = Duration::from_hours(1);
 
-/// Initialises the sqlite connection and sets the [`POOL`] static.
+/// Initialises the sqlite connection and sets the [`POOL`] static,
+/// then runs the plugin's migrations.
 ///
 /// This must be called before [`self::pool`] is run, or else it will panic.
-pub(crate) async fn init(url: &str) -> Result<()> {
+pub(crate) async fn init(url: &str, migrations: Vec<Migration>) -> Result<()> {
     if Sqlite::database_exists(url).await.unwrap_or(false) {
         Sqlite::create_database(url).await?;
     }
 
     let init_pool = SqlitePool::connect(url).await?;
-    POOL.get_or_init(|| init_pool);
+    let pool = POOL.get_or_init(|| init_pool);
+    create_tables(pool).await?;
+    run_migrations(pool, migrations).await?;
+    WRITER.get_or_init(|| BatchWriter::spawn(pool.clone()));
+
+    Ok(())
+}
 
+/// Creates the tables used by covey itself.
+///
+/// Plugins must not use the table names `activations`, `query_log`,
+/// `recent_items`, `pins`, `clipboard_history` or `_migrations`.
+async fn create_tables(pool: &SqlitePool) -> Result<()> {
     sqlx::query(
         "
         CREATE TABLE IF NOT EXISTS activations (
@@ -24,11 +58,106 @@ pub(crate) async fn init(url: &str) -> Result<()> {
             frequency INTEGER NOT NULL,
             last_use DATETIME NOT NULL
         );
+        CREATE TABLE IF NOT EXISTS query_log (
+            id INTEGER PRIMARY KEY NOT NULL,
+            query TEXT NOT NULL,
+            time DATETIME NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS recent_items (
+            title TEXT PRIMARY KEY NOT NULL,
+            item BLOB NOT NULL,
+            query TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS pins (
+            title TEXT PRIMARY KEY NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS clipboard_history (
+            text TEXT PRIMARY KEY NOT NULL,
+            time DATETIME NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS _migrations (
+            version INTEGER PRIMARY KEY NOT NULL,
+            applied_at DATETIME NOT NULL
+        );
         ",
     )
-    .execute(pool())
+    .execute(pool)
     .await?;
 
+    // pins were unordered before
+    let has_position: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('pins') WHERE name = 'position'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_position {
+        sqlx::query("ALTER TABLE pins ADD COLUMN position INTEGER NOT NULL DEFAULT 0")
+            .execute(pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// A change to the schema of a plugin's database.
+///
+/// Every plugin has its own database, so table names only need to be
+/// unique within the plugin. The tables `activations`, `query_log`,
+/// `recent_items`, `pins`, `clipboard_history` and `_migrations` are
+/// reserved by covey.
+///
+/// See [`Plugin::migrations`](crate::Plugin::migrations).
+#[derive(Debug, Clone)]
+pub struct Migration {
+    version: u32,
+    sql: &'static str,
+}
+
+impl Migration {
+    /// A migration that runs the `sql` statements.
+    ///
+    /// Migrations are run in order of their version, which must be unique.
+    /// Once a version has been applied, it will never be run again, so
+    /// the `sql` of an existing migration should not be changed. Add a new
+    /// migration instead.
+    pub fn new(version: u32, sql: &'static str) -> Self {
+        Self { version, sql }
+    }
+}
+
+/// Runs every migration that hasn't been applied yet.
+async fn run_migrations(pool: &SqlitePool, mut migrations: Vec<Migration>) -> Result<()> {
+    migrations.sort_by_key(|migration| migration.version);
+    if let Some(pair) = migrations
+        .windows(2)
+        .find(|pair| pair[0].version == pair[1].version)
+    {
+        bail!("multiple migrations have version {}", pair[0].version);
+    }
+
+    let applied: Vec<u32> = sqlx::query_scalar("SELECT version FROM _migrations")
+        .fetch_all(pool)
+        .await?;
+
+    for migration in migrations {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+
+        let mut transaction = pool.begin().await?;
+        sqlx::query(migration.sql)
+            .execute(&mut *transaction)
+            .await
+            .with_context(|| format!("failed to run migration {}", migration.version))?;
+        sqlx::query("INSERT INTO _migrations (version, applied_at) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(OffsetDateTime::now_utc())
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+    }
+
     Ok(())
 }
 
@@ -40,19 +169,935 @@ pub fn pool() -> &'static SqlitePool {
 
 // other helper stuff //
 
-pub(crate) async fn increment_frequency_table(title: &str) -> Result<()> {
-    sqlx::query(
+/// Records an activation of the list item with this title.
+///
+/// This does not wait for the database to be written to. Call [`flush`]
+/// to wait for all recorded activations to be written. Nothing is
+/// recorded if tracking is disabled.
+pub(crate) fn increment_frequency_table(title: &str) {
+    if let Some(writer) = WRITER.get() {
+        writer.increment(title.to_owned());
+    } else {
+        eprintln!("activation of {title:?} was not recorded: database is not initialised");
+    }
+}
+
+/// Stops recording activations, for plugins that the user doesn't want
+/// to be tracked.
+///
+/// Activations that were recorded before are no longer used to rank
+/// items or show recent items either.
+pub(crate) fn disable_tracking() {
+    if let Some(writer) = WRITER.get() {
+        writer.disable_tracking();
+    }
+}
+
+/// Whether activations are recorded, so the history can be used.
+pub(crate) fn tracks_activations() -> bool {
+    WRITER.get().is_some_and(BatchWriter::tracks_activations)
+}
+
+/// Starts or stops recording queries in the `query_log` table.
+///
+/// This is called every time the plugin is initialised, so that turning
+/// off `log_queries` stops logging. Does nothing if the database is not
+/// initialised.
+pub(crate) fn set_query_log(enabled: bool) {
+    if let Some(logger) = QUERY_LOGGER.get() {
+        logger.set_enabled(enabled);
+    } else if let Some(writer) = WRITER.get().filter(|_| enabled) {
+        QUERY_LOGGER.get_or_init(|| QueryLogger::spawn(writer, QUERY_LOG_DEBOUNCE));
+    }
+}
+
+/// Keeps at most `max_entries` activations, deleting the least recently
+/// used ones now and then every [`PRUNE_INTERVAL`].
+///
+/// Pruning is done by the [`BatchWriter`] in the background. Does nothing
+/// if the database is not initialised.
+pub(crate) fn enable_pruning(max_entries: u32) {
+    if let Some(writer) = WRITER.get() {
+        let sender = writer.sender.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            loop {
+                // the first tick completes immediately
+                interval.tick().await;
+                if sender.send(Write::Prune { max_entries }).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Records a query once the user stops typing.
+///
+/// Does nothing unless the user has enabled `log_queries`.
+pub(crate) fn log_query(query: &str) {
+    if let Some(logger) = QUERY_LOGGER.get() {
+        logger.log(query.to_owned());
+    }
+}
+
+/// Starts saving activated items, so that they can be shown when the
+/// query is empty.
+pub(crate) fn enable_recent_items() {
+    SHOW_RECENT.store(true, Ordering::Relaxed);
+}
+
+/// Whether the user has enabled `show_recent`, and activations are
+/// tracked.
+pub(crate) fn shows_recent_items() -> bool {
+    SHOW_RECENT.load(Ordering::Relaxed) && tracks_activations()
+}
+
+/// Starts recording the text that [`Action::Copy`](crate::Action::Copy)
+/// copies.
+pub(crate) fn enable_clipboard_history() {
+    CLIPBOARD_HISTORY.store(true, Ordering::Relaxed);
+}
+
+/// Records text that was copied, moving it to the top of the history
+/// if it was copied before.
+///
+/// Does nothing unless the user has enabled `clipboard_history`.
+pub(crate) async fn record_copy(text: &str) -> Result<()> {
+    let Some(pool) = POOL.get() else {
+        return Ok(());
+    };
+    if !CLIPBOARD_HISTORY.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    record_copy_in(pool, text, OffsetDateTime::now_utc()).await
+}
+
+/// Saves an activated item so that it can be shown as a recent item.
+///
+/// Like [`increment_frequency_table`], this does not wait for the
+/// database to be written to.
+pub(crate) fn remember_item(title: &str, item: RecentItem) {
+    if let Some(writer) = WRITER.get() {
+        writer.remember(title.to_owned(), item);
+    }
+}
+
+/// An item that was activated, saved so that it can be shown again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RecentItem {
+    /// The item as an encoded `proto::ListItem`, without its children.
+    pub(crate) item: Vec<u8>,
+    /// The query that returned the item.
+    pub(crate) query: String,
+}
+
+/// Gets up to `limit` recent items, with the highest frecency first.
+pub(crate) async fn recent_items(limit: usize) -> Result<Vec<RecentItem>> {
+    recent_items_from(pool(), limit, OffsetDateTime::now_utc()).await
+}
+
+async fn recent_items_from(
+    pool: &SqlitePool,
+    limit: usize,
+    now: OffsetDateTime,
+) -> Result<Vec<RecentItem>> {
+    let rows: Vec<(Vec<u8>, String, i64, OffsetDateTime)> = sqlx::query_as(
         "
-        INSERT INTO activations (title, frequency, last_use)
-        VALUES (?, 1, ?)
-        ON CONFLICT (title) DO UPDATE SET
-            frequency = frequency + 1,
-            last_use = excluded.last_use
+        SELECT recent_items.item, recent_items.query, frequency, last_use
+        FROM recent_items JOIN activations USING (title)
         ",
     )
+    .fetch_all(pool)
+    .await?;
+
+    let frecency = FrecencyStrategy::user_default();
+    let mut scored: Vec<_> = rows
+        .into_iter()
+        .map(|(item, query, frequency, last_use)| {
+            let score = frecency.score(frequency.saturating_as(), last_use, now);
+            (score, RecentItem { item, query })
+        })
+        .collect();
+    // sort reversed
+    scored.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+    Ok(scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect())
+}
+
+/// Pins the item with this title, so that [`rank`](crate::rank::rank)
+/// sorts it above unpinned items.
+///
+/// The item is sorted after the items that are already pinned. Pins are
+/// kept until [`unpin`] is called.
+pub async fn pin(title: &str) -> Result<()> {
+    pin_in(pool(), title).await
+}
+
+/// Unpins the item with this title. Does nothing if it isn't pinned.
+pub async fn unpin(title: &str) -> Result<()> {
+    unpin_in(pool(), title).await.map(drop)
+}
+
+/// Whether the item with this title is pinned.
+pub async fn is_pinned(title: &str) -> Result<bool> {
+    Ok(pinned_titles_in(pool())
+        .await?
+        .iter()
+        .any(|pin| pin == title))
+}
+
+/// Changes the order that [`rank`](crate::rank::rank) sorts pinned items
+/// in, pinning any of the titles that aren't pinned yet.
+///
+/// The titles take the places of the same pins, so pins that aren't
+/// in `titles` keep their places.
+pub async fn reorder_pins(titles: &[&str]) -> Result<()> {
+    reorder_pins_in(pool(), titles).await
+}
+
+/// Unpins the item with this title if it is pinned, or else pins it.
+pub(crate) async fn toggle_pin(title: &str) -> Result<()> {
+    toggle_pin_in(pool(), title).await
+}
+
+/// Titles of every pinned item, in order.
+///
+/// This is empty if the database is not initialised.
+pub(crate) async fn pinned_titles() -> Vec<String> {
+    let Some(pool) = POOL.get() else {
+        return Vec::new();
+    };
+    pinned_titles_in(pool).await.unwrap_or_else(|e| {
+        eprintln!("failed to read pinned items: {e:#}");
+        Vec::new()
+    })
+}
+
+async fn pin_in(pool: &SqlitePool, title: &str) -> Result<()> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO pins (title, position)
+        VALUES (?, (SELECT COALESCE(MAX(position) + 1, 0) FROM pins))",
+    )
     .bind(title)
-    .bind(time::OffsetDateTime::now_utc())
-    .execute(pool())
+    .execute(pool)
     .await?;
     Ok(())
 }
+
+async fn reorder_pins_in(pool: &SqlitePool, titles: &[&str]) -> Result<()> {
+    let mut transaction = pool.begin().await?;
+    let mut order = pinned_titles_in(&mut *transaction).await?;
+    let mut reordered = titles.iter().map(|&title| title.to_owned());
+    for pin in &mut order {
+        if titles.contains(&pin.as_str()) {
+            if let Some(title) = reordered.next() {
+                *pin = title;
+            }
+        }
+    }
+    order.extend(reordered);
+
+    for (position, title) in order.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO pins (title, position) VALUES (?, ?)
+            ON CONFLICT (title) DO UPDATE SET position = excluded.position",
+        )
+        .bind(title)
+        .bind(position.saturating_as::<i64>())
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Returns whether the item was pinned.
+async fn unpin_in(pool: &SqlitePool, title: &str) -> Result<bool> {
+    let removed = sqlx::query("DELETE FROM pins WHERE title = ?")
+        .bind(title)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(removed > 0)
+}
+
+async fn toggle_pin_in(pool: &SqlitePool, title: &str) -> Result<()> {
+    if !unpin_in(pool, title).await? {
+        pin_in(pool, title).await?;
+    }
+    Ok(())
+}
+
+async fn pinned_titles_in(
+    executor: impl sqlx::Executor<'_, Database = Sqlite>,
+) -> Result<Vec<String>> {
+    // pins from before they were ordered all have the same position
+    Ok(
+        sqlx::query_scalar("SELECT title FROM pins ORDER BY position, title")
+            .fetch_all(executor)
+            .await?,
+    )
+}
+
+/// Waits for all recorded activations and queries to be written to
+/// the database.
+///
+/// A query that is still being debounced is written immediately.
+pub(crate) async fn flush() {
+    if let Some(logger) = QUERY_LOGGER.get() {
+        logger.flush().await;
+    }
+    if let Some(writer) = WRITER.get() {
+        writer.flush().await;
+    }
+}
+
+/// A query that the user settled on.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct LoggedQuery {
+    pub query: String,
+    pub time: OffsetDateTime,
+}
+
+/// Gets the `limit` most recently logged queries, newest first.
+///
+/// Queries are only logged if the user has enabled `log_queries`
+/// in their config. Otherwise, this will be empty.
+pub async fn query_log(limit: u32) -> Result<Vec<LoggedQuery>> {
+    query_log_from(pool(), limit).await
+}
+
+async fn query_log_from(pool: &SqlitePool, limit: u32) -> Result<Vec<LoggedQuery>> {
+    Ok(
+        sqlx::query_as("SELECT query, time FROM query_log ORDER BY time DESC, id DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+/// Text that was copied by one of the plugin's items.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct CopiedText {
+    pub text: String,
+    /// When the text was last copied.
+    pub time: OffsetDateTime,
+}
+
+/// Gets the `limit` most recently copied texts, newest first.
+///
+/// Only text copied with [`Action::Copy`](crate::Action::Copy) by this
+/// plugin is recorded, and only if the user has enabled
+/// `clipboard_history` in their config. Otherwise, this will be empty.
+/// Copying an entry again moves it to the top.
+pub async fn clipboard_history(limit: u32) -> Result<Vec<CopiedText>> {
+    clipboard_history_from(pool(), limit).await
+}
+
+async fn clipboard_history_from(pool: &SqlitePool, limit: u32) -> Result<Vec<CopiedText>> {
+    Ok(
+        sqlx::query_as("SELECT text, time FROM clipboard_history ORDER BY time DESC LIMIT ?")
+            .bind(limit)
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+async fn record_copy_in(pool: &SqlitePool, text: &str, time: OffsetDateTime) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO clipboard_history (text, time) VALUES (?, ?)
+        ON CONFLICT (text) DO UPDATE SET time = excluded.time",
+    )
+    .bind(text)
+    .bind(time)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+enum Write {
+    Activation { title: String, time: OffsetDateTime },
+    Query { query: String, time: OffsetDateTime },
+    Recent { title: String, item: RecentItem },
+    Prune { max_entries: u32 },
+    Flush(oneshot::Sender<()>),
+}
+
+/// A background task that writes activations and queries to the database.
+///
+/// Writes that are recorded while a write is in progress are
+/// combined into a single transaction, with repeated titles coalesced.
+/// The history is pruned in the same transaction, after the writes.
+struct BatchWriter {
+    sender: mpsc::UnboundedSender<Write>,
+    /// Whether activations and recent items are written.
+    track_activations: AtomicBool,
+}
+
+impl BatchWriter {
+    /// Spawns the writer on the current tokio runtime.
+    fn spawn(pool: SqlitePool) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(Self::run(pool, receiver));
+        Self {
+            sender,
+            track_activations: AtomicBool::new(true),
+        }
+    }
+
+    fn disable_tracking(&self) {
+        self.track_activations.store(false, Ordering::Relaxed);
+    }
+
+    fn tracks_activations(&self) -> bool {
+        self.track_activations.load(Ordering::Relaxed)
+    }
+
+    fn increment(&self, title: String) {
+        if !self.tracks_activations() {
+            return;
+        }
+        let time = OffsetDateTime::now_utc();
+        // only fails if the writer task has stopped, which happens
+        // if the runtime is shutting down.
+        _ = self.sender.send(Write::Activation { title, time });
+    }
+
+    fn remember(&self, title: String, item: RecentItem) {
+        if self.tracks_activations() {
+            _ = self.sender.send(Write::Recent { title, item });
+        }
+    }
+
+    async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(Write::Flush(tx)).is_ok() {
+            _ = rx.await;
+        }
+    }
+
+    async fn run(pool: SqlitePool, mut receiver: mpsc::UnboundedReceiver<Write>) {
+        while let Some(first) = receiver.recv().await {
+            // (frequency increment, last use)
+            let mut batch: HashMap<String, (u32, OffsetDateTime)> = HashMap::new();
+            let mut queries = vec![];
+            let mut recent = HashMap::new();
+            let mut prune = None;
+            let mut flushed = vec![];
+
+            let mut next = Some(first);
+            while let Some(write) = next {
+                match write {
+                    Write::Activation { title, time } => {
+                        let (frequency, last_use) = batch.entry(title).or_insert((0, time));
+                        *frequency += 1;
+                        *last_use = time.max(*last_use);
+                    }
+                    Write::Query { query, time } => queries.push((query, time)),
+                    Write::Recent { title, item } => {
+                        recent.insert(title, item);
+                    }
+                    Write::Prune { max_entries } => prune = Some(max_entries),
+                    Write::Flush(tx) => flushed.push(tx),
+                }
+                next = receiver.try_recv().ok();
+            }
+
+            if let Err(e) = Self::write(&pool, batch, queries, recent, prune).await {
+                eprintln!("failed to record activations: {e:#}");
+            }
+            for tx in flushed {
+                _ = tx.send(());
+            }
+        }
+    }
+
+    async fn write(
+        pool: &SqlitePool,
+        batch: HashMap<String, (u32, OffsetDateTime)>,
+        queries: Vec<(String, OffsetDateTime)>,
+        recent: HashMap<String, RecentItem>,
+        prune: Option<u32>,
+    ) -> Result<()> {
+        if batch.is_empty() && queries.is_empty() && recent.is_empty() && prune.is_none() {
+            return Ok(());
+        }
+
+        let mut transaction = pool.begin().await?;
+        for (title, (frequency, last_use)) in batch {
+            sqlx::query(
+                "
+                INSERT INTO activations (title, frequency, last_use)
+                VALUES (?, ?, ?)
+                ON CONFLICT (title) DO UPDATE SET
+                    frequency = frequency + excluded.frequency,
+                    last_use = excluded.last_use
+                ",
+            )
+            .bind(title)
+            .bind(frequency)
+            .bind(last_use)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        for (query, time) in queries {
+            sqlx::query("INSERT INTO query_log (query, time) VALUES (?, ?)")
+                .bind(query)
+                .bind(time)
+                .execute(&mut *transaction)
+                .await?;
+        }
+        for (title, RecentItem { item, query }) in recent {
+            sqlx::query(
+                "
+                INSERT INTO recent_items (title, item, query)
+                VALUES (?, ?, ?)
+                ON CONFLICT (title) DO UPDATE SET
+                    item = excluded.item,
+                    query = excluded.query
+                ",
+            )
+            .bind(title)
+            .bind(item)
+            .bind(query)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        if let Some(max_entries) = prune {
+            prune_activations(&mut transaction, max_entries).await?;
+        }
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Deletes all but the `max_entries` most recently used activations,
+/// along with their recent items.
+async fn prune_activations(conn: &mut SqliteConnection, max_entries: u32) -> Result<()> {
+    let deleted = sqlx::query(
+        "
+        DELETE FROM activations WHERE id NOT IN (
+            SELECT id FROM activations ORDER BY last_use DESC LIMIT ?
+        )
+        ",
+    )
+    .bind(max_entries)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if deleted > 0 {
+        sqlx::query("DELETE FROM recent_items WHERE title NOT IN (SELECT title FROM activations)")
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+enum LoggedInput {
+    Query(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// A background task that passes queries on to the [`BatchWriter`]
+/// once they have stopped changing.
+///
+/// This avoids logging a query for every keystroke.
+struct QueryLogger {
+    sender: mpsc::UnboundedSender<LoggedInput>,
+    enabled: AtomicBool,
+}
+
+impl QueryLogger {
+    /// Spawns the logger on the current tokio runtime.
+    fn spawn(writer: &BatchWriter, debounce: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(Self::run(writer.sender.clone(), receiver, debounce));
+        Self {
+            sender,
+            enabled: AtomicBool::new(true),
+        }
+    }
+
+    fn log(&self, query: String) {
+        if self.enabled.load(Ordering::Relaxed) {
+            _ = self.sender.send(LoggedInput::Query(query));
+        }
+    }
+
+    /// Starts or stops logging queries.
+    ///
+    /// A query that is still being debounced when logging is stopped
+    /// is dropped.
+    fn set_enabled(&self, enabled: bool) {
+        if !self.enabled.swap(enabled, Ordering::Relaxed) || enabled {
+            return;
+        }
+        // empty queries replace the pending query without being logged
+        _ = self.sender.send(LoggedInput::Query(String::new()));
+    }
+
+    /// Waits for the pending query to be sent to the writer.
+    ///
+    /// This does not wait for it to be written. Flush the writer after
+    /// flushing this.
+    async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        if self.sender.send(LoggedInput::Flush(tx)).is_ok() {
+            _ = rx.await;
+        }
+    }
+
+    async fn run(
+        writer: mpsc::UnboundedSender<Write>,
+        mut receiver: mpsc::UnboundedReceiver<LoggedInput>,
+        debounce: Duration,
+    ) {
+        let mut pending: Option<(String, OffsetDateTime)> = None;
+        let send = |pending: &mut Option<(String, OffsetDateTime)>| {
+            if let Some((query, time)) = pending.take() {
+                _ = writer.send(Write::Query { query, time });
+            }
+        };
+
+        loop {
+            let input = if pending.is_some() {
+                match tokio::time::timeout(debounce, receiver.recv()).await {
+                    Ok(input) => input,
+                    Err(_elapsed) => {
+                        send(&mut pending);
+                        continue;
+                    }
+                }
+            } else {
+                receiver.recv().await
+            };
+
+            match input {
+                Some(LoggedInput::Query(query)) => {
+                    let query = query.trim();
+                    pending =
+                        (!query.is_empty()).then(|| (query.to_owned(), OffsetDateTime::now_utc()));
+                }
+                Some(LoggedInput::Flush(tx)) => {
+                    send(&mut pending);
+                    _ = tx.send(());
+                }
+                None => {
+                    send(&mut pending);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::SqlitePool;
+
+    use super::{
+        clipboard_history_from, create_tables, pin_in, pinned_titles_in, prune_activations,
+        query_log_from, recent_items_from, record_copy_in, reorder_pins_in, run_migrations,
+        toggle_pin_in, unpin_in, BatchWriter, Migration, QueryLogger, RecentItem,
+    };
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rapid_activations_coalesce() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let writer = BatchWriter::spawn(pool.clone());
+
+        for i in 0..300 {
+            writer.increment(if i % 3 == 0 { "a" } else { "b" }.to_owned());
+        }
+        writer.flush().await;
+        writer.increment("a".to_owned());
+        writer.flush().await;
+
+        let mut counts: Vec<(String, i64)> =
+            sqlx::query_as("SELECT title, frequency FROM activations")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        counts.sort();
+
+        assert_eq!(counts, [("a".to_owned(), 101), ("b".to_owned(), 200)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn untracked_activations_are_not_written() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let writer = BatchWriter::spawn(pool.clone());
+        writer.disable_tracking();
+
+        writer.increment("a".to_owned());
+        writer.remember(
+            "a".to_owned(),
+            RecentItem {
+                item: vec![],
+                query: String::new(),
+            },
+        );
+        writer.flush().await;
+
+        let count = |table: &'static str| {
+            let pool = &pool;
+            async move {
+                let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {table}"))
+                    .fetch_one(pool)
+                    .await
+                    .unwrap();
+                count
+            }
+        };
+        assert_eq!(count("activations").await, 0);
+        assert_eq!(count("recent_items").await, 0);
+    }
+
+    #[tokio::test]
+    async fn migrations_run_once() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let migrations = || {
+            vec![
+                Migration::new(
+                    2,
+                    "INSERT INTO bookmarks (url) VALUES ('https://example.com')",
+                ),
+                Migration::new(1, "CREATE TABLE bookmarks (url TEXT NOT NULL)"),
+            ]
+        };
+
+        run_migrations(&pool, migrations()).await.unwrap();
+        // would insert another row if re-run
+        run_migrations(&pool, migrations()).await.unwrap();
+
+        let urls: Vec<String> = sqlx::query_scalar("SELECT url FROM bookmarks")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(urls, ["https://example.com"]);
+    }
+
+    #[tokio::test]
+    async fn duplicate_migration_versions_error() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+
+        let result = run_migrations(
+            &pool,
+            vec![
+                Migration::new(1, "CREATE TABLE a (x INTEGER)"),
+                Migration::new(1, "CREATE TABLE b (x INTEGER)"),
+            ],
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn settled_queries_are_logged_once() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let writer = BatchWriter::spawn(pool.clone());
+        let logger = QueryLogger::spawn(&writer, Duration::from_millis(50));
+
+        for query in ["f", "fi", "fir", "fire"] {
+            logger.log(query.to_owned());
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        writer.flush().await;
+
+        let queries: Vec<String> = query_log_from(&pool, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|logged| logged.query)
+            .collect();
+        assert_eq!(queries, ["fire"]);
+
+        // flushing writes the pending query without waiting
+        logger.log("firefox".to_owned());
+        logger.flush().await;
+        writer.flush().await;
+
+        let queries: Vec<String> = query_log_from(&pool, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|logged| logged.query)
+            .collect();
+        assert_eq!(queries, ["firefox", "fire"]);
+    }
+
+    #[tokio::test]
+    async fn disabled_query_log_stops_logging() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let writer = BatchWriter::spawn(pool.clone());
+        let logger = QueryLogger::spawn(&writer, Duration::from_secs(10));
+
+        logger.log("fire".to_owned());
+        logger.flush().await;
+        // the pending query is dropped too
+        logger.log("firefox".to_owned());
+        logger.set_enabled(false);
+        logger.log("terminal".to_owned());
+        logger.flush().await;
+        logger.set_enabled(true);
+        logger.log("settings".to_owned());
+        logger.flush().await;
+        writer.flush().await;
+
+        let queries: Vec<String> = query_log_from(&pool, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|logged| logged.query)
+            .collect();
+        assert_eq!(queries, ["settings", "fire"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn recent_items_are_ordered_by_frecency() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let writer = BatchWriter::spawn(pool.clone());
+        let recent = |query: &str| RecentItem {
+            item: query.as_bytes().to_vec(),
+            query: query.to_owned(),
+        };
+
+        writer.increment("Firefox".to_owned());
+        writer.remember("Firefox".to_owned(), recent("fire"));
+        for _ in 0..3 {
+            writer.increment("Terminal".to_owned());
+        }
+        writer.remember("Terminal".to_owned(), recent("term"));
+        // activated, but not saved as a recent item
+        writer.increment("Settings".to_owned());
+        writer.flush().await;
+
+        let now = time::OffsetDateTime::now_utc();
+        let items = recent_items_from(&pool, 10, now).await.unwrap();
+        assert_eq!(items, [recent("term"), recent("fire")]);
+
+        let items = recent_items_from(&pool, 1, now).await.unwrap();
+        assert_eq!(items, [recent("term")]);
+    }
+
+    #[tokio::test]
+    async fn copies_are_recorded_newest_first() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let start = time::OffsetDateTime::now_utc();
+        let history = |limit| {
+            let pool = &pool;
+            async move {
+                clipboard_history_from(pool, limit)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|copied| copied.text)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        for (i, text) in ["token", "hello", "token"].into_iter().enumerate() {
+            let time = start + time::Duration::seconds(i.try_into().unwrap());
+            record_copy_in(&pool, text, time).await.unwrap();
+        }
+
+        // copying again moves the entry to the top
+        assert_eq!(history(10).await, ["token", "hello"]);
+        assert_eq!(history(1).await, ["token"]);
+    }
+
+    #[tokio::test]
+    async fn pins_are_reordered() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        for title in ["a", "b", "c", "d"] {
+            pin_in(&pool, title).await.unwrap();
+        }
+
+        // only the shown pins are moved
+        reorder_pins_in(&pool, &["d", "b"]).await.unwrap();
+        assert_eq!(pinned_titles_in(&pool).await.unwrap(), ["a", "d", "c", "b"]);
+
+        reorder_pins_in(&pool, &["c", "e"]).await.unwrap();
+        assert_eq!(
+            pinned_titles_in(&pool).await.unwrap(),
+            ["a", "d", "c", "b", "e"]
+        );
+        // new pins go last
+        pin_in(&pool, "f").await.unwrap();
+        assert_eq!(pinned_titles_in(&pool).await.unwrap().last().unwrap(), "f");
+    }
+
+    #[tokio::test]
+    async fn pins_are_toggled() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let pinned = || async { pinned_titles_in(&pool).await.unwrap() };
+
+        pin_in(&pool, "Firefox").await.unwrap();
+        // pinning twice keeps one pin
+        pin_in(&pool, "Firefox").await.unwrap();
+        toggle_pin_in(&pool, "Terminal").await.unwrap();
+        assert_eq!(pinned().await, ["Firefox", "Terminal"]);
+
+        toggle_pin_in(&pool, "Terminal").await.unwrap();
+        assert!(unpin_in(&pool, "Firefox").await.unwrap());
+        assert!(!unpin_in(&pool, "Firefox").await.unwrap());
+        assert!(pinned().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pruning_keeps_most_recent_activations() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        create_tables(&pool).await.unwrap();
+        let now = time::OffsetDateTime::now_utc();
+        for (i, title) in ["a", "b", "c", "d", "e"].into_iter().enumerate() {
+            sqlx::query("INSERT INTO activations (title, frequency, last_use) VALUES (?, 10, ?)")
+                .bind(title)
+                .bind(now - time::Duration::hours(i.try_into().unwrap()))
+                .execute(&pool)
+                .await
+                .unwrap();
+            sqlx::query("INSERT INTO recent_items (title, item, query) VALUES (?, x'', '')")
+                .bind(title)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let mut transaction = pool.begin().await.unwrap();
+        prune_activations(&mut transaction, 3).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        let titles = |table: &'static str| {
+            let pool = &pool;
+            async move {
+                let mut titles: Vec<(String,)> =
+                    sqlx::query_as(&format!("SELECT title FROM {table}"))
+                        .fetch_all(pool)
+                        .await
+                        .unwrap();
+                titles.sort();
+                titles.into_iter().map(|(title,)| title).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(titles("activations").await, ["a", "b", "c"]);
+        assert_eq!(titles("recent_items").await, ["a", "b", "c"]);
+    }
+}
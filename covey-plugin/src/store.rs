@@ -1,21 +1,44 @@
 use std::{
     collections::VecDeque,
-    iter,
     ops::Range,
     sync::atomic::{AtomicU64, Ordering},
 };
 
-use az::CheckedAs;
+use az::{CheckedAs, SaturatingAs as _};
 use parking_lot::Mutex;
+use prost::Message as _;
 
-use crate::{list::ListItemCallbacks, proto, Icon, List, ListItem, ListStyle};
+use crate::{
+    list::{ListItemCallbacks, SelectedItem},
+    proto, sql,
+    sql::RecentItem,
+    Action, Icon, Input, List, ListItem, ListStyle, TextSpan,
+};
 
 static STORE: Mutex<ListItemStore> = Mutex::new(ListItemStore::new());
 
 /// Stores the result of a query, returning the response that should be
 /// sent to covey.
-pub(crate) fn store_query_result(list: List) -> proto::QueryResponse {
-    STORE.lock().store_query_result(list)
+pub(crate) fn store_query_result(query: &str, list: List) -> proto::QueryResponse {
+    STORE.lock().store_query_result(query, list)
+}
+
+/// Stores a page of results of the most recent query, returning the
+/// response that should be sent to covey.
+pub(crate) fn store_page_result(list: List) -> proto::QueryResponse {
+    STORE.lock().store_page_result(list)
+}
+
+/// Stores items loaded from the recent items, returning the response
+/// that should be sent to covey.
+pub(crate) fn store_recent_items(items: Vec<RecentItem>) -> proto::QueryResponse {
+    STORE.lock().store_recent_items(items)
+}
+
+/// Stores an item that replaces one from the most recent query,
+/// returning the item that should be sent to covey.
+pub(crate) fn store_updated_item(item: ListItem) -> proto::ListItem {
+    STORE.lock().store_updated_item(item)
 }
 
 /// Finds the associated callbacks of an ID.
@@ -28,6 +51,17 @@ pub(crate) fn fetch_callbacks_of(list_item_id: u64) -> Option<ListItemCallbacks>
     STORE.lock().fetch_callbacks_of(list_item_id)
 }
 
+/// Finds the item of an ID to preview, without disposing of any
+/// old queries.
+pub(crate) fn selected_item_of(list_item_id: u64) -> Option<SelectedItem> {
+    STORE
+        .lock()
+        .queries
+        .iter()
+        .find_map(|query| query.callback_of_id(list_item_id))
+        .map(ListItemCallbacks::selected_item)
+}
+
 /// Store to map list item IDs to their callbacks.
 ///
 /// Only one should be constructed.
@@ -44,6 +78,8 @@ struct ListItemStore {
     /// The IDs stored in the deque should be increasing and contiguous.
     queries: VecDeque<QueryListItemStore>,
     ids: AutoIncrementer,
+    /// The most recent query, which updated items are a result of.
+    query: String,
 }
 
 impl ListItemStore {
@@ -51,54 +87,134 @@ impl ListItemStore {
         Self {
             queries: VecDeque::new(),
             ids: AutoIncrementer(AtomicU64::new(0)),
+            query: String::new(),
         }
     }
 
-    fn store_query_result(&mut self, list: List) -> proto::QueryResponse {
+    fn store_query_result(&mut self, query: &str, list: List) -> proto::QueryResponse {
+        query.clone_into(&mut self.query);
+
+        self.store_list(list, false)
+    }
+
+    /// Stores more results of the most recent query, which are part of
+    /// that query like updated items.
+    fn store_page_result(&mut self, list: List) -> proto::QueryResponse {
+        self.store_list(list, true)
+    }
+
+    fn store_list(&mut self, list: List, is_update: bool) -> proto::QueryResponse {
         // Don't store an empty result
         if list.items.is_empty() {
             return proto::QueryResponse {
                 items: vec![],
                 list_style: list.style.map(ListStyle::into_proto),
+                handled: Some(list.handled),
+                // there is no first item to activate
+                default_actions: vec![],
+                next_page_token: list.next_page,
+                header: list.header,
+                correction: list.correction.map(Input::into_proto),
+                sort_key: list.sort_key.as_ref().map(|(key, _)| key.clone()),
+                sort_desc: list.sort_key.map(|(_, descending)| descending),
+                refresh_ms: list
+                    .refresh
+                    .map(|interval| interval.as_millis().saturating_as()),
             };
         }
 
-        let (items, callbacks) = split_item_vec(&self.ids, list.items);
+        let default_actions = list
+            .default_action
+            .map(|actions| {
+                actions
+                    .list
+                    .into_iter()
+                    .filter_map(Action::into_proto)
+                    .collect()
+            })
+            .unwrap_or_default();
 
-        self.queries.push_back(QueryListItemStore {
-            callbacks,
-            first_id: items.first().expect("list should be non empty").id,
-        });
+        let items = self.store_items(list.items, is_update);
 
-        return proto::QueryResponse {
+        proto::QueryResponse {
             items,
             list_style: list.style.map(ListStyle::into_proto),
-        };
+            handled: Some(list.handled),
+            default_actions,
+            next_page_token: list.next_page,
+            header: list.header,
+            correction: list.correction.map(Input::into_proto),
+            sort_key: list.sort_key.as_ref().map(|(key, _)| key.clone()),
+            sort_desc: list.sort_key.map(|(_, descending)| descending),
+            refresh_ms: list
+                .refresh
+                .map(|interval| interval.as_millis().saturating_as()),
+        }
+    }
 
-        fn split_item_vec(
-            ids: &AutoIncrementer,
-            vec: Vec<ListItem>,
-        ) -> (Vec<proto::ListItem>, Vec<ListItemCallbacks>) {
-            let new_ids = ids.fetch_many(vec.len() as u64);
-
-            let mut items = vec![];
-            let mut callbacks = vec![];
-
-            for (id, item) in iter::zip(new_ids, vec) {
-                items.push(proto::ListItem {
-                    id,
-                    title: item.title,
-                    description: item.description,
-                    icon: item.icon.map(Icon::into_proto),
-                    available_commands: item.commands.ids().map(|s| s.to_owned()).collect(),
-                });
-                callbacks.push(item.commands);
-            }
+    /// Stores recent items, which find their commands by running their
+    /// query again when activated.
+    ///
+    /// Items that can't be decoded are skipped.
+    fn store_recent_items(&mut self, recent: Vec<RecentItem>) -> proto::QueryResponse {
+        let items: Vec<_> = recent
+            .into_iter()
+            .filter_map(|recent| {
+                let item = proto::ListItem::decode(recent.item.as_slice()).ok()?;
+                Some((item, recent.query))
+            })
+            .collect();
+        if items.is_empty() {
+            return proto::QueryResponse::default();
+        }
 
-            (items, callbacks)
+        let mut ids = self.ids.fetch_many(items.len() as u64);
+        let (items, callbacks): (Vec<_>, Vec<_>) = items
+            .into_iter()
+            .map(|(mut item, query)| {
+                item.id = ids.next().expect("ids should be fetched for every item");
+                let mut callbacks = ListItemCallbacks::new(item.title.clone());
+                callbacks.requery = Some(query);
+                (item, callbacks)
+            })
+            .unzip();
+        self.push_query(&items, callbacks, false);
+
+        proto::QueryResponse {
+            items,
+            ..Default::default()
         }
     }
 
+    /// Stores a replacement for an item of the most recent query.
+    fn store_updated_item(&mut self, item: ListItem) -> proto::ListItem {
+        self.store_items(vec![item], true)
+            .pop()
+            .expect("one item should be stored")
+    }
+
+    /// Gives the items IDs and stores their callbacks.
+    ///
+    /// `items` must not be empty.
+    fn store_items(&mut self, items: Vec<ListItem>, is_update: bool) -> Vec<proto::ListItem> {
+        let (items, callbacks) = split_item_vec(&self.ids, items, &self.query);
+        self.push_query(&items, callbacks, is_update);
+        items
+    }
+
+    fn push_query(
+        &mut self,
+        items: &[proto::ListItem],
+        callbacks: Vec<ListItemCallbacks>,
+        is_update: bool,
+    ) {
+        self.queries.push_back(QueryListItemStore {
+            callbacks,
+            first_id: items.first().expect("list should be non empty").id,
+            is_update,
+        });
+    }
+
     fn fetch_callbacks_of(&mut self, id: u64) -> Option<ListItemCallbacks> {
         // linear search is good enough
         let (found_index, found_callback) =
@@ -111,23 +227,117 @@ impl ListItemStore {
         // Remove old queries.
         // Don't include the current query, since the action could be
         // nothing and the same query could have another list item activated.
-        self.queries.drain(..found_index);
+        // Updated items are part of the query before them, so keep that
+        // query too.
+        let current_query = self
+            .queries
+            .iter()
+            .take(found_index + 1)
+            .rposition(|query| !query.is_update)
+            .unwrap_or(0);
+        self.queries.drain(..current_query);
 
         Some(found_callback)
     }
 }
 
+/// Children are given IDs directly after their parent, so that
+/// the IDs of every item in the tree are contiguous.
+fn split_item_vec(
+    ids: &AutoIncrementer,
+    vec: Vec<ListItem>,
+    query: &str,
+) -> (Vec<proto::ListItem>, Vec<ListItemCallbacks>) {
+    let mut new_ids = ids.fetch_many(count_items(&vec));
+    let mut callbacks = vec![];
+    let remember = sql::shows_recent_items().then_some(query);
+    let items = convert_items(&mut new_ids, vec, &mut callbacks, remember);
+
+    (items, callbacks)
+}
+
+fn count_items(items: &[ListItem]) -> u64 {
+    items
+        .iter()
+        .map(|item| 1 + count_items(&item.children))
+        .sum()
+}
+
+/// Converts the items, adding their callbacks in the order of their IDs.
+///
+/// If `remember` is the query of the items, each callback keeps a copy
+/// of its item to save as a recent item.
+fn convert_items(
+    ids: &mut Range<u64>,
+    vec: Vec<ListItem>,
+    callbacks: &mut Vec<ListItemCallbacks>,
+    remember: Option<&str>,
+) -> Vec<proto::ListItem> {
+    vec.into_iter()
+        .map(|mut item| {
+            let id = ids.next().expect("ids should be fetched for every item");
+            let available_commands = item.commands.ids().map(|s| s.to_owned()).collect();
+
+            let mut converted = proto::ListItem {
+                id,
+                title: item.title,
+                description: item.description,
+                icon: item.icon.map(Icon::into_proto),
+                available_commands,
+                linkify_description: Some(item.linkify_description),
+                children: vec![],
+                title_spans: item
+                    .title_spans
+                    .into_iter()
+                    .map(TextSpan::into_proto)
+                    .collect(),
+                is_error: Some(item.is_error),
+                timestamp: item.timestamp,
+                key: item.id,
+                shortcut: item.shortcut,
+                description_action: item
+                    .description_action
+                    .and_then(|action| action.into_proto().map(Box::new)),
+                inline_action: item
+                    .inline_action
+                    .and_then(|action| action.into_proto().map(Box::new)),
+                symbolic_icon: Some(item.symbolic_icon),
+                requires_confirmation: Some(item.confirmation_prompt.is_some()),
+                confirmation_prompt: item.confirmation_prompt,
+                exact_match: Some(item.exact_match),
+                metadata: item.metadata,
+                pinned: Some(item.pinned),
+            };
+
+            item.commands.item_id.clone_from(&converted.key);
+            if let Some(query) = remember {
+                item.commands.recent = Some(RecentItem {
+                    item: converted.encode_to_vec(),
+                    query: query.to_owned(),
+                });
+            }
+            callbacks.push(item.commands);
+            converted.children = convert_items(ids, item.children, callbacks, remember);
+            converted
+        })
+        .collect()
+}
+
 /// INVARIANTS:
 /// - IDs of the list items are increasing and contiguous.
 /// - Number of items stored is non-zero.
 struct QueryListItemStore {
     callbacks: Vec<ListItemCallbacks>,
     first_id: u64,
+    /// Whether this stores a replacement for an item or a page of results
+    /// of the previous query, rather than the result of a query.
+    is_update: bool,
 }
 
 impl QueryListItemStore {
     pub fn callback_of_id(&self, id: u64) -> Option<&ListItemCallbacks> {
-        let offset = id - self.first_id;
+        // the ID may be from an older query that has been removed
+        let offset = id.checked_sub(self.first_id)?;
         self.callbacks.get(
             offset
                 .checked_as::<usize>()
@@ -148,3 +358,104 @@ impl AutoIncrementer {
         lower_bound..upper_bound
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use prost::Message as _;
+
+    use super::ListItemStore;
+    use crate::{proto, sql::RecentItem, List, ListItem};
+
+    #[test]
+    fn children_have_callbacks() {
+        let mut store = ListItemStore::new();
+        let response = store.store_query_result(
+            "",
+            List::new(vec![
+                ListItem::new("repo")
+                    .with_children(vec![ListItem::new("main"), ListItem::new("feature")]),
+                ListItem::new("other repo"),
+            ]),
+        );
+
+        let repo = &response.items[0];
+        let feature = &repo.children[1];
+        assert_eq!(feature.title, "feature");
+        assert_eq!(response.items[1].title, "other repo");
+        assert!(response.items[1].children.is_empty());
+
+        let callbacks = store.fetch_callbacks_of(feature.id).unwrap();
+        assert_eq!(callbacks.item_title, "feature");
+        let callbacks = store.fetch_callbacks_of(response.items[1].id).unwrap();
+        assert_eq!(callbacks.item_title, "other repo");
+    }
+
+    #[test]
+    fn error_items() {
+        let mut store = ListItemStore::new();
+        let response = store.store_query_result(
+            "",
+            List::new(vec![
+                ListItem::error("API rate limited"),
+                ListItem::new("cached result"),
+            ]),
+        );
+
+        let error = &response.items[0];
+        assert_eq!(error.title, "API rate limited");
+        assert!(error.is_error());
+        assert!(error.available_commands.is_empty());
+        assert!(!response.items[1].is_error());
+    }
+
+    #[test]
+    fn updated_items_keep_their_query() {
+        let mut store = ListItemStore::new();
+        let response = store.store_query_result(
+            "",
+            List::new(vec![
+                ListItem::new("star").with_id("a"),
+                ListItem::new("other"),
+            ]),
+        );
+        let updated = store.store_updated_item(ListItem::new("unstar").with_id("a"));
+        assert_eq!(updated.key.as_deref(), Some("a"));
+
+        let callbacks = store.fetch_callbacks_of(updated.id).unwrap();
+        assert_eq!(callbacks.item_title, "unstar");
+        // the rest of the list can still be activated
+        let callbacks = store.fetch_callbacks_of(response.items[1].id).unwrap();
+        assert_eq!(callbacks.item_title, "other");
+
+        // but a new query replaces both
+        let response = store.store_query_result("", List::new(vec![ListItem::new("new")]));
+        store.fetch_callbacks_of(response.items[0].id).unwrap();
+        assert!(store.fetch_callbacks_of(updated.id).is_none());
+    }
+
+    #[test]
+    fn recent_items_are_found_by_their_query() {
+        let mut store = ListItemStore::new();
+        let item = proto::ListItem {
+            title: "Firefox".to_owned(),
+            available_commands: vec!["activate".to_owned()],
+            ..Default::default()
+        };
+        let response = store.store_recent_items(vec![
+            RecentItem {
+                item: item.encode_to_vec(),
+                query: "fire".to_owned(),
+            },
+            RecentItem {
+                item: b"not an item".to_vec(),
+                query: "bad".to_owned(),
+            },
+        ]);
+
+        assert_eq!(response.items.len(), 1);
+        assert_eq!(response.items[0].title, "Firefox");
+        assert_eq!(response.items[0].available_commands, ["activate"]);
+        let callbacks = store.fetch_callbacks_of(response.items[0].id).unwrap();
+        assert_eq!(callbacks.requery.as_deref(), Some("fire"));
+    }
+}
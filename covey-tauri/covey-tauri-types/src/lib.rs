@@ -18,21 +18,106 @@ pub enum Event {
         contents: String,
         selection: (u16, u16),
     },
+    /// Focus the input, selecting all of its text if `select_all` is true.
+    FocusInput {
+        select_all: bool,
+    },
+    /// Queries suggested by the plugin, shown separately from the list.
+    SetSuggestions {
+        suggestions: Vec<String>,
+    },
     SetList {
         items: Vec<ListItem>,
         style: Option<ListStyle>,
+        /// Status line from the plugin to show above the list.
+        header: Option<String>,
+        /// Summary of the results to show in the footer.
+        summary: Option<String>,
+        /// Corrected query to suggest with "Did you mean ...?".
+        correction: Option<String>,
+    },
+    /// More results to add to the end of the list.
+    AppendList {
+        items: Vec<ListItem>,
+    },
+    SetPlaceholder {
+        placeholder: String,
+    },
+    SetSelection {
+        index: usize,
+    },
+    /// Briefly flash the menu after an item is activated.
+    Flash,
+    /// Switch between compact and normal rows.
+    ToggleCompact,
+    /// Full content of the selected item. Hidden if there is none.
+    SetPreview {
+        text: Option<String>,
+    },
+    /// Ask the user to confirm activating an item.
+    Confirm {
+        prompt: String,
+    },
+    /// Briefly show whether an action worked.
+    ShowToast {
+        message: String,
+        success: bool,
+    },
+    /// Empty the input without querying it.
+    ClearInput,
+    /// Show the prefix of the active plugin as a chip, or remove the
+    /// chip if `prefix` is `None`.
+    SetPrefixChip {
+        prefix: Option<String>,
+    },
+    /// Show the titles of the selected item's commands in a menu, or
+    /// hide the menu if `titles` is `None`.
+    SetActionsMenu {
+        titles: Option<Vec<String>>,
+    },
+    SetContexts {
+        /// Breadcrumbs of the contexts, from outermost to innermost.
+        labels: Vec<String>,
+    },
+    /// Replace a shown item, keeping the selection.
+    UpdateItem {
+        id: ListItemId,
+        item: Box<ListItem>,
     },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "build", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
+#[expect(
+    clippy::struct_excessive_bools,
+    reason = "each flag changes how the item is shown"
+)]
 pub struct ListItem {
     pub title: String,
-    pub description: String,
+    /// Styled text to show instead of the title, if not empty.
+    pub title_spans: Vec<TextSpan>,
+    pub description: Vec<DescriptionSegment>,
+    /// The whole description, if `description` was truncated.
+    pub full_description: Option<String>,
     pub icon: Option<Icon>,
     pub id: ListItemId,
     pub available_commands: Vec<String>,
+    /// Nested items, hidden until this item is expanded.
+    pub children: Vec<ListItem>,
+    /// Error items can't be selected or activated.
+    pub is_error: bool,
+    /// Seconds since the unix epoch, shown relative to the current time.
+    #[cfg_attr(feature = "build", ts(type = "number | null"))]
+    pub timestamp: Option<i64>,
+    /// Key that activates this item when pressed with alt.
+    pub shortcut: Option<String>,
+    /// Whether clicking the description runs its own action.
+    pub has_description_action: bool,
+    /// Whether the query exactly matches this item.
+    pub is_exact_match: bool,
+    /// Pinned items can be dragged to reorder them.
+    pub is_pinned: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -45,12 +130,45 @@ pub struct ListItemId {
     pub plugin_id: Key,
 }
 
+/// A section of a title with its own style.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TextSpan {
+    pub text: String,
+    /// A CSS hex color.
+    pub color: Option<String>,
+    pub bold: bool,
+}
+
+/// Part of a list item's description.
+///
+/// Descriptions are only split into links if the plugin requested it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "build", derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase", tag = "kind")]
-pub enum Icon {
-    File { path: PathBuf },
+pub enum DescriptionSegment {
     Text { text: String },
+    Link { url: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "build", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum Icon {
+    /// Symbolic icons are monochrome and should be recolored to match
+    /// the theme.
+    File {
+        path: PathBuf,
+        symbolic: bool,
+    },
+    Text {
+        text: String,
+    },
+    /// An image given by the plugin, as a `data:` URL.
+    Image {
+        src: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
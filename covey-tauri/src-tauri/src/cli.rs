@@ -0,0 +1,44 @@
+//! Command line arguments.
+//!
+//! ```sh
+//! covey [QUERY...]
+//! ```
+//!
+//! If a query is provided, the menu is opened with the query filled in.
+
+/// Gets the query to initially open the menu with, from the command
+/// line arguments excluding the binary name.
+///
+/// Multiple arguments are joined with spaces.
+pub fn initial_query(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let args: Vec<_> = args.into_iter().collect();
+    (!args.is_empty()).then(|| args.join(" "))
+}
+
+/// Gets the query that launching covey again forwards to the running
+/// instance, from every argument of the new process.
+///
+/// The first argument is the binary, which is skipped.
+pub fn forwarded_query(args: Vec<String>) -> Option<String> {
+    initial_query(args.into_iter().skip(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::forwarded_query;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| arg.to_owned()).collect()
+    }
+
+    #[test]
+    fn second_launch_forwards_its_query() {
+        assert_eq!(
+            forwarded_query(args(&["covey", "@g ", "rust"])).as_deref(),
+            Some("@g  rust")
+        );
+        // launching without a query only shows the menu
+        assert_eq!(forwarded_query(args(&["/usr/bin/covey"])), None);
+        assert_eq!(forwarded_query(vec![]), None);
+    }
+}
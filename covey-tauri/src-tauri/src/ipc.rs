@@ -1,4 +1,5 @@
 use color_eyre::eyre::Result;
+use covey::MouseButton;
 use covey_config::{config::GlobalConfig, manifest::PluginManifest};
 use covey_tauri_types::{Event, ListItemId};
 use tauri::{ipc::Channel, Manager, State, WebviewWindowBuilder};
@@ -29,6 +30,20 @@ pub fn query(state: State<'_, AppState>, text: String) {
     tokio::spawn(state.host().query(text));
 }
 
+/// Requests more results for the shown list, if the plugin has any.
+#[tauri::command]
+pub fn load_more(state: State<'_, AppState>) {
+    if let Some(fut) = state.host().load_more() {
+        tokio::spawn(fut);
+    }
+}
+
+/// Replaces the input with a suggested query.
+#[tauri::command]
+pub fn select_suggestion(state: State<'_, AppState>, text: String) {
+    tokio::spawn(state.host().set_input(covey::Input::new(text)));
+}
+
 #[tauri::command]
 pub fn activate(state: State<'_, AppState>, list_item_id: ListItemId, command_name: String) {
     let state = &state;
@@ -42,6 +57,158 @@ pub fn activate(state: State<'_, AppState>, list_item_id: ListItemId, command_na
     }
 }
 
+/// Previews the selected item.
+#[tauri::command]
+pub fn select(state: State<'_, AppState>, list_item_id: ListItemId) {
+    if let Some(item) = state.find_list_item(&list_item_id) {
+        tokio::spawn(state.host().select(item));
+    }
+}
+
+/// Runs the action attached to an item's description.
+#[tauri::command]
+pub fn activate_description(state: State<'_, AppState>, list_item_id: ListItemId) {
+    let id = list_item_id;
+    let fut = state
+        .find_list_item(&id)
+        .and_then(|item| state.host().activate_description(&item));
+
+    if let Some(fut) = fut {
+        tokio::spawn(fut);
+    } else {
+        tracing::warn!("list item with id {id:?} has no description action")
+    }
+}
+
+/// Runs the configured action for a query without results.
+#[tauri::command]
+pub fn activate_without_results(state: State<'_, AppState>) {
+    if let Some(fut) = state.host().activate_without_results() {
+        tokio::spawn(fut);
+    }
+}
+
+/// Stores the new order of pinned items after one was dragged.
+#[tauri::command]
+pub fn reorder_pins(state: State<'_, AppState>, list_item_ids: Vec<ListItemId>) {
+    let items: Option<Vec<_>> = list_item_ids
+        .iter()
+        .map(|id| state.find_list_item(id))
+        .collect();
+
+    if let Some(fut) = items.and_then(|items| state.host().reorder_pins(items)) {
+        tokio::spawn(fut);
+    } else {
+        tracing::warn!("items {list_item_ids:?} are not pinned items of one plugin")
+    }
+}
+
+/// Activates the first item with this shortcut.
+#[tauri::command]
+pub fn activate_shortcut(state: State<'_, AppState>, key: String) {
+    if let Some(fut) = state.host().activate_shortcut(&key) {
+        tokio::spawn(fut);
+    } else {
+        tracing::warn!("no item has the shortcut {key:?}")
+    }
+}
+
+/// Activates an item with the command for a mouse button, numbered
+/// like the DOM's `MouseEvent.button`.
+#[tauri::command]
+pub fn click(state: State<'_, AppState>, list_item_id: ListItemId, button: u8) {
+    let button = match button {
+        1 => MouseButton::Middle,
+        3 => MouseButton::Back,
+        4 => MouseButton::Forward,
+        _ => return,
+    };
+    let id = list_item_id;
+
+    if let Some(item) = state.find_list_item(&id) {
+        if let Some(fut) = state.host().click(item, button) {
+            tokio::spawn(fut);
+        }
+    } else {
+        tracing::warn!("list item with id {id:?} not found")
+    }
+}
+
+/// Answers the question asked before activating an item.
+#[tauri::command]
+pub fn confirm(state: State<'_, AppState>, confirmed: bool) {
+    state.host().confirm(confirmed);
+}
+
+#[tauri::command]
+pub fn pop_context(state: State<'_, AppState>) {
+    if let Some(fut) = state.host().pop_context() {
+        tokio::spawn(fut);
+    }
+}
+
+/// Sets the input to the correction suggested by the list.
+#[tauri::command]
+pub fn apply_correction(state: State<'_, AppState>) {
+    if let Some(fut) = state.host().apply_correction() {
+        tokio::spawn(fut);
+    }
+}
+
+/// Removes the prefix chip from the query.
+#[tauri::command]
+pub fn remove_prefix(state: State<'_, AppState>) {
+    if let Some(fut) = state.host().remove_prefix() {
+        tokio::spawn(fut);
+    }
+}
+
+/// Lists the commands of an item, if it has more than one.
+#[tauri::command]
+pub fn open_actions_menu(state: State<'_, AppState>, list_item_id: ListItemId) {
+    let id = list_item_id;
+    if let Some(item) = state.find_list_item(&id) {
+        state.host().open_actions_menu(&item);
+    } else {
+        tracing::warn!("list item with id {id:?} not found")
+    }
+}
+
+#[tauri::command]
+pub fn filter_actions_menu(state: State<'_, AppState>, query: String) {
+    state.host().filter_actions_menu(&query);
+}
+
+/// Activates the command at this index of the actions menu.
+#[tauri::command]
+pub fn select_action(state: State<'_, AppState>, index: usize) {
+    if let Some(fut) = state.host().select_action(index) {
+        tokio::spawn(fut);
+    }
+}
+
+#[tauri::command]
+pub fn close_actions_menu(state: State<'_, AppState>) {
+    state.host().close_actions_menu();
+}
+
+/// Leaves a context, clears the query or closes the menu.
+#[tauri::command]
+pub fn escape(state: State<'_, AppState>) {
+    tokio::spawn(state.host().escape());
+}
+
+/// Formats a unix timestamp relative to now, like "5m ago".
+#[tauri::command]
+pub fn format_relative_time(timestamp: i64) -> String {
+    covey::time::format_relative_to_now(timestamp)
+}
+
+#[tauri::command]
+pub fn open_url(state: State<'_, AppState>, url: String) {
+    state.host().open_url(url);
+}
+
 #[tauri::command]
 pub fn show_settings_window(app: tauri::AppHandle) {
     let window = app.get_webview_window("settings").unwrap_or_else(|| {
@@ -74,12 +241,21 @@ pub fn set_global_config(state: State<'_, AppState>, config: GlobalConfig) {
     state.host().reload(config)
 }
 
+/// Re-reads the config file, applying any changes made to it.
+#[tauri::command]
+pub fn reload_config(state: State<'_, AppState>) {
+    state.host().reload_config()
+}
+
 #[tauri::command]
 pub fn get_manifest(state: State<'_, AppState>, plugin_name: String) -> Option<PluginManifest> {
-    state
-        .host()
-        .plugins()
-        .get(&*plugin_name)
-        .map(|plugin| plugin.manifest())
-        .cloned()
+    state.host().plugins().get(&*plugin_name).map(|plugin| {
+        // prefer what the plugin reports about itself
+        let mut manifest = plugin.manifest().clone();
+        manifest.name = plugin.display_name().to_owned();
+        manifest.description = plugin.description().map(ToOwned::to_owned);
+        manifest.authors = plugin.author().into_iter().collect();
+        manifest.schema = plugin.settings_schema().clone();
+        manifest
+    })
 }
@@ -1,3 +1,4 @@
+mod cli;
 mod ipc;
 mod state;
 mod window;
@@ -11,9 +12,19 @@ use tauri::{
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let initial_query = cli::initial_query(std::env::args().skip(1));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+        // Launching covey again while it's running exits the new process
+        // and runs this callback in the existing one instead. The instance
+        // is registered with DBus on Linux and a named mutex on Windows,
+        // which are released by the OS if covey crashes, so there is no
+        // stale lock to clean up.
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            if let Some(query) = cli::forwarded_query(args) {
+                app.state::<AppState>().set_query(query);
+            }
             window::show_menu(app);
         }))
         .plugin(tauri_plugin_opener::init())
@@ -21,7 +32,20 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_fs::init())
         .setup(|app| {
-            app.manage(AppState::new());
+            let start_hidden = covey::Host::read_config().map_or_else(
+                |e| {
+                    tracing::warn!("failed to read config: {e:#}");
+                    false
+                },
+                |config| config.start_hidden,
+            );
+            let show_on_launch = window::shows_on_launch(start_hidden, initial_query.is_some());
+            if !show_on_launch {
+                tracing::info!(
+                    "starting hidden, launch covey again or click the tray icon to show it"
+                );
+            }
+            app.manage(AppState::new(initial_query));
 
             TrayIconBuilder::new()
                 .icon(app.default_window_icon().unwrap().clone())
@@ -70,20 +94,45 @@ pub fn run() {
                         if !*focused {
                             main_window.hide().unwrap();
                         }
+                        main_window.state::<AppState>().set_visible(*focused);
                     }
                     _ => {}
                 }
             });
 
+            if show_on_launch {
+                window::show_menu(app.handle());
+            }
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             ipc::setup,
             ipc::query,
             ipc::activate,
+            ipc::click,
+            ipc::activate_shortcut,
+            ipc::activate_description,
+            ipc::reorder_pins,
+            ipc::activate_without_results,
+            ipc::select,
+            ipc::select_suggestion,
+            ipc::load_more,
+            ipc::confirm,
+            ipc::pop_context,
+            ipc::remove_prefix,
+            ipc::apply_correction,
+            ipc::open_actions_menu,
+            ipc::filter_actions_menu,
+            ipc::select_action,
+            ipc::close_actions_menu,
+            ipc::escape,
+            ipc::open_url,
+            ipc::format_relative_time,
             ipc::show_settings_window,
             ipc::get_global_config,
             ipc::set_global_config,
+            ipc::reload_config,
             ipc::get_manifest,
         ])
         .run(tauri::generate_context!())
@@ -1,24 +1,30 @@
-use std::sync::OnceLock;
+use std::{num::NonZeroU16, path::PathBuf, sync::OnceLock};
 
-use color_eyre::eyre::Result;
-use covey::{Frontend, Host};
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use color_eyre::eyre::{Context as _, Result};
+use covey::{Frontend, Host, Input, Toast};
+use covey_tauri_types::{DescriptionSegment, Icon, ListItemId, TextSpan};
 pub use covey_tauri_types::{Event, ListItem, ListStyle};
-use covey_tauri_types::{Icon, ListItemId};
+use parking_lot::Mutex;
 use tauri::{ipc::Channel, Manager};
 use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_opener::OpenerExt;
 
 use crate::window;
 
 /// Must be initialised exactly once with [`AppState::init`].
 pub struct AppState {
     inner: OnceLock<Host>,
+    /// Query to set once the host is initialised.
+    initial_query: Mutex<Option<String>>,
 }
 
 impl AppState {
-    pub fn new() -> Self {
+    pub fn new(initial_query: Option<String>) -> Self {
         Self {
             inner: OnceLock::new(),
+            initial_query: Mutex::new(initial_query),
         }
     }
 
@@ -34,9 +40,32 @@ impl AppState {
                 .unwrap_or_else(|_| tracing::warn!("already set up"));
         }
 
+        if let Some(query) = self.initial_query.lock().take() {
+            tokio::spawn(self.host().set_input(Input::new(query)));
+        }
+
         Ok(())
     }
 
+    /// Sets the query of the menu, or waits until the host is
+    /// initialised to set it.
+    pub fn set_query(&self, query: String) {
+        if let Some(host) = self.inner.get() {
+            tokio::spawn(host.set_input(Input::new(query)));
+        } else {
+            *self.initial_query.lock() = Some(query);
+        }
+    }
+
+    /// Notifies plugins that the menu was shown or hidden.
+    ///
+    /// Does nothing if this has not been initialised yet.
+    pub fn set_visible(&self, visible: bool) {
+        if let Some(host) = self.inner.get() {
+            tokio::spawn(host.set_visible(visible));
+        }
+    }
+
     /// # Panics
     /// Panics if this has not been initialised yet.
     pub fn host(&self) -> &Host {
@@ -47,30 +76,13 @@ impl AppState {
         &self,
         lis: impl ExactSizeIterator<Item = covey::ListItem>,
     ) -> Vec<ListItem> {
-        lis.map(|li| {
-            let icon: Option<Icon> = match li.icon() {
-                Some(covey::Icon::Name(name)) => freedesktop_icons::lookup(&name)
-                    .with_cache()
-                    .with_size(48)
-                    .find()
-                    .map(|path| Icon::File { path }),
-                Some(covey::Icon::Text(text)) => Some(Icon::Text { text }),
-                None => None,
-            };
-            let id = ListItemId {
-                local_id: li.id().local_id.to_string(),
-                plugin_id: li.id().plugin.id().to_owned(),
-            };
+        let appearance = self.host().config().appearance;
+        let icons = IconLookup {
+            size: appearance.icon_size.map_or(48, NonZeroU16::get),
+            theme: appearance.icon_theme,
+        };
 
-            ListItem {
-                title: li.title().to_owned(),
-                description: li.description().to_owned(),
-                icon,
-                id,
-                available_commands: li.available_commands().to_vec(),
-            }
-        })
-        .collect()
+        convert_list_items(lis, &icons)
     }
 
     pub fn find_list_item(&self, id: &ListItemId) -> Option<covey::ListItemId> {
@@ -92,8 +104,8 @@ impl covey::Frontend for EventChannel {
         window::hide_menu(&self.app);
     }
 
-    fn copy(&mut self, str: String) {
-        self.app.clipboard().write_text(str).unwrap();
+    fn copy(&mut self, str: String) -> Result<()> {
+        Ok(self.app.clipboard().write_text(str)?)
     }
 
     fn set_input(&mut self, input: covey::Input) {
@@ -105,16 +117,122 @@ impl covey::Frontend for EventChannel {
             .unwrap();
     }
 
+    fn clear_input(&mut self) {
+        self.channel.send(Event::ClearInput).unwrap();
+    }
+
+    fn focus_input(&mut self, select_all: bool) {
+        self.channel.send(Event::FocusInput { select_all }).unwrap();
+    }
+
+    fn set_suggestions(&mut self, suggestions: Vec<String>) {
+        self.channel
+            .send(Event::SetSuggestions { suggestions })
+            .unwrap();
+    }
+
     fn set_list(&mut self, list: covey::List) {
         let state = self.app.state::<AppState>();
+        let summary = list.summary();
         self.channel
             .send(Event::SetList {
                 items: state.register_list_items(list.items.into_iter()),
                 style: list.style.map(list_style_from_covey),
+                header: list.header,
+                summary,
+                correction: list.correction.map(|input| input.contents),
             })
             .unwrap();
     }
 
+    fn append_list(&mut self, list: covey::List) {
+        let state = self.app.state::<AppState>();
+        self.channel
+            .send(Event::AppendList {
+                items: state.register_list_items(list.items.into_iter()),
+            })
+            .unwrap();
+    }
+
+    fn set_selection(&mut self, index: usize) {
+        self.channel.send(Event::SetSelection { index }).unwrap();
+    }
+
+    fn update_item(&mut self, old: covey::ListItemId, item: covey::ListItem) {
+        let state = self.app.state::<AppState>();
+        let id = ListItemId {
+            local_id: old.local_id.to_string(),
+            plugin_id: old.plugin.id().to_owned(),
+        };
+        let item = state
+            .register_list_items(std::iter::once(item))
+            .pop()
+            .expect("one item should be converted");
+        self.channel
+            .send(Event::UpdateItem {
+                id,
+                item: Box::new(item),
+            })
+            .unwrap();
+    }
+
+    fn set_placeholder(&mut self, placeholder: String) {
+        self.channel
+            .send(Event::SetPlaceholder { placeholder })
+            .unwrap();
+    }
+
+    fn set_prefix_chip(&mut self, prefix: Option<String>) {
+        self.channel.send(Event::SetPrefixChip { prefix }).unwrap();
+    }
+
+    fn set_actions_menu(&mut self, titles: Option<Vec<String>>) {
+        self.channel.send(Event::SetActionsMenu { titles }).unwrap();
+    }
+
+    fn set_contexts(&mut self, labels: Vec<String>) {
+        self.channel.send(Event::SetContexts { labels }).unwrap();
+    }
+
+    fn flash(&mut self) {
+        self.channel.send(Event::Flash).unwrap();
+    }
+
+    fn toggle_compact(&mut self) {
+        self.channel.send(Event::ToggleCompact).unwrap();
+    }
+
+    fn set_preview(&mut self, preview: Option<String>) {
+        self.channel
+            .send(Event::SetPreview { text: preview })
+            .unwrap();
+    }
+
+    fn open_preview_window(&mut self, content: String) {
+        window::open_preview(&self.app, &content);
+    }
+
+    fn open_url(&mut self, url: String) -> Result<()> {
+        self.app
+            .opener()
+            .open_url(&url, None::<&str>)
+            .wrap_err(format!("failed to open {url}"))
+    }
+
+    fn confirm(&mut self, prompt: String) {
+        self.channel.send(Event::Confirm { prompt }).unwrap();
+    }
+
+    fn show_toast(&mut self, toast: Toast) {
+        let (message, success) = match toast {
+            Toast::Success(message) => (message, true),
+            Toast::Failure(message) => (message, false),
+        };
+        self.channel
+            .send(Event::ShowToast { message, success })
+            .unwrap();
+    }
+
     fn display_error(&mut self, title: &str, error: color_eyre::eyre::Report) {
         self.app
             .notification()
@@ -126,6 +244,117 @@ impl covey::Frontend for EventChannel {
     }
 }
 
+/// Icon shown for named icons that aren't in any theme.
+const FALLBACK_ICON: &str = "image-missing";
+
+/// How to find named icons.
+struct IconLookup {
+    size: u16,
+    /// Theme to look in before the default theme.
+    theme: Option<String>,
+}
+
+impl IconLookup {
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        find_icon(name, self.theme.as_deref(), |name, theme| {
+            let lookup = freedesktop_icons::lookup(name)
+                .with_cache()
+                .with_size(self.size);
+            match theme {
+                Some(theme) => lookup.with_theme(theme).find(),
+                None => lookup.find(),
+            }
+        })
+    }
+}
+
+/// Finds a named icon in the theme, then in the default theme (the
+/// theme [`None`]), then falls back to a generic icon.
+fn find_icon(
+    name: &str,
+    theme: Option<&str>,
+    lookup: impl Fn(&str, Option<&str>) -> Option<PathBuf>,
+) -> Option<PathBuf> {
+    [name, FALLBACK_ICON].into_iter().find_map(|name| {
+        theme
+            .and_then(|theme| lookup(name, Some(theme)))
+            .or_else(|| lookup(name, None))
+    })
+}
+
+fn convert_list_items(
+    lis: impl Iterator<Item = covey::ListItem>,
+    icons: &IconLookup,
+) -> Vec<ListItem> {
+    lis.map(|li| {
+        let icon: Option<Icon> = match li.icon() {
+            Some(covey::Icon::Name(name)) => icons.find(&name).map(|path| Icon::File {
+                path,
+                symbolic: li.has_symbolic_icon(),
+            }),
+            Some(covey::Icon::Text(text)) => Some(Icon::Text { text }),
+            Some(covey::Icon::Bytes { mime, data }) => Some(Icon::Image {
+                src: format!("data:{mime};base64,{}", BASE64_STANDARD.encode(data)),
+            }),
+            None => None,
+        };
+        let id = ListItemId {
+            local_id: li.id().local_id.to_string(),
+            plugin_id: li.id().plugin.id().to_owned(),
+        };
+
+        ListItem {
+            title: li.title().to_owned(),
+            title_spans: li
+                .title_spans()
+                .into_iter()
+                .map(|span| TextSpan {
+                    text: span.text,
+                    color: span.color,
+                    bold: span.bold,
+                })
+                .collect(),
+            description: description_segments(&li),
+            full_description: li.full_description().map(ToOwned::to_owned),
+            icon,
+            id,
+            available_commands: li.available_commands().to_vec(),
+            children: convert_list_items(li.children().into_iter(), icons),
+            is_error: li.is_error(),
+            timestamp: li.timestamp(),
+            shortcut: li.shortcut().map(ToOwned::to_owned),
+            has_description_action: li.has_description_action(),
+            is_exact_match: li.is_exact_match(),
+            is_pinned: li.is_pinned(),
+        }
+    })
+    .collect()
+}
+
+fn description_segments(li: &covey::ListItem) -> Vec<DescriptionSegment> {
+    use covey::linkify::{segments, Segment};
+
+    if li.description().is_empty() {
+        vec![]
+    } else if li.linkify_description() {
+        segments(&li.description())
+            .into_iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => DescriptionSegment::Text {
+                    text: text.to_owned(),
+                },
+                Segment::Url(url) => DescriptionSegment::Link {
+                    url: url.to_owned(),
+                },
+            })
+            .collect()
+    } else {
+        vec![DescriptionSegment::Text {
+            text: li.description().into_owned(),
+        }]
+    }
+}
+
 fn list_style_from_covey(value: covey::ListStyle) -> ListStyle {
     match value {
         covey::ListStyle::Rows => ListStyle::Rows,
@@ -133,3 +362,35 @@ fn list_style_from_covey(value: covey::ListStyle) -> ListStyle {
         covey::ListStyle::GridWithColumns(columns) => ListStyle::GridWithColumns { columns },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{find_icon, FALLBACK_ICON};
+
+    #[test]
+    fn missing_icons_fall_back_to_default_theme() {
+        // the chosen theme only has `folder`, the default theme has both
+        let lookup = |name: &str, theme: Option<&str>| {
+            let found = match theme {
+                Some("Papirus") => name == "folder",
+                Some(_) => false,
+                None => name == "folder" || name == "firefox" || name == FALLBACK_ICON,
+            };
+            found.then(|| PathBuf::from(format!("{}/{name}", theme.unwrap_or("hicolor"))))
+        };
+
+        let find = |name| find_icon(name, Some("Papirus"), lookup);
+        assert_eq!(find("folder"), Some(PathBuf::from("Papirus/folder")));
+        assert_eq!(find("firefox"), Some(PathBuf::from("hicolor/firefox")));
+        assert_eq!(
+            find("not-an-icon"),
+            Some(PathBuf::from(format!("hicolor/{FALLBACK_ICON}")))
+        );
+        assert_eq!(
+            find_icon("firefox", None, lookup),
+            Some(PathBuf::from("hicolor/firefox"))
+        );
+    }
+}
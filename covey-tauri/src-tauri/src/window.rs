@@ -1,11 +1,16 @@
 //! Utilities for managing the window
 
-use tauri::Manager;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use tauri::{Manager, WebviewUrl, WebviewWindowBuilder};
+
+use crate::state::AppState;
 
 pub fn hide_menu(app: &tauri::AppHandle) {
     eprintln!("hiding window");
     if let Some(window) = app.get_webview_window("main") {
         window.hide().unwrap();
+        app.state::<AppState>().set_visible(false);
     } else {
         eprintln!("WARN: main window was not found");
     }
@@ -24,3 +29,47 @@ pub fn show_menu(app: &tauri::AppHandle) {
         eprintln!("WARN: main window was not found");
     }
 }
+
+/// Whether the menu should be shown as soon as covey starts.
+///
+/// A hidden menu is only shown once covey is launched again or its tray
+/// icon is clicked, which focuses it. A query given on the command line
+/// always shows the menu.
+pub fn shows_on_launch(start_hidden: bool, has_initial_query: bool) -> bool {
+    !start_hidden || has_initial_query
+}
+
+/// Opens a new window showing the text, leaving the menu open.
+///
+/// Every preview gets its own window, so several can be open at once.
+pub fn open_preview(app: &tauri::AppHandle, content: &str) {
+    static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+    let label = format!("preview-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+
+    // the page reads the content from this global when it loads
+    let script = format!(
+        "window.__COVEY_PREVIEW__ = {};",
+        serde_json::to_string(content).expect("strings can be serialized")
+    );
+    let result = WebviewWindowBuilder::new(app, label, WebviewUrl::App("/preview".into()))
+        .title("covey preview")
+        .inner_size(480.0, 360.0)
+        .always_on_top(true)
+        .initialization_script(&script)
+        .build();
+    if let Err(e) = result {
+        tracing::warn!("failed to open preview window: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shows_on_launch;
+
+    #[test]
+    fn hidden_start_waits_to_be_shown() {
+        assert!(shows_on_launch(false, false));
+        assert!(!shows_on_launch(true, false));
+        assert!(shows_on_launch(true, true));
+    }
+}
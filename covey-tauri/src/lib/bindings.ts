@@ -2,8 +2,13 @@
  * Re-exported bindings with some overridden to make records required.
  */
 
+import type { ActivationFeedback } from "./bindings/ActivationFeedback";
+import type { Appearance } from "./bindings/Appearance";
 import type { Command } from "./bindings/Command";
+import type { DescriptionSegment } from "./bindings/DescriptionSegment";
+import type { EscapeAction } from "./bindings/EscapeAction";
 import type { Event } from "./bindings/Event";
+import type { FrecencyStrategy } from "./bindings/FrecencyStrategy";
 import type { Hotkey } from "./bindings/Hotkey";
 import type { Icon } from "./bindings/Icon";
 import type { Key } from "./bindings/Key";
@@ -12,6 +17,8 @@ import type { KeyedList } from "./bindings/KeyedList";
 import type { ListItem } from "./bindings/ListItem";
 import type { ListItemId } from "./bindings/ListItemId";
 import type { ListStyle } from "./bindings/ListStyle";
+import type { MatchAlgorithm } from "./bindings/MatchAlgorithm";
+import type { MouseButtons } from "./bindings/MouseButtons";
 import type { PluginConfig as PluginConfigBinding } from "./bindings/PluginConfig";
 import type { PluginConfigSchema as PluginConfigSchemaBinding } from "./bindings/PluginConfigSchema";
 import type { PluginManifest } from "./bindings/PluginManifest";
@@ -24,11 +31,19 @@ import type { SchemaMap as SchemaMapBinding } from "./bindings/SchemaMap";
 import type { SchemaStruct as SchemaStructBinding } from "./bindings/SchemaStruct";
 import type { SchemaText } from "./bindings/SchemaText";
 import type { SchemaType as SchemaTypeBinding } from "./bindings/SchemaType";
+import type { Shell } from "./bindings/Shell";
+import type { TextOverflow } from "./bindings/TextOverflow";
+import type { TextSpan } from "./bindings/TextSpan";
 import type { JsonValue as JsonValueBinding } from "./bindings/serde_json/JsonValue";
 
 export type {
+  ActivationFeedback,
+  Appearance,
   Command,
+  DescriptionSegment,
+  EscapeAction,
   Event,
+  FrecencyStrategy,
   GlobalConfig,
   Hotkey,
   Icon,
@@ -39,6 +54,8 @@ export type {
   ListItem,
   ListItemId,
   ListStyle,
+  MatchAlgorithm,
+  MouseButtons,
   PluginConfig,
   PluginConfigSchema,
   PluginManifest,
@@ -51,6 +68,9 @@ export type {
   SchemaStruct,
   SchemaText,
   SchemaType,
+  Shell,
+  TextOverflow,
+  TextSpan,
 };
 
 type JsonValue =
@@ -83,6 +103,29 @@ type PluginConfigSchema = PluginConfigSchemaBinding & {
 
 type GlobalConfig = {
   plugins: KeyedList<PluginConfig>;
+  type_text_delay_ms: number;
+  clear_query_on_activate: boolean;
+  appearance: Appearance;
+  query_transform: string | null;
+  activation_feedback: ActivationFeedback;
+  timeout_ms: number | null;
+  max_concurrent_queries: number | null;
+  log_queries: boolean;
+  shell: Shell;
+  event_socket: boolean;
+  min_score: number | null;
+  match_algorithm: MatchAlgorithm;
+  frecency: FrecencyStrategy;
+  show_recent: boolean;
+  max_message_bytes: number | null;
+  mouse_buttons: MouseButtons;
+  max_displayed_results: number;
+  aliases: Record<string, string>;
+  show_expanded_aliases: boolean;
+  results_delay_ms: number;
+  max_description_chars: number | null;
+  commands_prefix: string | null;
+  escape: EscapeAction;
 };
 
 type PluginConfig = PluginConfigBinding & {
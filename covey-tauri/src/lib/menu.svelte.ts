@@ -2,17 +2,112 @@
 
 import { Channel, invoke } from "@tauri-apps/api/core";
 
-import type { Event, Hotkey, ListItem, ListStyle } from "./bindings";
+import type {
+  Appearance,
+  Event,
+  GlobalConfig,
+  Hotkey,
+  ListItem,
+  ListItemId,
+  ListStyle,
+} from "./bindings";
 import * as keys from "./keys";
 
+/** A list item that is shown, which may be nested in an expanded item. */
+export type Row = {
+  item: ListItem;
+  depth: number;
+  /** Index of the parent row, if this is nested. */
+  parent: number | undefined;
+};
+
+const itemKey = (item: ListItem) => `${item.id.pluginId}/${item.id.localId}`;
+
+/** Replaces the item with the key `old`, searching nested items too. */
+const replaceItem = (
+  items: ListItem[],
+  old: string,
+  replacement: ListItem,
+): ListItem[] =>
+  items.map((item) =>
+    itemKey(item) === old
+      ? replacement
+      : { ...item, children: replaceItem(item.children, old, replacement) },
+  );
+
+/** Flattens the items into rows, including the children of expanded items. */
+const flattenRows = (
+  items: ListItem[],
+  expanded: Set<string>,
+  depth = 0,
+  parent: number | undefined = undefined,
+  rows: Row[] = [],
+): Row[] => {
+  for (const item of items) {
+    const index = rows.length;
+    rows.push({ item, depth, parent });
+    if (expanded.has(itemKey(item))) {
+      flattenRows(item.children, expanded, depth + 1, index, rows);
+    }
+  }
+  return rows;
+};
+
 export class Menu {
   public items = $state<ListItem[]>([]);
+  /** Keys of the items that are expanded. */
+  private expanded = $state<Set<string>>(new Set());
+  /** Items that are shown. `selection` is an index into this. */
+  public rows = $derived(flattenRows(this.items, this.expanded));
   public style = $state<ListStyle | undefined>();
+  /** Status line from the plugin, shown above the list. */
+  public header = $state<string | undefined>();
+  /** Corrected query suggested by the plugin. Hidden if undefined. */
+  public correction = $state<string | undefined>();
+  /** Number of results and where they came from. Hidden if undefined. */
+  public summary = $state<string | undefined>();
   public selection = $state<number>(0);
   public inputText = $state<string>("");
+  /** Prefix of the active plugin, shown before the input instead of in it. */
+  public prefixChip = $state<string | undefined>();
+  /** Breadcrumbs of the contexts navigated into, outermost first. */
+  public contexts = $state<string[]>([]);
+  /** Queries suggested by the active plugin, shown above the list. */
+  public suggestions = $state<string[]>([]);
+  /** Placeholder provided by the active plugin. Empty to use the default. */
+  public placeholder = $state<string>("");
   // this is only updated by plugins, so no need to keep live
   // with the actual selection when changed by UI
   public textSelection = $state<[number, number]>([0, 0]);
+  /** Incremented whenever a plugin asks for the input to be focused. */
+  public focuses = $state<number>(0);
+  /** Incremented whenever the menu should flash after an activation. */
+  public flashes = $state<number>(0);
+  /** Full content of the selected item. Hidden if undefined. */
+  public preview = $state<string | undefined>();
+  /** Titles of the selected item's commands. Hidden if undefined. */
+  public actionsMenu = $state<string[] | undefined>();
+  public actionsSelection = $state<number>(0);
+  /** Question to answer before an item is activated. */
+  public confirmation = $state<string | undefined>();
+  /** Whether the last action worked. Hidden after a short time. */
+  public toast = $state<{ message: string; success: boolean } | undefined>();
+  private toastTimeout: ReturnType<typeof setTimeout> | undefined;
+  /** Incremented whenever the menu is shown, to refresh relative times. */
+  public shows = $state<number>(0);
+  public appearance = $state<Appearance>({
+    icon_size: null,
+    row_spacing: 16,
+    show_descriptions: true,
+    text_overflow: "wrap",
+    max_lines: null,
+    compact: false,
+    icon_theme: null,
+  });
+  /** Whether descriptions are hidden and rows are smaller. */
+  public compact = $state<boolean>(false);
+  /** Whether the next change of the input shouldn't be queried. */
+  private skipQuery = false;
 
   private constructor() {}
 
@@ -25,30 +120,329 @@ export class Menu {
         case "setInput":
           self.inputText = msg.contents;
           self.textSelection = msg.selection;
+          self.stripPrefixChip();
+          break;
+        case "clearInput":
+          // the host already cleared the list
+          self.skipQuery = self.inputText !== "";
+          self.inputText = "";
+          self.textSelection = [0, 0];
+          break;
+        case "focusInput":
+          self.focuses += 1;
+          if (msg.selectAll) {
+            self.textSelection = [0, self.inputText.length];
+          }
           break;
         case "setList":
           self.items = msg.items;
+          self.expanded = new Set();
           self.style = msg.style ?? undefined;
-          self.selection = 0;
+          self.summary = msg.summary ?? undefined;
+          self.header = msg.header ?? undefined;
+          self.correction = msg.correction ?? undefined;
+          self.selection = Math.max(
+            0,
+            self.rows.findIndex((row) => !row.item.isError),
+          );
+          break;
+        case "appendList":
+          self.items = [...self.items, ...msg.items];
+          break;
+        case "setSuggestions":
+          self.suggestions = msg.suggestions;
+          break;
+        case "setSelection":
+          self.selection = msg.index;
+          break;
+        case "updateItem": {
+          const old = `${msg.id.pluginId}/${msg.id.localId}`;
+          self.items = replaceItem(self.items, old, msg.item);
+          // keep the replacement expanded if the old item was
+          if (self.expanded.has(old)) {
+            self.expanded = new Set(
+              [...self.expanded].map((key) =>
+                key === old ? itemKey(msg.item) : key,
+              ),
+            );
+          }
+          break;
+        }
+        case "setPlaceholder":
+          self.placeholder = msg.placeholder;
+          break;
+        case "setPrefixChip":
+          self.prefixChip = msg.prefix ?? undefined;
+          self.stripPrefixChip();
+          break;
+        case "setContexts":
+          self.contexts = msg.labels;
+          break;
+        case "flash":
+          self.flashes += 1;
+          break;
+        case "toggleCompact":
+          self.toggleCompact();
+          break;
+        case "setPreview":
+          self.preview = msg.text ?? undefined;
+          break;
+        case "setActionsMenu":
+          self.actionsMenu = msg.titles ?? undefined;
+          self.actionsSelection = 0;
+          break;
+        case "confirm":
+          self.confirmation = msg.prompt;
+          break;
+        case "showToast":
+          self.showToast(msg.message, msg.success);
           break;
       }
     };
 
     await invoke("setup", { events });
+    const config = await invoke<GlobalConfig>("get_global_config");
+    self.appearance = config.appearance;
+    self.compact = config.appearance.compact;
     return self;
   }
 
+  /** Re-reads the config file, then applies its appearance. */
+  public async reloadConfig() {
+    await invoke("reload_config");
+    const config = await invoke<GlobalConfig>("get_global_config");
+    this.appearance = config.appearance;
+    this.compact = config.appearance.compact;
+  }
+
+  /** Switches between compact and normal rows, until the config is reloaded. */
+  public toggleCompact() {
+    this.compact = !this.compact;
+  }
+
   public query() {
-    void invoke("query", { text: this.inputText });
+    // read before returning, so that effects calling this keep tracking it
+    const text = (this.prefixChip ?? "") + this.inputText;
+    if (this.skipQuery) {
+      this.skipQuery = false;
+      return;
+    }
+    void invoke("query", { text });
+  }
+
+  /** Removes the prefix from the input while it is shown as a chip. */
+  private stripPrefixChip() {
+    const prefix = this.prefixChip;
+    if (prefix === undefined || !this.inputText.startsWith(prefix)) return;
+
+    this.inputText = this.inputText.slice(prefix.length);
+    this.textSelection = [
+      Math.max(0, this.textSelection[0] - prefix.length),
+      Math.max(0, this.textSelection[1] - prefix.length),
+    ];
+  }
+
+  /** Replaces the input with the suggested correction. */
+  public applyCorrection() {
+    this.correction = undefined;
+    void invoke("apply_correction");
+  }
+
+  /** Removes the prefix chip, leaving its plugin. */
+  public removePrefix() {
+    void invoke("remove_prefix");
+  }
+
+  /** Replaces the input with a suggested query. */
+  private showToast(message: string, success: boolean) {
+    clearTimeout(this.toastTimeout);
+    this.toast = { message, success };
+    this.toastTimeout = setTimeout(() => {
+      this.toast = undefined;
+    }, 2000);
+  }
+
+  /** Answers the confirmation question, activating the item if confirmed. */
+  public confirm(confirmed: boolean) {
+    this.confirmation = undefined;
+    void invoke("confirm", { confirmed });
+  }
+
+  /** Requests more results when scrolled to the end of the list. */
+  public loadMore() {
+    void invoke("load_more");
+  }
+
+  public selectSuggestion(text: string) {
+    void invoke("select_suggestion", { text });
+  }
+
+  /** Leaves the innermost context, restoring the previous query. */
+  /** Leaves a context, clears the query or closes the menu. */
+  public escape() {
+    void invoke("escape");
+  }
+
+  public popContext() {
+    void invoke("pop_context");
+  }
+
+  /**
+   * Moves the selection up or down, skipping over error items.
+   *
+   * The selection stays where it is if there is nothing to move to.
+   */
+  public moveSelection(step: 1 | -1) {
+    for (
+      let i = this.selection + step;
+      i >= 0 && i < this.rows.length;
+      i += step
+    ) {
+      if (!this.rows[i].item.isError) {
+        this.selection = i;
+        return;
+      }
+    }
   }
 
   public activate(name: string) {
+    if (this.rows.length === 0 && name === "activate") {
+      void invoke("activate_without_results");
+      return;
+    }
+    const row = this.rows[this.selection] as Row | undefined;
+    // error items can't be activated
+    if (row === undefined || row.item.isError) return;
+
     void invoke("activate", {
-      listItemId: this.items[this.selection].id,
+      listItemId: row.item.id,
       commandName: name,
     });
   }
 
+  /** Lists the commands of the selected item to choose from. */
+  public openActionsMenu() {
+    const row = this.rows[this.selection] as Row | undefined;
+    if (row === undefined || row.item.isError) return;
+    void invoke("open_actions_menu", { listItemId: row.item.id });
+  }
+
+  public filterActionsMenu(query: string) {
+    void invoke("filter_actions_menu", { query });
+  }
+
+  /** Activates the command at this index of the actions menu. */
+  public selectAction(index: number) {
+    this.actionsMenu = undefined;
+    void invoke("select_action", { index });
+  }
+
+  public closeActionsMenu() {
+    this.actionsMenu = undefined;
+    void invoke("close_actions_menu");
+  }
+
+  /** Runs the action attached to the description of this item. */
+  /** Asks for a preview of the selected item. */
+  public previewSelection() {
+    const row = this.rows[this.selection] as Row | undefined;
+    if (row === undefined || row.item.isError) {
+      this.preview = undefined;
+      return;
+    }
+    void invoke("select", { listItemId: row.item.id });
+  }
+
+  public activateDescription(id: ListItemId) {
+    void invoke("activate_description", { listItemId: id });
+  }
+
+  /**
+   * Activates the first item with this shortcut.
+   *
+   * @returns Whether any item has the shortcut.
+   */
+  public activateShortcut(key: string): boolean {
+    if (!this.rows.some((row) => row.item.shortcut === key)) return false;
+
+    void invoke("activate_shortcut", { key });
+    return true;
+  }
+
+  /**
+   * Activates the item at this row with the command configured for a
+   * mouse button, selecting it first.
+   */
+  public click(index: number, button: number) {
+    const row = this.rows[index] as Row | undefined;
+    if (row === undefined || row.item.isError) return;
+
+    this.selection = index;
+    void invoke("click", { listItemId: row.item.id, button });
+  }
+
+  /**
+   * Moves the pinned item at row `from` to the place of the pinned item
+   * at row `to`, storing the new order of the plugin's pins.
+   */
+  public movePin(from: number, to: number) {
+    const moved = this.rows[from] as Row | undefined;
+    const target = this.rows[to] as Row | undefined;
+    if (moved === undefined || target === undefined || from === to) return;
+    const pluginId = moved.item.id.pluginId;
+    if (!target.item.isPinned || target.item.id.pluginId !== pluginId) return;
+
+    const pins = this.rows
+      .map((row) => row.item)
+      .filter((item) => item.isPinned && item.id.pluginId === pluginId);
+    const toPin = pins.indexOf(target.item);
+    pins.splice(pins.indexOf(moved.item), 1);
+    pins.splice(toPin, 0, moved.item);
+    void invoke("reorder_pins", {
+      listItemIds: pins.map((item) => item.id),
+    });
+  }
+
+  /**
+   * Expands the selected item, or moves into its children if it is
+   * already expanded.
+   *
+   * @returns Whether the selected item has children.
+   */
+  public expandSelection(): boolean {
+    const row = this.rows[this.selection] as Row | undefined;
+    if (row === undefined || row.item.children.length === 0) return false;
+
+    const key = itemKey(row.item);
+    if (this.expanded.has(key)) {
+      this.selection += 1;
+    } else {
+      this.expanded = new Set([...this.expanded, key]);
+    }
+    return true;
+  }
+
+  /**
+   * Collapses the selected item, or moves to its parent if it is not
+   * expanded.
+   *
+   * @returns Whether the selected item is expanded or nested.
+   */
+  public collapseSelection(): boolean {
+    const row = this.rows[this.selection] as Row | undefined;
+    if (row === undefined) return false;
+
+    const key = itemKey(row.item);
+    if (this.expanded.has(key)) {
+      this.expanded = new Set([...this.expanded].filter((k) => k !== key));
+      return true;
+    } else if (row.parent !== undefined) {
+      this.selection = row.parent;
+      return true;
+    }
+    return false;
+  }
+
   // TODO: retrieve command settings from rust side
   // make left click = enter.
   public maybeHotkeyActivate(ev: KeyboardEvent) {
@@ -68,6 +462,15 @@ export class Menu {
     // this.activate("activate");
   }
 
+  /** Formats a unix timestamp relative to now, like "5m ago". */
+  public formatRelativeTime(timestamp: number): Promise<string> {
+    return invoke<string>("format_relative_time", { timestamp });
+  }
+
+  public openUrl(url: string) {
+    void invoke("open_url", { url });
+  }
+
   public showSettingsWindow() {
     void invoke("show_settings_window");
   }
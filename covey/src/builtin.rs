@@ -0,0 +1,133 @@
+//! Commands of covey itself, like reloading the config.
+//!
+//! These are shown by a built-in plugin when the query starts with the
+//! configured `commands_prefix`, so that they can be searched for like
+//! any other result.
+
+use color_eyre::eyre::{eyre, Result};
+
+use crate::proto;
+
+/// A command that covey runs itself when its item is activated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BuiltinCommand {
+    ReloadConfig,
+    ToggleCompact,
+    OpenConfig,
+}
+
+impl BuiltinCommand {
+    const ALL: [Self; 3] = [Self::ReloadConfig, Self::ToggleCompact, Self::OpenConfig];
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::ReloadConfig => "Reload config",
+            Self::ToggleCompact => "Toggle compact mode",
+            Self::OpenConfig => "Open config file",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::ReloadConfig => "Re-read the config file and restart changed plugins",
+            Self::ToggleCompact => "Switch between compact and normal rows",
+            Self::OpenConfig => "Edit the config file with the default editor",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Self::ReloadConfig => "view-refresh",
+            Self::ToggleCompact => "view-list",
+            Self::OpenConfig => "document-edit",
+        }
+    }
+
+    /// The command of an item returned by [`query`].
+    pub(crate) fn from_id(id: u64) -> Result<Self> {
+        usize::try_from(id)
+            .ok()
+            .and_then(|index| Self::ALL.get(index))
+            .copied()
+            .ok_or_else(|| eyre!("no built-in command with ID {id}"))
+    }
+}
+
+/// Lists the commands that fuzzy match the query, best match first.
+pub(crate) fn query(query: &str) -> proto::QueryResponse {
+    let mut matches: Vec<_> = BuiltinCommand::ALL
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, command)| Some((match_score(query, command.title())?, index, command)))
+        .collect();
+    // stable, so equal matches keep their order
+    matches.sort_by_key(|&(score, ..)| std::cmp::Reverse(score));
+
+    let items = matches
+        .into_iter()
+        .map(|(_, index, command)| proto::ListItem {
+            id: index as u64,
+            title: command.title().to_owned(),
+            description: command.description().to_owned(),
+            icon: Some(proto::list_item::Icon::Name(command.icon().to_owned())),
+            available_commands: vec!["activate".to_owned()],
+            ..Default::default()
+        })
+        .collect();
+    proto::QueryResponse {
+        items,
+        ..Default::default()
+    }
+}
+
+/// Scores how well the query matches the text, ignoring case.
+///
+/// Every character of the query must appear in the text in order.
+/// Characters that follow the previous match or start a word score
+/// higher. Returns [`None`] if the text doesn't match.
+pub(crate) fn match_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = next + text[next..].iter().position(|&t| t == c)?;
+        score += if found == 0 || text[found - 1] == ' ' {
+            3
+        } else if found == next && next > 0 {
+            2
+        } else {
+            1
+        };
+        next = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_score, query, BuiltinCommand};
+
+    #[test]
+    fn commands_are_fuzzy_matched() {
+        let titles = |q: &str| -> Vec<String> {
+            query(q).items.into_iter().map(|item| item.title).collect()
+        };
+        assert_eq!(titles("reload"), ["Reload config"]);
+        assert_eq!(titles("cfg"), ["Reload config", "Open config file"]);
+        assert_eq!(titles("compact"), ["Toggle compact mode"]);
+        assert!(titles("xyz").is_empty());
+        assert_eq!(titles("").len(), BuiltinCommand::ALL.len());
+
+        assert!(match_score("oc", "Open config file") > match_score("oc", "Reload config"));
+    }
+
+    #[test]
+    fn items_map_back_to_commands() {
+        let item = &query("reload").items[0];
+        assert_eq!(
+            BuiltinCommand::from_id(item.id).unwrap(),
+            BuiltinCommand::ReloadConfig
+        );
+        assert!(BuiltinCommand::from_id(100).is_err());
+    }
+}
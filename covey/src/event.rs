@@ -1,17 +1,49 @@
 //! Actions returned by a plugin.
 
 use core::fmt;
+use std::{borrow::Cow, cmp::Ordering, path::PathBuf, time::Duration};
 
 use az::SaturatingAs as _;
 
-use crate::{proto, Plugin};
+use crate::{builtin::BuiltinCommand, proto, Plugin};
 
 /// Event returned by a plugin.
 pub(crate) enum PluginEvent {
     /// Set the displayed list.
-    SetList { list: List, index: u64 },
-    /// Run a sequence of actions.
+    SetList {
+        list: List,
+        /// Placeholder of the plugin that provided the list, if the
+        /// query was empty.
+        placeholder: String,
+        index: u64,
+    },
+    /// Set the suggested queries, after the list with the same index
+    /// has been set.
+    SetSuggestions {
+        suggestions: Vec<String>,
+        index: u64,
+    },
+    /// Add more results to the end of the list with the same index.
+    AppendList { list: List, index: u64 },
+    /// Run a sequence of actions, without waiting for the commands of
+    /// earlier actions to exit.
     Run(Vec<Action>),
+    /// Run a sequence of actions, waiting for the command of each action
+    /// to exit before starting the next.
+    RunSequentially(Vec<Action>),
+    /// A query was replaced by a newer one before it finished, or an
+    /// activation was not confirmed.
+    Cancelled,
+}
+
+impl PluginEvent {
+    /// Runs the actions with the execution policy.
+    pub(crate) fn run(actions: Vec<Action>, execution: ExecutionPolicy) -> Self {
+        match execution {
+            ExecutionPolicy::Parallel => Self::Run(actions),
+            ExecutionPolicy::Sequential => Self::RunSequentially(actions),
+        }
+    }
 }
 
 impl fmt::Debug for PluginEvent {
@@ -21,18 +53,116 @@ impl fmt::Debug for PluginEvent {
                 .debug_tuple("PluginEvent::SetList")
                 .field(&format!("{} items", list.len()))
                 .finish(),
+            Self::AppendList { list, .. } => f
+                .debug_tuple("PluginEvent::AppendList")
+                .field(&format!("{} items", list.len()))
+                .finish(),
+            Self::SetSuggestions { suggestions, .. } => f
+                .debug_tuple("PluginEvent::SetSuggestions")
+                .field(suggestions)
+                .finish(),
             Self::Run(actions) => f.debug_tuple("PluginEvent::Run").field(actions).finish(),
+            Self::RunSequentially(actions) => f
+                .debug_tuple("PluginEvent::RunSequentially")
+                .field(actions)
+                .finish(),
+            Self::Cancelled => f.write_str("PluginEvent::Cancelled"),
         }
     }
 }
 
-#[derive(Debug)]
+/// How the actions from an activation are run.
+///
+/// [`Action::Close`] is always run after every other action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ExecutionPolicy {
+    #[default]
+    Parallel,
+    Sequential,
+}
+
+impl ExecutionPolicy {
+    pub(crate) fn from_proto(proto: proto::ExecutionPolicy) -> Self {
+        match proto {
+            proto::ExecutionPolicy::Parallel => Self::Parallel,
+            proto::ExecutionPolicy::Sequential => Self::Sequential,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[expect(
+    clippy::large_enum_variant,
+    reason = "actions are short-lived, so boxing the item isn't worth it"
+)]
 pub(crate) enum Action {
     Close,
     RunCommand(String, Vec<String>),
     RunShell(String),
     Copy(String),
     SetInput(Input),
+    OpenUrl(String),
+    TypeText(String),
+    PushContext {
+        label: String,
+        input: Input,
+    },
+    UpdateItem {
+        key: String,
+        item: ListItem,
+    },
+    FocusEntry {
+        select_all: bool,
+    },
+    WriteFile {
+        path: PathBuf,
+        contents: String,
+        append: bool,
+    },
+    /// Run one of covey's own commands.
+    Builtin(BuiltinCommand),
+    /// Walk the user through a form, then submit it to the plugin.
+    ShowForm {
+        plugin: Plugin,
+        form: Form,
+    },
+    OpenPreviewWindow {
+        content: String,
+    },
+    /// Empty the input and the list without querying.
+    ClearInput,
+    /// Copy a field of the activated item, which is replaced with
+    /// [`Action::Copy`] when the item is activated.
+    CopyField(String),
+}
+
+/// Prompts that are filled in one at a time with the input.
+#[derive(Debug, Clone)]
+pub(crate) struct Form {
+    pub(crate) id: String,
+    pub(crate) fields: Vec<FormField>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FormField {
+    pub(crate) label: String,
+    pub(crate) placeholder: String,
+}
+
+impl Form {
+    pub(crate) fn from_proto(proto: proto::Form) -> Self {
+        Self {
+            id: proto.id,
+            fields: proto
+                .fields
+                .into_iter()
+                .map(|field| FormField {
+                    label: field.label,
+                    placeholder: field.placeholder.unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
 }
 
 /// The main text input contents and selection.
@@ -41,9 +171,23 @@ pub struct Input {
     pub contents: String,
     /// Range in terms of chars, not bytes
     pub selection: (u16, u16),
+    /// Index of the list item to select once the results of this
+    /// input are shown.
+    pub(crate) select_item: Option<u32>,
 }
 
 impl Input {
+    /// An input with the cursor at the end of the contents.
+    pub fn new(contents: impl Into<String>) -> Self {
+        let contents = contents.into();
+        let len = contents.chars().count().saturating_as();
+        Self {
+            contents,
+            selection: (len, len),
+            select_item: None,
+        }
+    }
+
     pub(crate) fn prefix_with(&mut self, prefix: &str) {
         self.contents.insert_str(0, prefix);
         let prefix_len =
@@ -57,6 +201,7 @@ impl Input {
         let mut input = Self {
             contents: il.query,
             selection: (il.range_lb.saturating_as(), il.range_ub.saturating_as()),
+            select_item: il.select_item,
         };
         input.prefix_with(plugin.prefix());
         input
@@ -68,6 +213,27 @@ impl Input {
 pub struct List {
     pub items: Vec<ListItem>,
     pub style: Option<ListStyle>,
+    /// A status line to show above the results.
+    pub header: Option<String>,
+    /// A corrected query to suggest with "Did you mean ...?", which is
+    /// applied with [`Host::apply_correction`](crate::Host::apply_correction).
+    pub correction: Option<Input>,
+    /// Actions to run instead of activating the first item.
+    pub(crate) default_actions: Vec<Action>,
+    /// How to request more results, if the plugin has more.
+    pub(crate) next_page: Option<NextPage>,
+    /// Time after which the plugin should be queried again to replace
+    /// the results.
+    pub(crate) refresh: Option<Duration>,
+}
+
+/// A request for the next page of a plugin's results.
+#[derive(Debug, Clone)]
+pub(crate) struct NextPage {
+    pub(crate) plugin: Plugin,
+    /// The query that the plugin was given, without its prefix.
+    pub(crate) query: String,
+    pub(crate) token: String,
 }
 
 impl List {
@@ -79,14 +245,107 @@ impl List {
         self.items.is_empty()
     }
 
-    pub(crate) fn from_proto(plugin: &Plugin, proto: proto::QueryResponse) -> Self {
+    /// A short summary of the number of results and where they came from,
+    /// like "3 results from Open".
+    ///
+    /// Returns [`None`] if the list is empty.
+    pub fn summary(&self) -> Option<String> {
+        let mut plugins: Vec<&Plugin> = self.items.iter().map(ListItem::plugin).collect();
+        plugins.sort();
+        plugins.dedup();
+
+        let results = match self.len() {
+            0 => return None,
+            1 => "1 result".to_owned(),
+            n => format!("{n} results"),
+        };
+        Some(match *plugins {
+            [plugin] => format!("{results} from {}", plugin.display_name()),
+            _ => format!("{results} from {} plugins", plugins.len()),
+        })
+    }
+
+    /// Converts the results of the `query`, which doesn't include the
+    /// plugin's prefix.
+    pub(crate) fn from_proto(plugin: &Plugin, query: &str, proto: proto::QueryResponse) -> Self {
+        let next_page = proto.next_page_token.map(|token| NextPage {
+            plugin: Plugin::clone(plugin),
+            query: query.to_owned(),
+            token,
+        });
         let style = proto.list_style.map(ListStyle::from_proto);
-        let list: Vec<_> = proto
+        let mut list: Vec<_> = proto
             .items
             .into_iter()
             .map(|li| ListItem::new(Plugin::clone(plugin), li))
             .collect();
-        Self { style, items: list }
+        if let Some(key) = &proto.sort_key {
+            sort_by_metadata(&mut list, key, proto.sort_desc.unwrap_or(false));
+        }
+        Self {
+            style,
+            items: list,
+            header: proto.header,
+            correction: proto
+                .correction
+                .map(|input| Input::from_proto(plugin, input)),
+            default_actions: plugin.map_proto_actions(proto.default_actions),
+            next_page,
+            refresh: proto
+                .refresh_ms
+                .filter(|&ms| ms > 0)
+                .map(|ms| Duration::from_millis(ms.into())),
+        }
+    }
+}
+
+/// Sorts the items by the value of `key` in their metadata, keeping the
+/// order of equal items.
+///
+/// Values are compared as numbers if every value is a number. Items
+/// without the key are sorted last.
+fn sort_by_metadata(items: &mut [ListItem], key: &str, descending: bool) {
+    let numeric = items
+        .iter()
+        .filter_map(|item| item.item.metadata.get(key))
+        .all(|value| value.parse::<f64>().is_ok());
+    items.sort_by(
+        |a, b| match (a.item.metadata.get(key), b.item.metadata.get(key)) {
+            (Some(a), Some(b)) => {
+                let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+                    (Ok(a), Ok(b)) if numeric => a.total_cmp(&b),
+                    _ => a.cmp(b),
+                };
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    );
+}
+
+/// The result of querying a plugin.
+#[derive(Debug)]
+pub(crate) enum QueryOutcome {
+    /// The plugin handled the query, possibly with no results.
+    Handled(List),
+    /// The plugin declined the query, so the next matching plugin
+    /// should be queried.
+    Passthrough,
+}
+
+impl QueryOutcome {
+    pub(crate) fn from_proto(plugin: &Plugin, query: &str, proto: proto::QueryResponse) -> Self {
+        if proto.handled.unwrap_or(true) {
+            Self::Handled(List::from_proto(plugin, query, proto))
+        } else {
+            Self::Passthrough
+        }
     }
 }
 
@@ -114,11 +373,23 @@ impl ListStyle {
 pub struct ListItem {
     plugin: Plugin,
     item: proto::ListItem,
+    /// Maximum number of characters of the description to show.
+    max_description_chars: Option<usize>,
 }
 
 impl ListItem {
     pub(crate) fn new(plugin: Plugin, item: proto::ListItem) -> Self {
-        Self { plugin, item }
+        Self {
+            plugin,
+            item,
+            max_description_chars: None,
+        }
+    }
+
+    /// Truncates the description of this item and its children to at
+    /// most `max` characters.
+    pub(crate) fn limit_description(&mut self, max: usize) {
+        self.max_description_chars = Some(max);
     }
 
     pub fn plugin(&self) -> &Plugin {
@@ -129,12 +400,158 @@ impl ListItem {
         &self.item.title
     }
 
-    pub fn description(&self) -> &str {
-        &self.item.description
+    /// The description, ending with an ellipsis if it was longer than
+    /// the configured maximum length.
+    pub fn description(&self) -> Cow<'_, str> {
+        let description = &self.item.description;
+        let Some(max) = self.max_description_chars else {
+            return Cow::Borrowed(description);
+        };
+        if description.char_indices().nth(max).is_none() {
+            return Cow::Borrowed(description);
+        }
+        // leave room for the ellipsis
+        let end = description
+            .char_indices()
+            .nth(max.saturating_sub(1))
+            .map_or(description.len(), |(i, _)| i);
+        Cow::Owned(format!("{}…", description[..end].trim_end()))
+    }
+
+    /// The whole description, if [`description`](Self::description) was
+    /// truncated.
+    pub fn full_description(&self) -> Option<&str> {
+        matches!(self.description(), Cow::Owned(_)).then_some(&*self.item.description)
+    }
+
+    /// The value of a field that [`Action::CopyField`] can copy: the
+    /// title, the whole description or a value in the metadata.
+    pub(crate) fn field(&self, name: &str) -> Option<&str> {
+        match name {
+            "title" => Some(&self.item.title),
+            "description" => Some(&self.item.description),
+            _ => self.item.metadata.get(name).map(String::as_str),
+        }
+    }
+
+    /// Styled text to show instead of the title.
+    ///
+    /// If this is empty, the plain [`title`](Self::title) should be shown.
+    pub fn title_spans(&self) -> Vec<TextSpan> {
+        self.item
+            .title_spans
+            .iter()
+            .map(|span| TextSpan {
+                text: span.text.clone(),
+                color: span.color.clone().filter(|color| is_hex_color(color)),
+                bold: span.bold(),
+            })
+            .collect()
+    }
+
+    /// Whether this item is an error message.
+    ///
+    /// Error items should be shown with an error style, and should not
+    /// be selectable or activatable.
+    pub fn is_error(&self) -> bool {
+        self.item.is_error()
+    }
+
+    /// When this item happened, in seconds since the unix epoch.
+    ///
+    /// Use [`crate::time::format_relative_to_now`] to show it.
+    pub fn timestamp(&self) -> Option<i64> {
+        self.item.timestamp
+    }
+
+    /// A key that activates this item when pressed, like `"1"`.
+    ///
+    /// Use [`Host::activate_shortcut`](crate::Host::activate_shortcut)
+    /// to activate it.
+    pub fn shortcut(&self) -> Option<&str> {
+        self.item.shortcut.as_deref()
+    }
+
+    /// Whether the description can be clicked to run an action.
+    ///
+    /// Use [`Host::activate_description`](crate::Host::activate_description)
+    /// to run it.
+    pub fn has_description_action(&self) -> bool {
+        self.item.description_action.is_some()
+    }
+
+    /// Whether the query exactly matches this item, in which case it
+    /// should be highlighted.
+    ///
+    /// The first exact match in a list is selected when it is shown.
+    pub fn is_exact_match(&self) -> bool {
+        self.item.exact_match()
+    }
+
+    /// Whether this item is pinned, so it can be dragged to reorder the
+    /// pins with [`Host::reorder_pins`](crate::Host::reorder_pins).
+    pub fn is_pinned(&self) -> bool {
+        self.item.pinned()
     }
 
+    /// The question to ask the user before activating this item, if
+    /// it needs to be confirmed.
+    pub fn confirmation_prompt(&self) -> Option<String> {
+        if !self.item.requires_confirmation() {
+            return None;
+        }
+        Some(
+            self.item.confirmation_prompt.clone().unwrap_or_else(|| {
+                format!("Are you sure you want to activate \"{}\"?", self.title())
+            }),
+        )
+    }
+
+    /// The action to run when the description is clicked.
+    pub(crate) fn description_action(&self) -> Option<Action> {
+        let action = self.item.description_action.as_deref()?.clone();
+        self.plugin.map_proto_actions(vec![action]).pop()
+    }
+
+    /// The action to run when the item is activated, instead of asking
+    /// its plugin.
+    pub(crate) fn inline_action(&self) -> Option<Action> {
+        let action = self.item.inline_action.as_deref()?.clone();
+        self.plugin.map_proto_actions(vec![action]).pop()
+    }
+
+    /// ID set by the plugin to refer to this item in later actions.
+    pub(crate) fn key(&self) -> Option<&str> {
+        self.item.key.as_deref()
+    }
+
+    /// Whether URLs in the description should be shown as links.
+    ///
+    /// Use [`crate::linkify`] to find the URLs.
+    pub fn linkify_description(&self) -> bool {
+        self.item.linkify_description()
+    }
+
+    /// The item's icon, or [`None`] if it has no icon or the icon can't
+    /// be shown.
     pub fn icon(&self) -> Option<Icon> {
-        self.item.icon.clone().map(Icon::from_proto)
+        self.item.icon.clone().and_then(Icon::from_proto)
+    }
+
+    /// Whether the icon is a named monochrome icon, which should be
+    /// recolored to match the theme.
+    ///
+    /// This is true for icons with names ending in `-symbolic`, or if the
+    /// plugin said the icon is symbolic.
+    pub fn has_symbolic_icon(&self) -> bool {
+        match &self.item.icon {
+            Some(proto::list_item::Icon::Name(name)) => {
+                self.item.symbolic_icon() || name.ends_with("-symbolic")
+            }
+            Some(proto::list_item::Icon::Text(_) | proto::list_item::Icon::Bytes(_)) | None => {
+                false
+            }
+        }
     }
 
     pub fn id(&self) -> ListItemId {
@@ -147,6 +564,19 @@ impl ListItem {
     pub fn available_commands(&self) -> &[String] {
         &self.item.available_commands
     }
+
+    /// Nested items, which should be hidden until this item is expanded.
+    pub fn children(&self) -> Vec<ListItem> {
+        self.item
+            .children
+            .iter()
+            .map(|child| ListItem {
+                plugin: Plugin::clone(&self.plugin),
+                item: child.clone(),
+                max_description_chars: self.max_description_chars,
+            })
+            .collect()
+    }
 }
 
 impl fmt::Debug for ListItem {
@@ -156,10 +586,30 @@ impl fmt::Debug for ListItem {
             .field("title", &self.item.title)
             .field("description", &self.item.description)
             .field("icon", &self.item.icon)
+            .field("max_description_chars", &self.max_description_chars)
             .finish()
     }
 }
 
+/// A section of text with its own style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSpan {
+    pub text: String,
+    /// A CSS hex color, like `#ff8800`.
+    pub color: Option<String>,
+    pub bold: bool,
+}
+
+/// Whether the color is a CSS hex color, like `#f80` or `#ff8800`.
+///
+/// Colors from plugins are only allowed in this form so that they can't
+/// inject anything else into the style.
+fn is_hex_color(color: &str) -> bool {
+    color.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
 /// A list item without rendering details (description, etc).
 ///
 /// Used by the model to call functions on this list item.
@@ -167,25 +617,322 @@ impl fmt::Debug for ListItem {
 /// This should usually be constructed by [`ListItem::id`]. However,
 /// all fields are public, so it can be constructed elsewhere. This
 /// struct does not guarantee that the local ID is known to the plugin.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ListItemId {
     pub plugin: Plugin,
     /// ID unique within the plugin.
     pub local_id: u64,
 }
 
+/// A short message about whether an action worked, which should only
+/// be shown briefly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Toast {
+    Success(String),
+    Failure(String),
+}
+
 #[derive(Debug, Clone)]
 pub enum Icon {
     Name(String),
     Text(String),
+    /// An encoded image with its MIME type, which is one of
+    /// [`Icon::IMAGE_TYPES`].
+    Bytes {
+        mime: String,
+        data: Vec<u8>,
+    },
 }
 
 impl Icon {
-    pub(crate) fn from_proto(proto: proto::list_item::Icon) -> Self {
+    /// MIME types of images that can be used as icons.
+    pub const IMAGE_TYPES: [&str; 5] = [
+        "image/png",
+        "image/jpeg",
+        "image/gif",
+        "image/webp",
+        "image/svg+xml",
+    ];
+
+    /// Largest image that can be used as an icon, in bytes.
+    pub const MAX_IMAGE_SIZE: usize = 1024 * 1024;
+
+    /// Converts the icon from a plugin, returning [`None`] if it is an
+    /// image that can't be shown.
+    pub(crate) fn from_proto(proto: proto::list_item::Icon) -> Option<Self> {
         use proto::list_item::Icon as Proto;
         match proto {
-            Proto::Name(name) => Self::Name(name),
-            Proto::Text(text) => Self::Text(text),
+            Proto::Name(name) => Some(Self::Name(name)),
+            Proto::Text(text) => Some(Self::Text(text)),
+            Proto::Bytes(bytes) => Self::from_bytes(bytes),
+        }
+    }
+
+    /// Converts an image from a plugin, returning [`None`] if it is too
+    /// large or of an unsupported type.
+    pub(crate) fn from_bytes(proto::IconBytes { mime, data }: proto::IconBytes) -> Option<Self> {
+        if !Self::IMAGE_TYPES.contains(&mime.as_str()) {
+            tracing::warn!("ignoring icon with unsupported type {mime}");
+            return None;
+        }
+        if data.len() > Self::MAX_IMAGE_SIZE {
+            tracing::warn!("ignoring {mime} icon of {} bytes", data.len());
+            return None;
+        }
+        Some(Self::Bytes { mime, data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Icon, Input, List, ListItem, TextSpan};
+    use crate::{proto, Plugin};
+
+    fn plugin(id: &str, name: &str) -> Plugin {
+        Plugin::with_manifest(
+            toml::from_str(&format!("id = {id:?}\nprefix = \"\"")).unwrap(),
+            toml::from_str(&format!("name = {name:?}")).unwrap(),
+        )
+    }
+
+    fn list(plugins: &[&Plugin]) -> List {
+        List {
+            items: plugins
+                .iter()
+                .map(|&plugin| ListItem::new(plugin.clone(), proto::ListItem::default()))
+                .collect(),
+            style: None,
+            default_actions: vec![],
+            next_page: None,
+            header: None,
+            correction: None,
+            refresh: None,
         }
     }
+
+    #[test]
+    fn summary() {
+        let open = plugin("open", "Open");
+        let qalc = plugin("qalc", "Calculator");
+
+        assert_eq!(list(&[]).summary(), None);
+        assert_eq!(list(&[&open]).summary().unwrap(), "1 result from Open");
+        assert_eq!(
+            list(&[&open, &open, &open]).summary().unwrap(),
+            "3 results from Open"
+        );
+        assert_eq!(
+            list(&[&open, &qalc, &open]).summary().unwrap(),
+            "3 results from 2 plugins"
+        );
+    }
+
+    #[test]
+    fn input_selection_is_after_prefix() {
+        let open = Plugin::with_manifest(
+            toml::from_str("id = \"open\"\nprefix = \"@\"").unwrap(),
+            toml::from_str("name = \"Open\"").unwrap(),
+        );
+        let input = Input::from_proto(
+            &open,
+            proto::Input {
+                query: "firefox".to_owned(),
+                range_lb: 4,
+                range_ub: u32::from(u16::MAX),
+                select_item: None,
+            },
+        );
+
+        assert_eq!(input.contents, "@firefox");
+        assert_eq!(input.selection, (5, u16::MAX));
+    }
+
+    #[test]
+    fn header() {
+        let open = plugin("open", "Open");
+        let response = |header: Option<&str>| proto::QueryResponse {
+            items: vec![proto::ListItem::default()],
+            header: header.map(ToOwned::to_owned),
+            ..Default::default()
+        };
+
+        let list = List::from_proto(&open, "", response(Some("Showing 1 of 230 issues")));
+        assert_eq!(list.header.as_deref(), Some("Showing 1 of 230 issues"));
+        assert_eq!(List::from_proto(&open, "", response(None)).header, None);
+    }
+
+    #[test]
+    fn items_are_sorted_by_metadata() {
+        let top = plugin("top", "Processes");
+        let item = |title: &str, cpu: Option<&str>| proto::ListItem {
+            title: title.to_owned(),
+            metadata: cpu
+                .map(|cpu| [("cpu".to_owned(), cpu.to_owned())].into())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let response = proto::QueryResponse {
+            items: vec![
+                item("idle", None),
+                item("shell", Some("2")),
+                item("browser", Some("10")),
+                item("editor", Some("1.5")),
+            ],
+            sort_key: Some("cpu".to_owned()),
+            sort_desc: Some(true),
+            ..Default::default()
+        };
+
+        let list = List::from_proto(&top, "", response);
+        let titles: Vec<_> = list.items.iter().map(ListItem::title).collect();
+        // compared as numbers, not "2" > "10"
+        assert_eq!(titles, ["browser", "shell", "editor", "idle"]);
+    }
+
+    #[test]
+    fn children() {
+        let open = plugin("open", "Open");
+        let item = ListItem::new(
+            open,
+            proto::ListItem {
+                id: 1,
+                title: "repo".to_owned(),
+                children: vec![proto::ListItem {
+                    id: 2,
+                    title: "main".to_owned(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        let children = item.children();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].title(), "main");
+        assert_eq!(children[0].id().local_id, 2);
+        assert!(children[0].children().is_empty());
+    }
+
+    #[test]
+    fn symbolic_icons() {
+        let open = plugin("open", "Open");
+        let item = |icon, symbolic_icon| {
+            ListItem::new(
+                open.clone(),
+                proto::ListItem {
+                    icon: Some(icon),
+                    symbolic_icon,
+                    ..Default::default()
+                },
+            )
+        };
+        let name = |name: &str| proto::list_item::Icon::Name(name.to_owned());
+
+        assert!(item(name("edit-copy-symbolic"), None).has_symbolic_icon());
+        assert!(item(name("mono-folder"), Some(true)).has_symbolic_icon());
+        assert!(!item(name("firefox"), None).has_symbolic_icon());
+        assert!(!item(name("firefox"), Some(false)).has_symbolic_icon());
+        // text can't be recolored like an image
+        let text = proto::list_item::Icon::Text("x".to_owned());
+        assert!(!item(text, Some(true)).has_symbolic_icon());
+    }
+
+    #[test]
+    fn image_icons_are_checked() {
+        let open = plugin("open", "Open");
+        let icon = |mime: &str, data: Vec<u8>| {
+            ListItem::new(
+                open.clone(),
+                proto::ListItem {
+                    icon: Some(proto::list_item::Icon::Bytes(proto::IconBytes {
+                        mime: mime.to_owned(),
+                        data,
+                    })),
+                    ..Default::default()
+                },
+            )
+            .icon()
+        };
+        let png = b"\x89PNG\r\n\x1a\n".to_vec();
+
+        assert!(matches!(
+            icon("image/png", png.clone()),
+            Some(Icon::Bytes { mime, data }) if mime == "image/png" && data == png
+        ));
+        assert!(icon("image/svg+xml", b"<svg/>".to_vec()).is_some());
+        assert!(icon("application/pdf", png).is_none());
+        assert!(icon("image/png", vec![0; Icon::MAX_IMAGE_SIZE + 1]).is_none());
+    }
+
+    #[test]
+    fn long_descriptions_are_truncated() {
+        let open = plugin("open", "Open");
+        let item = |description: &str| {
+            let mut item = ListItem::new(
+                open.clone(),
+                proto::ListItem {
+                    description: description.to_owned(),
+                    children: vec![proto::ListItem {
+                        description: description.to_owned(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            );
+            item.limit_description(8);
+            item
+        };
+
+        let long = item("/home/user/Documents");
+        assert_eq!(long.description(), "/home/u…");
+        assert_eq!(long.full_description(), Some("/home/user/Documents"));
+        assert_eq!(long.children()[0].description(), "/home/u…");
+
+        // characters, not bytes, are counted
+        let exact = item("ééééé éé");
+        assert_eq!(exact.description(), "ééééé éé");
+        assert_eq!(exact.full_description(), None);
+        assert_eq!(item("ééééé éé é").description(), "ééééé é…");
+    }
+
+    #[test]
+    fn title_spans() {
+        let open = plugin("open", "Open");
+        let item = |title_spans| {
+            ListItem::new(
+                open.clone(),
+                proto::ListItem {
+                    title: "let x".to_owned(),
+                    title_spans,
+                    ..Default::default()
+                },
+            )
+        };
+        let span = |text: &str, color: Option<&str>, bold| proto::TextSpan {
+            text: text.to_owned(),
+            color: color.map(ToOwned::to_owned),
+            bold: Some(bold),
+        };
+
+        assert!(item(vec![]).title_spans().is_empty());
+        assert_eq!(
+            item(vec![
+                span("let", Some("#c678dd"), true),
+                span(" <x>", Some("red; background: url(x)"), false),
+            ])
+            .title_spans(),
+            [
+                TextSpan {
+                    text: "let".to_owned(),
+                    color: Some("#c678dd".to_owned()),
+                    bold: true
+                },
+                TextSpan {
+                    text: " <x>".to_owned(),
+                    color: None,
+                    bold: false
+                }
+            ]
+        );
+    }
 }
@@ -0,0 +1,283 @@
+//! Events sent to external tools over a local socket.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::Duration,
+};
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use tracing::debug;
+
+/// Something that happened in covey that external tools may want to
+/// react to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub(crate) enum ExternalEvent {
+    Shown,
+    Hidden,
+    Activated { title: String },
+}
+
+type Client = Box<dyn Write + Send>;
+
+/// A message for the thread that writes to clients.
+enum Message {
+    Client(Client),
+    Line(String),
+    /// Replies with the number of connected clients once every earlier
+    /// message has been handled.
+    #[cfg(test)]
+    Flush(mpsc::Sender<usize>),
+}
+
+/// A socket that sends every [`ExternalEvent`] to all connected clients,
+/// as a line of JSON.
+///
+/// Clients only need to connect and read lines: anything they write
+/// is ignored. Writing happens on a separate thread, so slow clients
+/// never block the sender.
+pub(crate) struct EventSocket {
+    sender: mpsc::Sender<Message>,
+    path: Option<PathBuf>,
+    /// Tells the listening thread to stop accepting clients.
+    closed: Arc<AtomicBool>,
+}
+
+impl EventSocket {
+    /// Listens for clients on a Unix socket at `path`.
+    ///
+    /// Any existing file at the path is replaced.
+    ///
+    /// # Errors
+    /// Returns an error if the socket could not be created, or if this
+    /// is not a Unix platform.
+    #[cfg(unix)]
+    pub(crate) fn bind(path: &Path) -> Result<Self> {
+        use std::os::unix::net::UnixListener;
+
+        use color_eyre::eyre::Context as _;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // left over from a previous run
+        _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path)
+            .context(format!("failed to bind socket at {}", path.display()))?;
+
+        let sender = Self::spawn_writer(vec![]);
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let client_sender = sender.clone();
+        let listener_closed = Arc::clone(&closed);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if listener_closed.load(Ordering::Acquire) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                // a client that stops reading shouldn't hold up other clients
+                _ = stream.set_write_timeout(Some(Duration::from_millis(100)));
+                debug!("external event client connected");
+                if client_sender
+                    .send(Message::Client(Box::new(stream)))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            sender,
+            path: Some(path.to_owned()),
+            closed,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn bind(_path: &Path) -> Result<Self> {
+        color_eyre::eyre::bail!("the event socket is only supported on Unix")
+    }
+
+    /// Spawns the thread that writes lines to the clients, which stops
+    /// once every sender is dropped.
+    fn spawn_writer(mut clients: Vec<Client>) -> mpsc::Sender<Message> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for message in receiver {
+                match message {
+                    Message::Client(client) => clients.push(client),
+                    Message::Line(line) => clients.retain_mut(|client| {
+                        let sent = client.write_all(line.as_bytes()).is_ok();
+                        if !sent {
+                            debug!("external event client disconnected");
+                        }
+                        sent
+                    }),
+                    #[cfg(test)]
+                    Message::Flush(reply) => _ = reply.send(clients.len()),
+                }
+            }
+        });
+        sender
+    }
+
+    /// Sends the event to every client, disconnecting clients that
+    /// could not receive it.
+    pub(crate) fn send(&self, event: &ExternalEvent) {
+        let mut line = serde_json::to_string(event).expect("event should serialize");
+        line.push('\n');
+        // the writer only stops after this is dropped
+        _ = self.sender.send(Message::Line(line));
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+        if let Some(path) = &self.path {
+            // wake the listening thread so that it sees it is closed
+            #[cfg(unix)]
+            {
+                _ = std::os::unix::net::UnixStream::connect(path);
+            }
+            _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+use parking_lot::Mutex;
+
+#[cfg(test)]
+impl EventSocket {
+    /// A socket that sends events to a buffer instead of any clients.
+    pub(crate) fn to_buffer() -> (Self, Arc<Mutex<Vec<u8>>>) {
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let socket = Self {
+            sender: Self::spawn_writer(vec![Box::new(Buffer(Arc::clone(&buffer)))]),
+            path: None,
+            closed: Arc::new(AtomicBool::new(false)),
+        };
+        (socket, buffer)
+    }
+
+    /// Waits for every event sent so far to be written, returning the
+    /// number of connected clients.
+    pub(crate) fn flush(&self) -> usize {
+        let (reply, receiver) = mpsc::channel();
+        self.sender.send(Message::Flush(reply)).unwrap();
+        receiver.recv().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventSocket, ExternalEvent};
+
+    #[test]
+    fn sends_json_lines() {
+        let (socket, buffer) = EventSocket::to_buffer();
+        socket.send(&ExternalEvent::Shown);
+        socket.send(&ExternalEvent::Activated {
+            title: "Firefox".to_owned(),
+        });
+
+        socket.flush();
+        assert_eq!(
+            String::from_utf8(buffer.lock().clone()).unwrap(),
+            "{\"event\":\"shown\"}\n{\"event\":\"activated\",\"title\":\"Firefox\"}\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sends_to_socket_clients() {
+        use std::{
+            io::{BufRead as _, BufReader},
+            os::unix::net::UnixStream,
+            time::Duration,
+        };
+
+        let path = std::env::temp_dir().join(format!("covey-events-{}.sock", std::process::id()));
+        let socket = EventSocket::bind(&path).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        // wait for the client to be accepted
+        while socket.flush() == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        socket.send(&ExternalEvent::Hidden);
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        assert_eq!(line, "{\"event\":\"hidden\"}\n");
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clients_that_stop_reading_do_not_block_sending() {
+        use std::{
+            os::unix::net::UnixStream,
+            time::{Duration, Instant},
+        };
+
+        let path = std::env::temp_dir().join(format!("covey-slow-{}.sock", std::process::id()));
+        let socket = EventSocket::bind(&path).unwrap();
+        let _client = UnixStream::connect(&path).unwrap();
+        while socket.flush() == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // enough to fill the socket buffer several times over
+        let title = "x".repeat(10_000);
+        let start = Instant::now();
+        for _ in 0..100 {
+            socket.send(&ExternalEvent::Activated {
+                title: title.clone(),
+            });
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dropping_disconnects_clients() {
+        use std::{io::Read as _, os::unix::net::UnixStream, time::Duration};
+
+        let path = std::env::temp_dir().join(format!("covey-drop-{}.sock", std::process::id()));
+        let socket = EventSocket::bind(&path).unwrap();
+        let mut client = UnixStream::connect(&path).unwrap();
+        while socket.flush() == 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        // the clients are only closed once the listener has stopped too
+        drop(socket);
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut read = String::new();
+        client.read_to_string(&mut read).unwrap();
+        assert_eq!(read, "");
+    }
+}
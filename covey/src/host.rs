@@ -1,28 +1,183 @@
 use std::{
-    fs,
+    collections::{HashMap, HashSet},
+    fmt, fs,
     future::Future,
     io::{Read as _, Write as _},
+    num::NonZeroU16,
+    path::PathBuf,
+    pin::pin,
     sync::Arc,
+    time::Duration,
 };
 
-use color_eyre::eyre::{bail, Context, Result};
-use covey_config::{config::GlobalConfig, keyed_list::KeyedList};
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use covey_config::{
+    config::{ActivationFeedback, EscapeAction, GlobalConfig, NoResultsAction, Shell},
+    keyed_list::KeyedList,
+};
 use parking_lot::Mutex;
-use tracing::{debug, error, info};
+use tokio::{
+    io::AsyncWriteExt as _,
+    process::Child,
+    sync::{oneshot, watch, Semaphore},
+    task::AbortHandle,
+    time::Instant,
+};
+use tracing::{debug, error, info, warn, Instrument as _};
 
 use crate::{
-    event::{Action, ListItemId, PluginEvent},
-    Frontend, Plugin, CONFIG_PATH,
+    builtin::{self, BuiltinCommand},
+    event::{
+        Action, ExecutionPolicy, Form, Input, ListItemId, NextPage, PluginEvent, QueryOutcome,
+        Toast,
+    },
+    external::{EventSocket, ExternalEvent},
+    plugin::PluginPaths,
+    proto, Capability, Frontend, List, ListItem, Plugin, CONFIG_PATH, EVENT_SOCKET_PATH,
 };
 
+/// A mouse button other than the primary button, that runs a
+/// configured command when a list item is clicked with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Middle,
+    Back,
+    Forward,
+}
+
 struct HostInner {
     plugins: KeyedList<Plugin>,
+    /// Where plugins are looked up and keep their data.
+    plugin_paths: PluginPaths,
+    /// The most recent query, before it is transformed.
+    query: String,
+    /// Contexts that have been navigated into, from outermost to innermost.
+    contexts: Vec<QueryContext>,
+    /// Whether the menu is shown.
+    visible: bool,
+    /// List item to select when the next query's results are shown.
+    pending_selection: Option<u32>,
+    /// Files to write once the lock is released, as writing may block.
+    pending_writes: Vec<FileWrite>,
+    /// List item to select when the results of the query with this
+    /// index are shown.
+    requested_selection: Option<(u64, u32)>,
+    /// The first item of the shown list, and the actions to run instead
+    /// of activating it.
+    default_action: Option<(ListItemId, Vec<Action>)>,
+    /// Only exists if the `event_socket` config is enabled.
+    event_socket: Option<EventSocket>,
+    /// Every shown item, including nested items.
+    shown_items: Vec<ListItem>,
+    /// An expanded alias that was set as the frontend's input.
+    shown_expansion: Option<String>,
+    /// How to get more results for the shown list.
+    next_page: Option<NextPage>,
+    /// Number of items at the top level of the shown list.
+    shown_len: usize,
+    /// Lists that arrive before this time are held instead of shown,
+    /// so that lists which are quickly replaced are never shown.
+    hold_lists_until: Option<Instant>,
+    /// The newest list that is being held, with its placeholder and
+    /// action index.
+    held_list: Option<(List, String, u64)>,
+    /// When a list was last shown, so that lists are shown at most once
+    /// every [`LIST_FRAME_INTERVAL`].
+    last_list_shown: Option<Instant>,
+    /// Answers the question shown with [`Frontend::confirm`].
+    confirmation: Option<oneshot::Sender<bool>>,
+    /// Notified to stop the queries and pages that are still loading.
+    in_flight: watch::Sender<()>,
+    /// Notified when an item is activated, to stop the deferred work of
+    /// the previous activation.
+    activation: watch::Sender<()>,
+    /// Commands started by actions that are run in parallel, which are
+    /// killed once another item is activated.
+    deferred_processes: Vec<Child>,
+    /// The item that the frontend selected, which is previewed.
+    selected: Option<ListItemId>,
+    /// The form that is being filled in, if any.
+    form: Option<ActiveForm>,
+    /// Correction suggested by the shown list.
+    correction: Option<Input>,
+    /// Prefix of the active plugin that is shown as a chip.
+    prefix_chip: Option<String>,
+    /// Commands of an item that the user is choosing from.
+    actions_menu: Option<ActionsMenu>,
+    /// The shown list, if its plugin should be queried again once its
+    /// results expire.
+    refresh: Option<Refresh>,
+    /// The task that refreshes the shown list, see [`Host::start_refreshing`].
+    refreshing: Option<AbortHandle>,
+    /// The text selected in other apps, read once per show when a plugin
+    /// that uses it is queried.
+    primary_selection: Option<String>,
+    query_limiter: QueryLimiter,
     dispatched_actions: u64,
     activated_actions: u64,
     fe: Box<dyn Frontend>,
     config: GlobalConfig,
 }
 
+/// A sub-context that was entered with [`Action::PushContext`].
+#[derive(Debug)]
+struct QueryContext {
+    label: String,
+    /// The query before entering this context.
+    previous: Input,
+}
+
+/// A form shown with [`Action::ShowForm`] that is being filled in.
+///
+/// The form has its own context, labelled with the field that is being
+/// filled in, so leaving the context cancels the form.
+#[derive(Debug)]
+struct ActiveForm {
+    plugin: Plugin,
+    form: Form,
+    /// Values of the fields that have been filled in.
+    values: Vec<String>,
+    /// Number of contexts including the form's context.
+    depth: usize,
+}
+
+/// The commands of an item, listed so that one can be chosen.
+#[derive(Debug)]
+struct ActionsMenu {
+    item: ListItemId,
+    /// The ID and title of every command of the item.
+    commands: Vec<(String, String)>,
+    /// Indices of the commands that are listed, best match first.
+    listed: Vec<usize>,
+}
+
+/// A list that is replaced by querying its plugin again once its results
+/// expire.
+#[derive(Debug, Clone)]
+struct Refresh {
+    plugin: Plugin,
+    /// The query that the plugin was given, without its prefix.
+    query: String,
+    placeholder: String,
+    interval: Duration,
+    /// Index of the query that the list is the result of.
+    index: u64,
+}
+
+/// What activating the item of a form does.
+enum FormStep {
+    /// Fill in the next field.
+    Next,
+    /// Submit the values of every field to the plugin.
+    Submit {
+        plugin: Plugin,
+        id: String,
+        values: Vec<String>,
+        /// The query from before the form was shown.
+        previous: Input,
+    },
+}
+
 /// Main public API for interacting with covey.
 ///
 /// When an action is returned from a plugin, the frontend is updated.
@@ -35,6 +190,83 @@ pub struct Host {
 
 impl Host {
     pub fn new(fe: impl Frontend) -> Result<Self> {
+        let global_config = Self::read_config()?;
+        let plugin_paths = PluginPaths::default();
+        let plugins = Self::load_plugins(&global_config, &plugin_paths, &KeyedList::default());
+        let mut fe = fe;
+        Self::check_shell(&global_config, &mut fe);
+
+        info!("found plugins: {plugins:?}");
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(HostInner {
+                plugins,
+                plugin_paths,
+                query: String::new(),
+                contexts: vec![],
+                visible: false,
+                pending_selection: None,
+                pending_writes: vec![],
+                requested_selection: None,
+                default_action: None,
+                event_socket: Self::bind_event_socket(&global_config),
+                shown_items: vec![],
+                shown_expansion: None,
+                next_page: None,
+                shown_len: 0,
+                hold_lists_until: None,
+                held_list: None,
+                last_list_shown: None,
+                form: None,
+                correction: None,
+                prefix_chip: None,
+                actions_menu: None,
+                refresh: None,
+                refreshing: None,
+                primary_selection: None,
+                confirmation: None,
+                in_flight: watch::channel(()).0,
+                activation: watch::channel(()).0,
+                deferred_processes: vec![],
+                selected: None,
+                query_limiter: QueryLimiter::new(global_config.max_concurrent_queries),
+                dispatched_actions: 0,
+                activated_actions: 0,
+                fe: Box::new(fe),
+                config: global_config,
+            })),
+        })
+    }
+
+    /// Shows an error if the configured shell doesn't exist.
+    ///
+    /// The config is still used, so that the error is shown again if the
+    /// user tries to run a shell command.
+    fn check_shell(config: &GlobalConfig, fe: &mut dyn Frontend) {
+        if let Err(e) =
+            crate::spawn::find_program(&config.shell.program, std::env::var_os("PATH").as_deref())
+        {
+            error!("invalid shell: {e:#}");
+            fe.display_error("Invalid shell", e);
+        }
+    }
+
+    /// Creates the event socket if it is enabled, logging any errors.
+    fn bind_event_socket(config: &GlobalConfig) -> Option<EventSocket> {
+        if !config.event_socket {
+            return None;
+        }
+        EventSocket::bind(&EVENT_SOCKET_PATH)
+            .inspect_err(|e| error!("failed to create event socket: {e:#}"))
+            .ok()
+    }
+
+    /// Reads the config from the [`CONFIG_PATH`], creating an empty
+    /// config file if it doesn't exist.
+    ///
+    /// # Errors
+    /// Returns an error if there was an IO or deserialization issue.
+    pub fn read_config() -> Result<GlobalConfig> {
         info!("reading config from file: {:?}", &*CONFIG_PATH);
 
         let mut file = fs::OpenOptions::new()
@@ -49,36 +281,41 @@ impl Host {
 
         debug!("read config:\n{s}");
 
-        let global_config: GlobalConfig = toml::from_str(&s)?;
-        let plugins = Self::load_plugins(&global_config);
-
-        info!("found plugins: {plugins:?}");
-
-        Ok(Self {
-            inner: Arc::new(Mutex::new(HostInner {
-                plugins,
-                dispatched_actions: 0,
-                activated_actions: 0,
-                fe: Box::new(fe),
-                config: global_config,
-            })),
-        })
+        Ok(toml::from_str(&s)?)
     }
 
     /// Reads the manifests of every plugin listed in the config.
-    fn load_plugins(config: &GlobalConfig) -> KeyedList<Plugin> {
-        KeyedList::new_lossy(config.plugins.iter().filter_map(|config| {
-            match Plugin::new(config.clone()) {
-                Ok(plugin) => {
-                    debug!("found plugin {plugin:?}");
-                    Some(plugin)
+    ///
+    /// Plugins in `old` that have the same config are kept as they are,
+    /// so that they don't need to be restarted. The built-in commands
+    /// come first if they are enabled.
+    fn load_plugins(
+        config: &GlobalConfig,
+        paths: &PluginPaths,
+        old: &KeyedList<Plugin>,
+    ) -> KeyedList<Plugin> {
+        let builtin = config.commands_prefix.clone().map(Plugin::builtin);
+        KeyedList::new_lossy(builtin.into_iter().chain(config.plugins.iter().filter_map(
+            |plugin_config| {
+                if let Some(plugin) = old
+                    .get(plugin_config.id.as_str())
+                    .filter(|plugin| plugin.is_configured_by(plugin_config, config))
+                {
+                    return Some(plugin.clone());
                 }
-                Err(e) => {
-                    error!("error finding plugin: {e}");
-                    None
+
+                match Plugin::with_paths(plugin_config.clone(), config, paths.clone()) {
+                    Ok(plugin) => {
+                        debug!("found plugin {plugin:?}");
+                        Some(plugin)
+                    }
+                    Err(e) => {
+                        error!("error finding plugin: {e:#}");
+                        None
+                    }
                 }
-            }
-        }))
+            },
+        )))
     }
 
     /// Writes the config to the [`CONFIG_PATH`].
@@ -110,6 +347,23 @@ impl Host {
         }
     }
 
+    /// Like [`Self::make_event_future`], but the event is dropped if
+    /// `replaced` was notified before it arrived.
+    fn make_activation_future<Fut>(
+        &self,
+        event: Fut,
+        replaced: watch::Receiver<()>,
+    ) -> impl Future<Output = ()> + use<Fut>
+    where
+        Fut: Future<Output = Result<PluginEvent>> + Send + 'static,
+    {
+        let this = self.clone();
+        async move {
+            this.handle_event_until_replaced(event.await, replaced)
+                .await;
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn activate(
         &self,
@@ -118,140 +372,3885 @@ impl Host {
     ) -> impl Future<Output = ()> + use<> {
         debug!("activating {item:?}");
 
-        self.make_event_future(async move {
-            item.plugin
-                .activate(item.local_id, command_name)
+        let (form_step, default_actions, confirmation, replaced, activated) = {
+            let mut inner = self.inner.lock();
+            inner.cancel_in_flight();
+            let replaced = inner.replace_activation();
+            // boxed to keep the future small
+            let activated = inner.shown_item(&item).cloned().map(Box::new);
+            match inner.advance_form(&item) {
+                Some(step) => (Some(step), None, None, replaced, activated),
+                None => (
+                    None,
+                    inner
+                        .default_actions_for(&item, &command_name)
+                        .or_else(|| inner.inline_actions_for(&item, &command_name)),
+                    inner.request_confirmation(&item),
+                    replaced,
+                    activated,
+                ),
+            }
+        };
+        let this = self.clone();
+        let event = async move {
+            match form_step {
+                Some(FormStep::Next) => {
+                    return Ok(PluginEvent::Run(vec![Action::SetInput(Input::new(""))]));
+                }
+                Some(FormStep::Submit {
+                    plugin,
+                    id,
+                    values,
+                    previous,
+                }) => {
+                    let (mut actions, execution) = plugin.submit_form(id, values).await?;
+                    // don't leave the form's item shown
+                    if actions.is_empty() {
+                        actions.push(Action::SetInput(previous));
+                    }
+                    return Ok(PluginEvent::run(actions, execution));
+                }
+                None => {}
+            }
+            if let Some(confirmed) = confirmation {
+                // a dropped sender was replaced by a newer confirmation
+                if !confirmed.await.unwrap_or(false) {
+                    debug!("activation was not confirmed");
+                    return Ok(PluginEvent::Cancelled);
+                }
+            }
+            let (actions, execution) = match default_actions {
+                Some(actions) => (actions, ExecutionPolicy::Parallel),
+                None => item.plugin.activate(item.local_id, command_name).await?,
+            };
+            this.inner.lock().on_activated(&item);
+            let actions = resolve_fields(activated.as_deref(), actions);
+            Ok(PluginEvent::run(actions, execution))
+        };
+        // the response is dropped if another item is activated before it
+        // arrives, and its commands are killed if it is activated after
+        self.make_activation_future(event, replaced)
+    }
+
+    /// Shows a preview of the item that the frontend selected, if its
+    /// plugin has one.
+    ///
+    /// The preview is only requested once the selection has stayed on
+    /// the item for [`PREVIEW_DELAY`], and is not shown if another item
+    /// was selected before it arrived.
+    #[tracing::instrument(skip(self))]
+    pub fn select(&self, item: ListItemId) -> impl Future<Output = ()> + use<> {
+        self.inner.lock().selected = Some(item.clone());
+        let this = self.clone();
+        async move {
+            let is_selected = || this.inner.lock().selected.as_ref() == Some(&item);
+            tokio::time::sleep(PREVIEW_DELAY).await;
+            if !is_selected() {
+                return;
+            }
+
+            let preview = item
+                .plugin
+                .preview(item.local_id)
                 .await
-                .map(PluginEvent::Run)
+                .unwrap_or_else(|e| {
+                    warn!("failed to get preview from {:?}: {e:#}", item.plugin);
+                    None
+                });
+            if is_selected() {
+                this.inner.lock().fe.set_preview(preview);
+            }
+        }
+    }
+
+    /// Answers the question from [`Frontend::confirm`], activating the
+    /// item if `confirmed` is true.
+    #[tracing::instrument(skip(self))]
+    pub fn confirm(&self, confirmed: bool) {
+        if let Some(confirmation) = self.inner.lock().confirmation.take() {
+            _ = confirmation.send(confirmed);
+        }
+    }
+
+    /// Activates an item with the command configured for a mouse button.
+    ///
+    /// Returns [`None`] if the button has no command, in which case
+    /// nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn click(
+        &self,
+        item: ListItemId,
+        button: MouseButton,
+    ) -> Option<impl Future<Output = ()> + use<>> {
+        let command_name = self.inner.lock().command_for_button(button)?;
+        Some(self.activate(item, command_name))
+    }
+
+    /// Runs the action attached to an item's description.
+    ///
+    /// Returns [`None`] if the item isn't shown or its description has
+    /// no action, in which case nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn activate_description(
+        &self,
+        item: &ListItemId,
+    ) -> Option<impl Future<Output = ()> + use<>> {
+        let actions = {
+            let inner = self.inner.lock();
+            let shown = inner.shown_item(item)?;
+            resolve_fields(Some(shown), vec![shown.description_action()?])
+        };
+        Some(self.make_event_future(async move { Ok(PluginEvent::Run(actions)) }))
+    }
+
+    /// Runs the configured [`NoResultsAction`] with the query, for when
+    /// the user activates a list without results.
+    ///
+    /// Returns [`None`] if there are results, the query is empty or no
+    /// action is configured, in which case nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn activate_without_results(&self) -> Option<impl Future<Output = ()> + use<>> {
+        let action = {
+            let inner = self.inner.lock();
+            if !inner.shown_items.is_empty() || inner.query.is_empty() {
+                return None;
+            }
+            no_results_action(inner.config.no_results_action.as_ref()?, &inner.query)
+        };
+        Some(self.make_event_future(async move { Ok(PluginEvent::Run(vec![action])) }))
+    }
+
+    /// Stores a new order of pinned items that the user dragged, then
+    /// queries again to show them in that order.
+    ///
+    /// Returns [`None`] if the items aren't shown pinned items of a
+    /// single plugin, in which case nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn reorder_pins(&self, items: Vec<ListItemId>) -> Option<impl Future<Output = ()> + use<>> {
+        let (plugin, query) = {
+            let inner = self.inner.lock();
+            let plugin = items.first()?.plugin.clone();
+            let all_pinned = items.iter().all(|item| {
+                item.plugin == plugin && inner.shown_item(item).is_some_and(ListItem::is_pinned)
+            });
+            if !all_pinned {
+                return None;
+            }
+            (plugin, inner.query.clone())
+        };
+        let selection_ids = items.iter().map(|item| item.local_id).collect();
+        let this = self.clone();
+        Some(async move {
+            if let Err(e) = plugin.reorder_pins(selection_ids).await {
+                this.inner.lock().report("Error reordering pins", Err(e));
+                return;
+            }
+            Box::pin(this.query(query)).await;
         })
     }
 
-    /// Calls a plugin with this input.
+    /// Activates the first shown item with this shortcut.
+    ///
+    /// Returns [`None`] if no item has the shortcut, in which case
+    /// nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn activate_shortcut(&self, key: &str) -> Option<impl Future<Output = ()> + use<>> {
+        let item = self.inner.lock().item_for_shortcut(key)?;
+        Some(self.activate(item, "activate".to_owned()))
+    }
+
+    /// Lists the commands of an item in a menu, so that one can be chosen
+    /// with [`Host::select_action`].
+    ///
+    /// Returns false if the item doesn't have more than one command, in
+    /// which case no menu is shown.
+    #[tracing::instrument(skip(self))]
+    pub fn open_actions_menu(&self, item: &ListItemId) -> bool {
+        self.inner.lock().open_actions_menu(item)
+    }
+
+    /// Lists only the commands in the actions menu that fuzzy match the
+    /// query.
+    pub fn filter_actions_menu(&self, query: &str) {
+        let mut inner = self.inner.lock();
+        let Some(menu) = &mut inner.actions_menu else {
+            return;
+        };
+        let mut matches: Vec<_> = menu
+            .commands
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (_, title))| Some((builtin::match_score(query, title)?, index)))
+            .collect();
+        // stable, so equal matches keep their order
+        matches.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        menu.listed = matches.into_iter().map(|(_, index)| index).collect();
+        inner.show_actions_menu();
+    }
+
+    /// Activates the item with the command at this index of the actions
+    /// menu, then closes the menu.
+    ///
+    /// Returns [`None`] if the menu isn't open or doesn't list that many
+    /// commands, in which case nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn select_action(&self, index: usize) -> Option<impl Future<Output = ()> + use<>> {
+        let (item, command) = {
+            let mut inner = self.inner.lock();
+            let menu = inner.actions_menu.as_ref()?;
+            let (command, _) = &menu.commands[*menu.listed.get(index)?];
+            let selected = (menu.item.clone(), command.clone());
+            inner.close_actions_menu();
+            selected
+        };
+        Some(self.activate(item, command))
+    }
+
+    /// Hides the actions menu without activating anything.
+    pub fn close_actions_menu(&self) {
+        self.inner.lock().close_actions_menu();
+    }
+
+    /// Sets the input of the frontend, then queries it.
+    #[tracing::instrument(skip(self))]
+    pub fn set_input(&self, input: Input) -> impl Future<Output = ()> + use<> {
+        self.make_event_future(async move { Ok(PluginEvent::Run(vec![Action::SetInput(input)])) })
+    }
+
+    /// Sets the input to the correction suggested by the shown list, then
+    /// queries it.
+    ///
+    /// Returns [`None`] if the shown list has no correction, in which case
+    /// nothing happens.
     #[tracing::instrument(skip(self))]
+    pub fn apply_correction(&self) -> Option<impl Future<Output = ()> + use<>> {
+        let correction = self.inner.lock().correction.take()?;
+        Some(self.set_input(correction))
+    }
+
+    /// Calls a plugin with this input.
+    ///
+    /// Every query gets an increasing request ID, which is sent to the
+    /// plugin and recorded in the logs of the query.
+    #[tracing::instrument(skip(self), fields(request_id = tracing::field::Empty))]
     pub fn query(&self, input: String) -> impl Future<Output = ()> + use<> {
         debug!("setting input to {input:?}");
-        let (plugins, this_action_index) = {
+        let (plugins, this_action_index, transform, limiter, input, cancelled, form_list) = {
             let mut inner = self.inner.lock();
-            inner.dispatched_actions += 1;
+            // form fields aren't sent to plugins
+            let form_list = inner.form_list(&input);
+            let input = if form_list.is_some() {
+                input
+            } else {
+                let input = inner.expand_alias(input);
+                inner.update_prefix_chip(&input);
+                input
+            };
+            let this_action_index = inner.dispatch_query(&input);
+            tracing::Span::current().record("request_id", this_action_index);
 
-            (inner.plugins.clone(), inner.dispatched_actions)
+            (
+                inner.plugins.clone(),
+                this_action_index,
+                inner
+                    .config
+                    .query_transform
+                    .clone()
+                    .map(|cmd| (inner.config.shell.clone(), cmd)),
+                inner.query_limiter.clone(),
+                input,
+                inner.in_flight.subscribe(),
+                form_list,
+            )
         };
+        let this = self.clone();
+        let after_delay = self.clone();
 
-        self.make_event_future(async move {
-            for plugin in plugins {
-                let Some(stripped) = input.strip_prefix(plugin.prefix()) else {
-                    continue;
-                };
-                debug!("querying plugin {plugin:?}");
-                let list = plugin.query(stripped).await?;
-
+        let event = self.make_event_future(until_cancelled(cancelled, async move {
+            if let Some((list, placeholder)) = form_list {
                 return Ok(PluginEvent::SetList {
                     list,
+                    placeholder,
                     index: this_action_index,
                 });
             }
+            let input = match &transform {
+                Some((shell, cmd)) => transform_query(shell, cmd, input).await,
+                None => input,
+            };
 
-            bail!("no plugin activated")
-        })
-    }
+            let matching: Vec<_> = plugins
+                .into_iter()
+                .filter_map(|plugin| {
+                    let stripped = input.strip_prefix(plugin.prefix())?;
+                    // skip plugins that can't handle the query without asking them
+                    plugin
+                        .can_handle(stripped)
+                        .then(|| (plugin, stripped.to_owned()))
+                })
+                .collect();
 
-    async fn handle_event(&self, event: Result<PluginEvent>) {
-        let chained_query = self.inner.lock().handle_event(event);
+            if matching.is_empty() {
+                bail!("no plugin activated")
+            }
 
-        if let Some(query) = chained_query {
-            // indirection needed to avoid infinitely sized future
-            Box::pin(self.query(query)).await;
+            let is_stale = || this.inner.lock().dispatched_actions > this_action_index;
+            let handled = first_handled(matching, |(plugin, stripped)| {
+                let limiter = &limiter;
+                let this = &this;
+                async move {
+                    // later queries make this one stale while waiting
+                    let debounce = plugin.debounce();
+                    if !debounce.is_zero() {
+                        tokio::time::sleep(debounce).await;
+                        if is_stale() {
+                            return Err(Cancelled.into());
+                        }
+                    }
+                    limiter
+                        .run(is_stale, async {
+                            let selection = this.primary_selection_for(&plugin).await;
+                            debug!("querying plugin {plugin:?}");
+                            plugin.query(stripped, this_action_index, selection).await
+                        })
+                        .await
+                        .ok_or(Cancelled)?
+                }
+            })
+            .await;
+            let handled = match handled {
+                Err(e) if e.is::<Cancelled>() => {
+                    debug!("query was replaced before it finished");
+                    return Ok(PluginEvent::Cancelled);
+                }
+                other => other?,
+            };
+
+            let Some(((plugin, stripped), list)) = handled else {
+                debug!("every plugin passed through");
+                // setting a list never chains another query
+                _ = this.inner.lock().handle_event(Ok(PluginEvent::SetList {
+                    list: List::default(),
+                    placeholder: String::new(),
+                    index: this_action_index,
+                }));
+                return Ok(PluginEvent::SetSuggestions {
+                    suggestions: vec![],
+                    index: this_action_index,
+                });
+            };
+
+            // only show the placeholder if there is nothing to replace it
+            let placeholder = if stripped.is_empty() {
+                plugin.placeholder().to_owned()
+            } else {
+                String::new()
+            };
+
+            // show the list without waiting for suggestions
+            {
+                let mut inner = this.inner.lock();
+                let refresh = list.refresh.map(|interval| Refresh {
+                    plugin: Plugin::clone(&plugin),
+                    query: stripped.clone(),
+                    placeholder: placeholder.clone(),
+                    interval,
+                    index: this_action_index,
+                });
+                _ = inner.handle_event(Ok(PluginEvent::SetList {
+                    list,
+                    placeholder,
+                    index: this_action_index,
+                }));
+                if inner.newest_list_index() == this_action_index {
+                    inner.refresh = refresh;
+                }
+            }
+
+            let suggestions = plugin.suggestions(stripped).await.unwrap_or_else(|e| {
+                error!("failed to get suggestions from {plugin:?}: {e:#}");
+                vec![]
+            });
+            Ok(PluginEvent::SetSuggestions {
+                suggestions,
+                index: this_action_index,
+            })
+        }));
+
+        // keep the request ID in the logs of the plugin calls
+        async move {
+            let refresher = after_delay.clone();
+            Box::pin(after_delay.show_held_list_after(event)).await;
+            refresher.start_refreshing(this_action_index);
         }
+        .instrument(tracing::Span::current())
     }
 
-    /// Reloads all plugins with the new configuration.
-    #[tracing::instrument(skip_all)]
-    pub fn reload(&self, config: GlobalConfig) {
-        debug!("reloading");
-        let mut inner = self.inner.lock();
-        inner.plugins = Self::load_plugins(&config);
-        // TODO: spawn this in another task and handle errors properly
-        Self::write_config(&config).expect("TODO");
-        inner.config = config;
+    /// The text that the user selected, if the plugin reads it.
+    async fn primary_selection_for(&self, plugin: &Plugin) -> Option<String> {
+        if plugin
+            .supports(Capability::PrimarySelection)
+            .await
+            .unwrap_or(false)
+        {
+            // the selection is read by running a command, so the lock
+            // isn't held while waiting for it
+            let read = {
+                let mut inner = self.inner.lock();
+                if let Some(selection) = &inner.primary_selection {
+                    return Some(selection.clone());
+                }
+                inner.fe.primary_selection()
+            };
+            let selection = read.await;
+            Some(
+                self.inner
+                    .lock()
+                    .primary_selection
+                    .get_or_insert(selection)
+                    .clone(),
+            )
+        } else {
+            None
+        }
     }
 
-    pub fn config(&self) -> GlobalConfig {
-        self.inner.lock().config.clone()
+    /// Starts refreshing the list that is the result of the query at
+    /// `index` in another task, replacing the task that refreshed the
+    /// previous list.
+    ///
+    /// Does nothing if that list isn't shown or doesn't expire.
+    fn start_refreshing(&self, index: u64) {
+        let mut inner = self.inner.lock();
+        if inner.refresh_for(index).is_none() {
+            return;
+        }
+        let this = self.clone();
+        let task = tokio::spawn(async move { this.refresh_expired(index).await });
+        if let Some(previous) = inner.refreshing.replace(task.abort_handle()) {
+            previous.abort();
+        }
     }
 
-    /// Ordered set of all plugins.
-    #[tracing::instrument(skip(self))]
-    pub fn plugins(&self) -> KeyedList<Plugin> {
-        self.inner.lock().plugins.clone()
+    /// Queries the plugin of the shown list again each time its results
+    /// expire, until the query changes or the menu is hidden.
+    async fn refresh_expired(&self, index: u64) {
+        loop {
+            let Some(interval) = self.inner.lock().refresh_for(index).map(|r| r.interval) else {
+                return;
+            };
+            tokio::time::sleep(interval).await;
+            let Some(refresh) = self.inner.lock().refresh_for(index) else {
+                return;
+            };
+
+            debug!("refreshing results of {:?}", refresh.plugin);
+            let selection = self.primary_selection_for(&refresh.plugin).await;
+            let list = match refresh
+                .plugin
+                .query(&*refresh.query, index, selection)
+                .await
+            {
+                Ok(QueryOutcome::Handled(list)) => list,
+                Ok(QueryOutcome::Passthrough) => {
+                    debug!("plugin passed through when refreshing");
+                    return;
+                }
+                Err(e) => {
+                    warn!("failed to refresh results of {:?}: {e:#}", refresh.plugin);
+                    return;
+                }
+            };
+
+            let mut inner = self.inner.lock();
+            // the query may have changed while refreshing
+            if inner.refresh_for(index).is_none() {
+                return;
+            }
+            inner.refresh = list.refresh.map(|interval| Refresh {
+                interval,
+                ..refresh.clone()
+            });
+            inner.show_list(list, refresh.placeholder, index);
+        }
     }
 
-    /// Swaps out the frontend used.
-    ///
-    /// Note: this is only used for hot module reloading support.
-    pub fn set_frontend(&self, fe: impl Frontend) {
-        self.inner.lock().fe = Box::new(fe);
+    /// Waits for the query to finish, then shows any list that was
+    /// held until the results delay or the next frame.
+    async fn show_held_list_after(self, query: impl Future<Output = ()>) {
+        query.await;
+        let until = self.inner.lock().list_hold_deadline();
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
+            self.inner.lock().show_held_list();
+        }
     }
-}
 
-impl HostInner {
-    /// Optionally returns another string that should be queried.
+    /// Notifies plugins that the menu was shown or hidden.
+    ///
+    /// Plugins are only notified if the visibility changed, so this can be
+    /// called whenever the menu might have been shown or hidden. Plugins
+    /// that haven't been initialised are not notified.
     #[tracing::instrument(skip(self))]
-    fn handle_event(&mut self, event: Result<PluginEvent>) -> Option<String> {
-        debug!("handling event");
-
-        match event {
-            Ok(PluginEvent::SetList { list, index }) => {
-                if index <= self.activated_actions {
-                    return None;
+    pub fn set_visible(&self, visible: bool) -> impl Future<Output = ()> + use<> {
+        let plugins = {
+            let mut inner = self.inner.lock();
+            let changed = inner.visible != visible;
+            inner.visible = visible;
+            if changed {
+                // the user may have selected something else while hidden
+                inner.primary_selection = None;
+                inner.close_actions_menu();
+                if let Some(refreshing) = inner.refreshing.take() {
+                    refreshing.abort();
                 }
-                self.activated_actions = index;
-                self.fe.set_list(list);
-            }
-            Ok(PluginEvent::Run(actions)) => {
-                return actions
-                    .into_iter()
-                    .fold(None, |opt, action| self.handle_action(action).or(opt));
+                inner.emit(&if visible {
+                    ExternalEvent::Shown
+                } else {
+                    ExternalEvent::Hidden
+                });
             }
-            Err(e) => {
-                self.fe.display_error("Error in plugin", e);
+            changed.then(|| inner.plugins.clone())
+        };
+        let this = self.clone();
+
+        async move {
+            let Some(plugins) = plugins else { return };
+            if visible {
+                // refreshing stopped while hidden
+                let index = this.inner.lock().dispatched_actions;
+                this.start_refreshing(index);
             }
+            notify_all(plugins, |plugin| async move {
+                if visible {
+                    plugin.on_show().await
+                } else {
+                    plugin.on_hide().await
+                }
+            })
+            .await;
         }
+    }
 
-        None
+    /// Removes the prefix that is shown as a chip from the query, so that
+    /// its plugin is no longer active.
+    ///
+    /// Returns [`None`] if no chip is shown, in which case nothing happens.
+    #[tracing::instrument(skip(self))]
+    pub fn remove_prefix(&self) -> Option<impl Future<Output = ()> + use<>> {
+        let query = {
+            let mut inner = self.inner.lock();
+            let prefix = inner.prefix_chip.clone()?;
+            let query = inner.query.strip_prefix(&prefix)?.to_owned();
+            // removed before setting the input, which shouldn't have the
+            // prefix added back
+            inner.set_prefix_chip(None);
+            inner.fe.set_input(Input::new(query.clone()));
+            query
+        };
+        Some(self.query(query))
     }
 
-    /// Optionally returns another string that should be queried.
+    /// Leaves the innermost context, restoring the query from before it
+    /// was entered.
+    ///
+    /// Returns [`None`] if not in any context, in which case nothing
+    /// happens.
     #[tracing::instrument(skip(self))]
-    fn handle_action(&mut self, action: Action) -> Option<String> {
-        info!("handling action {action:?}");
+    pub fn pop_context(&self) -> Option<impl Future<Output = ()> + use<>> {
+        let query = self.inner.lock().pop_context()?;
+        Some(self.query(query))
+    }
 
-        match action {
-            Action::Close => self.fe.close(),
-            Action::RunCommand(cmd, args) => {
-                if let Err(e) = crate::spawn::free_null(&cmd, &args).context(format!(
-                    "failed to run command `{cmd} {args}`",
-                    args = args.join(" ")
-                )) {
-                    error!("Error running command: {e:#}");
-                    self.fe.display_error("Error running command", e);
-                }
-            }
-            Action::RunShell(str) => {
-                if let Err(e) = crate::spawn::free_null("sh", ["-c", &str])
-                    .context(format!("failed to run command `{str}`"))
-                {
-                    error!("Error running command: {e:#}");
-                    self.fe.display_error("Error running command", e);
-                }
-            }
-            Action::Copy(str) => {
-                self.fe.copy(str);
+    /// Handles the escape key.
+    ///
+    /// This leaves the innermost context if there is one. Otherwise, the
+    /// menu is closed, unless the config says to clear a non-empty query
+    /// first.
+    #[tracing::instrument(skip(self))]
+    pub fn escape(&self) -> impl Future<Output = ()> + use<> {
+        let query = {
+            let mut inner = self.inner.lock();
+            if let Some(previous) = inner.pop_context() {
+                Some(previous)
+            } else if inner.config.escape == EscapeAction::Clear && !inner.query.is_empty() {
+                inner.fe.set_input(Input::new(""));
+                Some(String::new())
+            } else {
+                inner.fe.close();
+                None
             }
-            Action::SetInput(input) => {
-                self.fe.set_input(input.clone());
-                return Some(input.contents);
+        };
+        let query = query.map(|query| Box::pin(self.query(query)));
+        async move {
+            if let Some(query) = query {
+                query.await;
             }
         }
+    }
+
+    /// Requests the next page of results of the shown list, which are
+    /// added to the end of the list.
+    ///
+    /// Returns [`None`] if the plugin doesn't have any more results, or
+    /// if the next page was already requested.
+    #[tracing::instrument(skip(self))]
+    pub fn load_more(&self) -> Option<impl Future<Output = ()> + use<>> {
+        let (next_page, index, cancelled) = {
+            let mut inner = self.inner.lock();
+            (
+                inner.next_page.take()?,
+                inner.activated_actions,
+                inner.in_flight.subscribe(),
+            )
+        };
+
+        Some(
+            self.make_event_future(until_cancelled(cancelled, async move {
+                let NextPage {
+                    plugin,
+                    query,
+                    token,
+                } = next_page;
+                let list = plugin.query_page(query, token).await?;
+                Ok(PluginEvent::AppendList { list, index })
+            })),
+        )
+    }
+
+    async fn handle_event(&self, event: Result<PluginEvent>) {
+        let replaced = self.inner.lock().activation.subscribe();
+        self.handle_event_until_replaced(event, replaced).await;
+    }
+
+    /// Handles the event, stopping sequential commands once `replaced` is
+    /// notified by a newer activation.
+    ///
+    /// Nothing is done if `replaced` was already notified.
+    async fn handle_event_until_replaced(
+        &self,
+        event: Result<PluginEvent>,
+        replaced: watch::Receiver<()>,
+    ) {
+        if replaced.has_changed().unwrap_or(false) {
+            debug!("activation was replaced before its response arrived");
+            return;
+        }
+        let chained_query = match event {
+            Ok(PluginEvent::RunSequentially(actions)) => {
+                self.run_sequentially(actions, replaced).await
+            }
+            event => self.inner.lock().handle_event(event),
+        };
+        self.write_pending_files().await;
+
+        if let Some(query) = chained_query {
+            // indirection needed to avoid infinitely sized future
+            Box::pin(self.query(query)).await;
+        }
+    }
+
+    /// Runs the actions one at a time, waiting for the command of each
+    /// action to exit before starting the next.
+    ///
+    /// If `replaced` is notified while a command is running, the command
+    /// is killed and the remaining actions are not run.
+    ///
+    /// Optionally returns another string that should be queried.
+    async fn run_sequentially(
+        &self,
+        actions: Vec<Action>,
+        mut replaced: watch::Receiver<()>,
+    ) -> Option<String> {
+        use futures::future::{select, Either};
+
+        let keeps_query = keeps_query(&actions);
+        let mut chained_query = None;
+        for action in close_last(actions) {
+            let outcome = self.inner.lock().handle_action(action);
+            self.write_pending_files().await;
+            chained_query = outcome.query.or(chained_query);
+            if let Some(mut process) = outcome.process {
+                let exited = match select(pin!(process.wait()), pin!(replaced.changed())).await {
+                    Either::Left((exited, _)) => Some(exited),
+                    Either::Right(_) => None,
+                };
+                match exited {
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => warn!("failed to wait for command to exit: {e}"),
+                    None => {
+                        debug!("activation was replaced, killing command");
+                        if let Err(e) = process.start_kill() {
+                            warn!("failed to kill command: {e}");
+                        }
+                        return None;
+                    }
+                }
+            }
+        }
+        self.inner.lock().finish_actions(keeps_query, chained_query)
+    }
+
+    /// Writes the files of [`Action::WriteFile`]s that were run, without
+    /// holding the lock.
+    async fn write_pending_files(&self) {
+        let writes = std::mem::take(&mut self.inner.lock().pending_writes);
+        for write in writes {
+            let result = write.run().await;
+            self.inner.lock().report("Error writing file", result);
+        }
+    }
+
+    /// Applies and saves a new configuration.
+    ///
+    /// Only plugins whose configuration changed are restarted.
+    #[tracing::instrument(skip_all)]
+    pub fn reload(&self, config: GlobalConfig) {
+        // TODO: spawn this in another task and handle errors properly
+        Self::write_config(&config).expect("TODO");
+        self.apply_config(config);
+    }
+
+    /// Re-reads the config file and applies it, without restarting
+    /// plugins whose configuration didn't change.
+    ///
+    /// The frontend shows an error if the config can't be read, and
+    /// the current config is kept.
+    #[tracing::instrument(skip_all)]
+    pub fn reload_config(&self) {
+        self.inner.lock().reload_config();
+    }
+
+    fn apply_config(&self, config: GlobalConfig) {
+        self.inner.lock().apply_config(config);
+    }
+
+    /// Opens a URL with the frontend.
+    ///
+    /// This is used for links shown in the UI (see [`crate::linkify`]),
+    /// rather than links returned by plugins.
+    pub fn open_url(&self, url: String) {
+        self.inner.lock().handle_action(Action::OpenUrl(url));
+    }
+
+    pub fn config(&self) -> GlobalConfig {
+        self.inner.lock().config.clone()
+    }
+
+    /// Ordered set of all plugins.
+    #[tracing::instrument(skip(self))]
+    pub fn plugins(&self) -> KeyedList<Plugin> {
+        self.inner.lock().plugins.clone()
+    }
+
+    /// Swaps out the frontend used.
+    ///
+    /// Note: this is only used for hot module reloading support.
+    pub fn set_frontend(&self, fe: impl Frontend) {
+        self.inner.lock().fe = Box::new(fe);
+    }
+}
+
+/// Replaces the first word of the query if it is an alias and is
+/// followed by whitespace.
+///
+/// Returns [`None`] if there is no alias to expand.
+fn expand_alias(aliases: &HashMap<String, String>, query: &str) -> Option<String> {
+    let (word, _) = query.split_once(char::is_whitespace)?;
+    let expansion = aliases.get(word)?;
+    Some(format!("{expansion}{}", &query[word.len()..]))
+}
+
+/// Time that the selection must stay on an item before it is previewed.
+const PREVIEW_DELAY: Duration = Duration::from_millis(50);
+
+/// Shortest time between showing two lists, about one frame of a 60 Hz
+/// display.
+///
+/// Lists that arrive faster than this are coalesced, so only the newest
+/// is shown instead of queueing every list in the frontend.
+const LIST_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Local ID of the item shown while filling in a form, which plugins
+/// never use as they number their items from 0.
+const FORM_ITEM_ID: u64 = u64::MAX;
+
+/// Maximum time that the query transform command can take.
+const QUERY_TRANSFORM_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Runs the query through the transform command with the user's shell.
+///
+/// The original query is returned if the command fails.
+async fn transform_query(shell: &Shell, cmd: &str, query: String) -> String {
+    match crate::spawn::pipe_through(shell, cmd, &query, QUERY_TRANSFORM_TIMEOUT).await {
+        Ok(transformed) => {
+            debug!("transformed query {query:?} to {transformed:?}");
+            transformed
+        }
+        Err(e) => {
+            error!("failed to transform query: {e:#}");
+            query
+        }
+    }
+}
+
+/// Limits how many plugin queries can run at the same time.
+///
+/// This is cheap to clone.
+#[derive(Clone, Default)]
+struct QueryLimiter(Option<Arc<Semaphore>>);
+
+impl QueryLimiter {
+    /// A limiter allowing up to `limit` queries at once, or unlimited if
+    /// [`None`].
+    fn new(limit: Option<NonZeroU16>) -> Self {
+        Self(limit.map(|limit| Arc::new(Semaphore::new(limit.get().into()))))
+    }
+
+    /// Runs the query once there is room for it.
+    ///
+    /// Returns [`None`] without running the query if it is `cancelled`
+    /// while waiting.
+    async fn run<T>(
+        &self,
+        cancelled: impl Fn() -> bool,
+        query: impl Future<Output = T>,
+    ) -> Option<T> {
+        let _permit = match &self.0 {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("semaphore should never be closed"),
+            ),
+            None => None,
+        };
+
+        if cancelled() {
+            return None;
+        }
+        Some(query.await)
+    }
+}
+
+/// Runs the event until it finishes, or until `cancelled` is notified
+/// by [`HostInner::cancel_in_flight`].
+async fn until_cancelled(
+    mut cancelled: watch::Receiver<()>,
+    event: impl Future<Output = Result<PluginEvent>>,
+) -> Result<PluginEvent> {
+    use futures::future::{select, Either};
+
+    match select(pin!(event), pin!(cancelled.changed())).await {
+        Either::Left((event, _)) => event,
+        Either::Right(_) => {
+            debug!("stopped loading results");
+            Ok(PluginEvent::Cancelled)
+        }
+    }
+}
+
+/// Error returned when a query is replaced by a newer one.
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("query was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Queries each plugin in order until one handles the query.
+///
+/// Returns the plugin that handled the query and its list, or [`None`]
+/// if every plugin passed through.
+async fn first_handled<T, Fut>(
+    plugins: impl IntoIterator<Item = T>,
+    mut query: impl FnMut(T) -> Fut,
+) -> Result<Option<(T, List)>>
+where
+    T: Clone,
+    Fut: Future<Output = Result<QueryOutcome>>,
+{
+    for plugin in plugins {
+        match query(plugin.clone()).await? {
+            QueryOutcome::Handled(list) => return Ok(Some((plugin, list))),
+            QueryOutcome::Passthrough => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Notifies every plugin at the same time, logging any errors.
+async fn notify_all<Fut>(
+    plugins: impl IntoIterator<Item = Plugin>,
+    mut notify: impl FnMut(Plugin) -> Fut,
+) where
+    Fut: Future<Output = Result<()>>,
+{
+    let notifications = plugins.into_iter().map(|plugin| {
+        let notification = notify(plugin.clone());
+        async move {
+            if let Err(e) = notification.await {
+                error!("failed to notify plugin {plugin:?}: {e:#}");
+            }
+        }
+    });
+    futures::future::join_all(notifications).await;
+}
+
+/// Contents to write or append to a file.
+struct FileWrite {
+    path: PathBuf,
+    contents: String,
+    append: bool,
+}
+
+impl FileWrite {
+    /// Writes the contents, creating the file's parent directories.
+    async fn run(self) -> Result<()> {
+        let write = async {
+            if let Some(parent) = self.path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(self.append)
+                .truncate(!self.append)
+                .open(&self.path)
+                .await?;
+            file.write_all(self.contents.as_bytes()).await?;
+            // tokio only finishes writing in the background otherwise
+            file.flush().await
+        };
+        write
+            .await
+            .context(format!("failed to write to {}", self.path.display()))
+    }
+}
+
+/// What is left after an action was started.
+#[derive(Default)]
+struct ActionOutcome {
+    /// Another string that should be queried.
+    query: Option<String>,
+    /// Command started by the action, which may still be running.
+    process: Option<Child>,
+}
+
+/// Whether the actions keep the list open, so the query shouldn't be
+/// cleared.
+fn keeps_query(actions: &[Action]) -> bool {
+    actions.iter().any(|action| {
+        matches!(
+            action,
+            Action::SetInput(_)
+                | Action::PushContext { .. }
+                | Action::ShowForm { .. }
+                | Action::UpdateItem { .. }
+                | Action::FocusEntry { .. }
+                | Action::OpenPreviewWindow { .. }
+                | Action::ClearInput
+        )
+    })
+}
+
+/// The action to run for a query without results, with `{query}`
+/// replaced by the query.
+fn no_results_action(action: &NoResultsAction, query: &str) -> Action {
+    match action {
+        NoResultsAction::RunShell(command) => Action::RunShell(command.replace("{query}", query)),
+        NoResultsAction::OpenUrl(url) => {
+            Action::OpenUrl(url.replace("{query}", &percent_encode(query)))
+        }
+    }
+}
+
+/// Encodes the text to be part of a URL, keeping only the characters
+/// that never need to be encoded.
+fn percent_encode(text: &str) -> String {
+    use std::fmt::Write as _;
+
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+/// Replaces each [`Action::CopyField`] with copying the value of that
+/// field of the activated item.
+///
+/// Fields that the item doesn't have are left to fail when the action
+/// is run.
+fn resolve_fields(item: Option<&ListItem>, actions: Vec<Action>) -> Vec<Action> {
+    actions
+        .into_iter()
+        .map(|action| match action {
+            Action::CopyField(field) => match item.and_then(|item| item.field(&field)) {
+                Some(value) => Action::Copy(value.to_owned()),
+                None => Action::CopyField(field),
+            },
+            action => action,
+        })
+        .collect()
+}
+
+/// Moves every [`Action::Close`] to the end, keeping the order of the
+/// other actions.
+fn close_last(mut actions: Vec<Action>) -> Vec<Action> {
+    // stable, so other actions keep their order
+    actions.sort_by_key(|action| matches!(action, Action::Close));
+    actions
+}
+
+/// Adds the items and all of their nested items to `flattened`.
+fn flatten_into(flattened: &mut Vec<ListItem>, items: &[ListItem]) {
+    for item in items {
+        flattened.push(item.clone());
+        flatten_into(flattened, &item.children());
+    }
+}
+
+impl HostInner {
+    /// Handles a successful activation of an item.
+    fn on_activated(&mut self, item: &ListItemId) {
+        self.activation_feedback();
+        if let Some(shown) = self.shown_items.iter().find(|shown| shown.id() == *item) {
+            self.emit(&ExternalEvent::Activated {
+                title: shown.title().to_owned(),
+            });
+        }
+    }
+
+    /// Gives the configured feedback for a successful activation.
+    ///
+    /// This does not wait for any sound to finish playing.
+    fn activation_feedback(&mut self) {
+        match &self.config.activation_feedback {
+            ActivationFeedback::None => {}
+            ActivationFeedback::Flash => self.fe.flash(),
+            ActivationFeedback::Sound(path) => {
+                if let Err(e) = crate::spawn::play_sound(path) {
+                    error!("failed to play activation sound: {e:#}");
+                }
+            }
+        }
+    }
+
+    /// Sends an event to external tools, if the event socket is enabled.
+    fn emit(&self, event: &ExternalEvent) {
+        if let Some(socket) = &self.event_socket {
+            socket.send(event);
+        }
+    }
+
+    /// Shows the list of results for the query with this index.
+    fn show_list(&mut self, mut list: List, placeholder: String, index: u64) {
+        self.activated_actions = index;
+        let max_results = usize::from(self.config.max_displayed_results);
+        if list.len() > max_results {
+            debug!("only showing {max_results} of {} results", list.len());
+            list.items.truncate(max_results);
+            list.next_page = None;
+        }
+        self.limit_descriptions(&mut list);
+        self.next_page = list.next_page.take();
+        self.shown_len = list.len();
+        self.default_action = match list.items.first() {
+            Some(first) if !list.default_actions.is_empty() => {
+                Some((first.id(), std::mem::take(&mut list.default_actions)))
+            }
+            _ => None,
+        };
+        self.set_shown_items(&list.items);
+        self.correction.clone_from(&list.correction);
+        let selection = self
+            .requested_selection
+            .take_if(|(requested_index, _)| *requested_index <= index)
+            .filter(|(requested_index, _)| *requested_index == index)
+            .and_then(|(_, selection)| {
+                // clamp to the end of the list
+                let last_index = list.len().checked_sub(1)?;
+                Some(
+                    usize::try_from(selection)
+                        .unwrap_or(usize::MAX)
+                        .min(last_index),
+                )
+            })
+            .or_else(|| {
+                list.items
+                    .iter()
+                    .position(|item| item.is_exact_match() && !item.is_error())
+            });
+
+        self.fe.set_placeholder(placeholder);
+        self.fe.set_list(list);
+        self.last_list_shown = Some(Instant::now());
+        if let Some(selection) = selection {
+            self.fe.set_selection(selection);
+        }
+    }
+
+    /// Truncates long descriptions, if configured to.
+    fn limit_descriptions(&self, list: &mut List) {
+        if let Some(max) = self.config.max_description_chars {
+            for item in &mut list.items {
+                item.limit_description(usize::from(max));
+            }
+        }
+    }
+
+    /// Stops loading any results, so that they don't replace the list
+    /// that an item is being activated from.
+    fn cancel_in_flight(&mut self) {
+        self.in_flight.send_replace(());
+        self.held_list = None;
+        self.refresh = None;
+    }
+
+    /// The list to refresh, if the result of the query at `index` is
+    /// still shown and visible.
+    fn refresh_for(&self, index: u64) -> Option<Refresh> {
+        self.refresh
+            .clone()
+            .filter(|refresh| refresh.index == index)
+            .filter(|_| self.visible && self.dispatched_actions == index)
+    }
+
+    /// Stops the deferred work of the previous activation.
+    ///
+    /// Returns a receiver that is notified when this activation is
+    /// replaced by the next one.
+    fn replace_activation(&mut self) -> watch::Receiver<()> {
+        for mut process in self.deferred_processes.drain(..) {
+            if let Err(e) = process.start_kill() {
+                warn!("failed to kill command: {e}");
+            }
+        }
+        self.activation.send_replace(());
+        self.activation.subscribe()
+    }
+
+    /// When lists stop being held, or [`None`] if they can be shown now.
+    ///
+    /// Lists are held until the results delay has passed, and until a
+    /// frame has passed since the last list was shown.
+    fn list_hold_deadline(&self) -> Option<Instant> {
+        let next_frame = self
+            .last_list_shown
+            .map(|shown| shown + LIST_FRAME_INTERVAL);
+        self.hold_lists_until
+            .max(next_frame)
+            .filter(|&until| Instant::now() < until)
+    }
+
+    /// Shows the held list, if there is one.
+    fn show_held_list(&mut self) {
+        if let Some((list, placeholder, index)) = self.held_list.take() {
+            self.show_list(list, placeholder, index);
+        }
+    }
+
+    /// Action index of the held list, or else of the shown list.
+    fn newest_list_index(&self) -> u64 {
+        self.held_list
+            .as_ref()
+            .map_or(self.activated_actions, |(_, _, index)| *index)
+    }
+
+    /// Remembers the shown items, including nested items.
+    fn set_shown_items(&mut self, items: &[ListItem]) {
+        self.shown_items.clear();
+        flatten_into(&mut self.shown_items, items);
+
+        let mut shortcuts = HashSet::new();
+        for item in &self.shown_items {
+            if let Some(shortcut) = item.shortcut() {
+                if !shortcuts.insert(shortcut) {
+                    warn!("duplicate shortcut {shortcut:?}: only the first item is activated");
+                }
+            }
+        }
+    }
+
+    /// The first shown item with this shortcut that can be activated.
+    fn item_for_shortcut(&self, key: &str) -> Option<ListItemId> {
+        self.shown_items
+            .iter()
+            .find(|item| item.shortcut() == Some(key))
+            .filter(|item| !item.is_error())
+            .map(ListItem::id)
+    }
+
+    /// Replaces the shown item from the same plugin with this key.
+    fn update_item(&mut self, key: &str, item: ListItem) {
+        let Some(shown) = self
+            .shown_items
+            .iter_mut()
+            .find(|shown| shown.plugin() == item.plugin() && shown.key() == Some(key))
+        else {
+            warn!("no shown item has the key {key:?}: not updating");
+            return;
+        };
+
+        let old = shown.id();
+        *shown = item.clone();
+        // the children of the old item are left, but they can't be
+        // shown anymore
+        flatten_into(&mut self.shown_items, &item.children());
+        self.fe.update_item(old, item);
+    }
+
+    /// Records that a query is about to be sent, returning its index.
+    fn dispatch_query(&mut self, query: &str) -> u64 {
+        self.dispatched_actions += 1;
+        let delay = Duration::from_millis(self.config.results_delay_ms.into());
+        let now = Instant::now();
+        // queries made while lists are held are shown at the same time
+        if !delay.is_zero() && self.hold_lists_until.is_none_or(|until| until <= now) {
+            self.hold_lists_until = Some(now + delay);
+        }
+        query.clone_into(&mut self.query);
+        if let Some(selection) = self.pending_selection.take() {
+            self.requested_selection = Some((self.dispatched_actions, selection));
+        }
+        self.dispatched_actions
+    }
+
+    /// Shows the prefix of the plugin that the query is for as a chip, if
+    /// that plugin is configured to.
+    fn update_prefix_chip(&mut self, query: &str) {
+        let chip = self
+            .plugins
+            .iter()
+            .find(|plugin| query.starts_with(plugin.prefix()))
+            .filter(|plugin| plugin.has_prefix_chip())
+            .map(|plugin| plugin.prefix().to_owned());
+        self.set_prefix_chip(chip);
+    }
+
+    fn set_prefix_chip(&mut self, chip: Option<String>) {
+        if self.prefix_chip != chip {
+            self.prefix_chip.clone_from(&chip);
+            self.fe.set_prefix_chip(chip);
+        }
+    }
+
+    /// Replaces an alias at the start of the query with its expansion.
+    ///
+    /// If the expansion is shown in the frontend, the frontend queries
+    /// it again, so the expanded query is not expanded a second time.
+    fn expand_alias(&mut self, query: String) -> String {
+        if self.shown_expansion.take().as_ref() == Some(&query) {
+            return query;
+        }
+        let Some(expanded) = expand_alias(&self.config.aliases, &query) else {
+            return query;
+        };
+
+        debug!("expanded alias in {query:?} to {expanded:?}");
+        if self.config.show_expanded_aliases {
+            self.fe.set_input(Input::new(expanded.clone()));
+            self.shown_expansion = Some(expanded.clone());
+        }
+        expanded
+    }
+
+    /// The command configured for a mouse button, if it has one.
+    fn command_for_button(&self, button: MouseButton) -> Option<String> {
+        let buttons = &self.config.mouse_buttons;
+        match button {
+            MouseButton::Middle => buttons.middle.clone(),
+            MouseButton::Back => buttons.back.clone(),
+            MouseButton::Forward => buttons.forward.clone(),
+        }
+    }
+
+    /// The actions to run instead of activating this item, if the plugin
+    /// gave a default action for it.
+    ///
+    /// Default actions only replace the `activate` command of the first
+    /// item.
+    fn default_actions_for(&self, item: &ListItemId, command_name: &str) -> Option<Vec<Action>> {
+        let (first, actions) = self.default_action.as_ref()?;
+        (command_name == "activate" && first == item).then(|| actions.clone())
+    }
+
+    fn shown_item(&self, item: &ListItemId) -> Option<&ListItem> {
+        self.shown_items.iter().find(|shown| shown.id() == *item)
+    }
+
+    /// The inline action of the item, which is run instead of asking the
+    /// plugin when the item is activated.
+    fn inline_actions_for(&self, item: &ListItemId, command_name: &str) -> Option<Vec<Action>> {
+        if command_name != "activate" {
+            return None;
+        }
+        let action = self.shown_item(item)?.inline_action()?;
+        Some(vec![action])
+    }
+
+    /// Asks the user to confirm activating the item, if the item needs
+    /// to be confirmed.
+    ///
+    /// The receiver gets the user's answer. Any previous question is
+    /// treated as cancelled.
+    fn request_confirmation(&mut self, item: &ListItemId) -> Option<oneshot::Receiver<bool>> {
+        let prompt = self.shown_item(item)?.confirmation_prompt()?;
+        let (sender, receiver) = oneshot::channel();
+        self.confirmation = Some(sender);
+        self.fe.confirm(prompt);
+        Some(receiver)
+    }
+
+    fn open_actions_menu(&mut self, item: &ListItemId) -> bool {
+        let Some(shown) = self.shown_items.iter().find(|shown| shown.id() == *item) else {
+            return false;
+        };
+        let manifest = item.plugin.manifest();
+        let commands: Vec<_> = shown
+            .available_commands()
+            .iter()
+            .map(|id| {
+                let title = manifest
+                    .commands
+                    .get(id)
+                    .map_or_else(|| id.clone(), |command| command.title.clone());
+                (id.clone(), title)
+            })
+            .collect();
+        if commands.len() < 2 {
+            return false;
+        }
+        self.actions_menu = Some(ActionsMenu {
+            item: item.clone(),
+            listed: (0..commands.len()).collect(),
+            commands,
+        });
+        self.show_actions_menu();
+        true
+    }
+
+    fn show_actions_menu(&mut self) {
+        let Some(menu) = &self.actions_menu else {
+            return;
+        };
+        let titles = menu
+            .listed
+            .iter()
+            .map(|&index| menu.commands[index].1.clone())
+            .collect();
+        self.fe.set_actions_menu(Some(titles));
+    }
+
+    fn close_actions_menu(&mut self) {
+        if self.actions_menu.take().is_some() {
+            self.fe.set_actions_menu(None);
+        }
+    }
+
+    /// Leaves the innermost context, returning the query to restore.
+    fn pop_context(&mut self) -> Option<String> {
+        let context = self.contexts.pop()?;
+        debug!("leaving context {:?}", context.label);
+        if self
+            .form
+            .take_if(|form| form.depth > self.contexts.len())
+            .is_some()
+        {
+            debug!("cancelled form");
+        }
+        self.set_contexts();
+        self.fe.set_input(context.previous.clone());
+        Some(context.previous.contents)
+    }
+
+    fn set_contexts(&mut self) {
+        let labels = self.contexts.iter().map(|c| c.label.clone()).collect();
+        self.fe.set_contexts(labels);
+    }
+
+    /// Optionally returns another string that should be queried.
+    #[tracing::instrument(skip(self))]
+    fn handle_event(&mut self, event: Result<PluginEvent>) -> Option<String> {
+        debug!("handling event");
+
+        match event {
+            Ok(PluginEvent::SetList {
+                list,
+                placeholder,
+                index,
+            }) => {
+                if index <= self.newest_list_index() {
+                    return None;
+                }
+                if self.list_hold_deadline().is_some() {
+                    debug!("holding list until the results delay or next frame");
+                    self.held_list = Some((list, placeholder, index));
+                } else {
+                    // a newer list replaces any held list
+                    self.held_list = None;
+                    self.show_list(list, placeholder, index);
+                }
+            }
+            Ok(PluginEvent::AppendList { mut list, index }) => {
+                if let Some((held, _, _)) = self
+                    .held_list
+                    .as_mut()
+                    .filter(|(_, _, held_index)| *held_index == index)
+                {
+                    // shown with the rest of the list
+                    held.items.append(&mut list.items);
+                    held.next_page = list.next_page;
+                    return None;
+                }
+                // the page is for a list that is no longer shown
+                if index != self.activated_actions {
+                    return None;
+                }
+                let max_results = usize::from(self.config.max_displayed_results);
+                let remaining = max_results.saturating_sub(self.shown_len);
+                if list.len() >= remaining {
+                    debug!("only showing {max_results} results");
+                    list.items.truncate(remaining);
+                    list.next_page = None;
+                }
+                self.limit_descriptions(&mut list);
+                self.next_page = list.next_page.take();
+                self.shown_len += list.len();
+                flatten_into(&mut self.shown_items, &list.items);
+                self.fe.append_list(list);
+            }
+            Ok(PluginEvent::SetSuggestions { suggestions, index }) => {
+                // only show suggestions for the newest list
+                if index == self.newest_list_index() {
+                    self.fe.set_suggestions(suggestions);
+                }
+            }
+            // sequential actions are normally run by `Host::handle_event`,
+            // as waiting for commands needs the lock to be released
+            Ok(PluginEvent::Run(actions) | PluginEvent::RunSequentially(actions)) => {
+                let keeps_query = keeps_query(&actions);
+                // exited commands don't need to be killed
+                self.deferred_processes
+                    .retain_mut(|process| matches!(process.try_wait(), Ok(None)));
+                let mut chained_query = None;
+                for action in close_last(actions) {
+                    let outcome = self.handle_action(action);
+                    chained_query = outcome.query.or(chained_query);
+                    self.deferred_processes.extend(outcome.process);
+                }
+                return self.finish_actions(keeps_query, chained_query);
+            }
+            Ok(PluginEvent::Cancelled) => {}
+            Err(e) => {
+                self.fe.display_error("Error in plugin", e);
+            }
+        }
+
         None
     }
+
+    /// Clears the query after the actions were run, unless they keep the
+    /// list open.
+    ///
+    /// Optionally returns another string that should be queried.
+    fn finish_actions(
+        &mut self,
+        keeps_query: bool,
+        chained_query: Option<String>,
+    ) -> Option<String> {
+        if !keeps_query && self.config.clear_query_on_activate {
+            self.fe.set_input(Input::default());
+            return Some(String::new());
+        }
+        chained_query
+    }
+
+    /// Starts the action, without waiting for any command it runs.
+    #[tracing::instrument(skip(self))]
+    fn handle_action(&mut self, action: Action) -> ActionOutcome {
+        info!("handling action {action:?}");
+
+        match action {
+            Action::Close => self.fe.close(),
+            Action::RunCommand(cmd, args) => {
+                let result = crate::spawn::free_null(&cmd, &args).context(format!(
+                    "failed to run command `{cmd} {args}`",
+                    args = args.join(" ")
+                ));
+                return self.started("Error running command", result);
+            }
+            Action::RunShell(str) => {
+                let result = crate::spawn::shell(&self.config.shell, &str)
+                    .context(format!("failed to run command `{str}`"));
+                return self.started("Error running command", result);
+            }
+            Action::Copy(str) => {
+                let result = self.fe.copy(str);
+                if result.is_ok() {
+                    self.fe.show_toast(Toast::Success("Copied".to_owned()));
+                }
+                self.report("Error copying", result);
+            }
+            Action::SetInput(input) => {
+                self.pending_selection = input.select_item;
+                self.fe.set_input(input.clone());
+                return ActionOutcome {
+                    query: Some(input.contents),
+                    process: None,
+                };
+            }
+            Action::OpenUrl(url) => {
+                let result = self.fe.open_url(url);
+                self.report("Error opening URL", result);
+            }
+            Action::PushContext { label, input } => {
+                self.contexts.push(QueryContext {
+                    label,
+                    previous: Input::new(self.query.clone()),
+                });
+                self.set_contexts();
+                self.fe.set_input(input.clone());
+                return ActionOutcome {
+                    query: Some(input.contents),
+                    process: None,
+                };
+            }
+            Action::UpdateItem { key, item } => self.update_item(&key, item),
+            Action::FocusEntry { select_all } => self.fe.focus_input(select_all),
+            Action::TypeText(text) => {
+                self.fe.close();
+                let key_delay = Duration::from_millis(self.config.type_text_delay_ms.into());
+                let result = crate::spawn::type_text(&text, key_delay);
+                self.report("Error typing text", result);
+            }
+            Action::WriteFile {
+                path,
+                contents,
+                append,
+            } => self.pending_writes.push(FileWrite {
+                path,
+                contents,
+                append,
+            }),
+            Action::Builtin(command) => self.run_builtin(command),
+            Action::ShowForm { plugin, form } => return self.show_form(plugin, form),
+            Action::OpenPreviewWindow { content } => self.fe.open_preview_window(content),
+            Action::ClearInput => self.clear_input(),
+            Action::CopyField(field) => {
+                // fields that exist were already replaced by `Copy`
+                self.report(
+                    "Error copying",
+                    Err(eyre!("activated item has no field `{field}`")),
+                );
+            }
+        }
+        ActionOutcome::default()
+    }
+
+    /// Empties the input and the list without querying again.
+    fn clear_input(&mut self) {
+        // results of earlier queries shouldn't replace the empty list
+        let index = self.dispatch_query("");
+        self.cancel_in_flight();
+        self.set_prefix_chip(None);
+        self.fe.clear_input();
+        self.show_list(List::default(), String::new(), index);
+    }
+
+    /// Enters a context for filling in the form's fields.
+    fn show_form(&mut self, plugin: Plugin, form: Form) -> ActionOutcome {
+        let Some(first) = form.fields.first() else {
+            warn!("{plugin:?} showed a form without any fields: ignoring");
+            return ActionOutcome::default();
+        };
+        self.contexts.push(QueryContext {
+            label: first.label.clone(),
+            previous: Input::new(self.query.clone()),
+        });
+        self.set_contexts();
+        self.form = Some(ActiveForm {
+            plugin,
+            form,
+            values: vec![],
+            depth: self.contexts.len(),
+        });
+        self.fe.set_input(Input::new(""));
+        ActionOutcome {
+            query: Some(String::new()),
+            process: None,
+        }
+    }
+
+    /// The list shown while filling in a form: a single item to activate
+    /// once the field is filled in, and the field's placeholder.
+    ///
+    /// Returns [`None`] if no form is being filled in.
+    fn form_list(&self, query: &str) -> Option<(List, String)> {
+        let active = self.form.as_ref()?;
+        let field = active.form.fields.get(active.values.len())?;
+        let description = match active.form.fields.get(active.values.len() + 1) {
+            Some(next) => format!("Press enter to fill in {}", next.label),
+            None => "Press enter to submit".to_owned(),
+        };
+        let item = ListItem::new(
+            active.plugin.clone(),
+            proto::ListItem {
+                id: FORM_ITEM_ID,
+                title: query.to_owned(),
+                description,
+                available_commands: vec!["activate".to_owned()],
+                ..Default::default()
+            },
+        );
+        let placeholder = if query.is_empty() {
+            field.placeholder.clone()
+        } else {
+            String::new()
+        };
+        Some((
+            List {
+                items: vec![item],
+                ..Default::default()
+            },
+            placeholder,
+        ))
+    }
+
+    /// Fills in the current field of the form with the query, if the
+    /// activated item is the form's item.
+    fn advance_form(&mut self, item: &ListItemId) -> Option<FormStep> {
+        if item.local_id != FORM_ITEM_ID {
+            return None;
+        }
+        let active = self
+            .form
+            .as_mut()
+            .filter(|active| active.plugin == item.plugin)?;
+        active.values.push(self.query.clone());
+        if let Some(next) = active.form.fields.get(active.values.len()) {
+            if let Some(context) = self.contexts.last_mut() {
+                context.label.clone_from(&next.label);
+            }
+            self.set_contexts();
+            return Some(FormStep::Next);
+        }
+
+        let active = self.form.take()?;
+        let previous = self
+            .contexts
+            .pop()
+            .map(|context| context.previous)
+            .unwrap_or_default();
+        self.set_contexts();
+        Some(FormStep::Submit {
+            plugin: active.plugin,
+            id: active.form.id,
+            values: active.values,
+            previous,
+        })
+    }
+
+    /// Reports an error if a command couldn't be started.
+    fn started(&mut self, title: &str, result: Result<Child>) -> ActionOutcome {
+        match result {
+            Ok(process) => ActionOutcome {
+                query: None,
+                process: Some(process),
+            },
+            Err(e) => {
+                self.report(title, Err(e));
+                ActionOutcome::default()
+            }
+        }
+    }
+
+    fn reload_config(&mut self) {
+        match Host::read_config() {
+            Ok(config) => self.apply_config(config),
+            Err(e) => {
+                error!("failed to reload config: {e:#}");
+                self.fe.display_error("Invalid config", e);
+            }
+        }
+    }
+
+    fn apply_config(&mut self, config: GlobalConfig) {
+        debug!("reloading");
+        self.plugins = Host::load_plugins(&config, &self.plugin_paths, &self.plugins);
+        Host::check_shell(&config, self.fe.as_mut());
+        if config.event_socket != self.config.event_socket {
+            // remove the old socket before making a new one
+            self.event_socket = None;
+            self.event_socket = Host::bind_event_socket(&config);
+        }
+        if config.max_concurrent_queries != self.config.max_concurrent_queries {
+            self.query_limiter = QueryLimiter::new(config.max_concurrent_queries);
+        }
+        self.config = config;
+    }
+
+    fn run_builtin(&mut self, command: BuiltinCommand) {
+        match command {
+            BuiltinCommand::ReloadConfig => self.reload_config(),
+            BuiltinCommand::ToggleCompact => self.fe.toggle_compact(),
+            BuiltinCommand::OpenConfig => {
+                let url = format!("file://{}", CONFIG_PATH.display());
+                let result = self.fe.open_url(url);
+                self.report("Error opening config", result);
+            }
+        }
+    }
+
+    /// Shows a failure toast with this title if an action failed.
+    ///
+    /// The full error is only logged, so that it isn't shown twice.
+    fn report(&mut self, title: &str, result: Result<()>) {
+        if let Err(e) = result {
+            error!("{title}: {e:#}");
+            self.fe.show_toast(Toast::Failure(title.to_owned()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        num::NonZeroU16,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use covey_config::{
+        config::{GlobalConfig, NoResultsAction, Shell},
+        keyed_list::KeyedList,
+    };
+    use parking_lot::Mutex;
+    use tokio::sync::watch;
+
+    use super::{
+        close_last, first_handled, no_results_action, notify_all, transform_query, Host, HostInner,
+        MouseButton, QueryLimiter, LIST_FRAME_INTERVAL,
+    };
+    use crate::{
+        builtin::BuiltinCommand,
+        event::{Action, NextPage, PluginEvent, QueryOutcome, Toast},
+        external::EventSocket,
+        proto, Frontend, Input, List, ListItem, ListItemId, Plugin,
+    };
+
+    /// Calls made to the [`MockFrontend`].
+    #[derive(Default)]
+    struct Calls {
+        inputs: Vec<String>,
+        selections: Vec<usize>,
+        contexts: Vec<String>,
+        flashes: usize,
+        /// Local IDs of the replaced items, and the titles of their
+        /// replacements.
+        updates: Vec<(u64, String)>,
+        /// Whether each focus selected all of the input.
+        focuses: Vec<bool>,
+        suggestions: Vec<String>,
+        /// Number of items in each list that was set.
+        list_lens: Vec<usize>,
+        toasts: Vec<Toast>,
+        /// Titles of the displayed errors.
+        errors: Vec<String>,
+        /// Questions asked before activating items.
+        confirmations: Vec<String>,
+        compact_toggles: usize,
+        closes: usize,
+        previews: Vec<Option<String>>,
+        preview_windows: Vec<String>,
+        actions_menus: Vec<Option<Vec<String>>>,
+        input_clears: usize,
+        /// Returned as the primary selection.
+        primary_selection: String,
+        primary_selection_reads: usize,
+        prefix_chips: Vec<Option<String>>,
+        /// The correction of each list that was set.
+        corrections: Vec<Option<String>>,
+        copies: Vec<String>,
+        urls: Vec<String>,
+        placeholders: Vec<String>,
+    }
+
+    struct MockFrontend(Arc<Mutex<Calls>>);
+
+    impl Frontend for MockFrontend {
+        fn close(&mut self) {
+            self.0.lock().closes += 1;
+        }
+        fn copy(&mut self, text: String) -> color_eyre::eyre::Result<()> {
+            self.0.lock().copies.push(text);
+            Ok(())
+        }
+        fn set_input(&mut self, input: Input) {
+            self.0.lock().inputs.push(input.contents);
+        }
+        fn focus_input(&mut self, select_all: bool) {
+            self.0.lock().focuses.push(select_all);
+        }
+        fn flash(&mut self) {
+            self.0.lock().flashes += 1;
+        }
+        fn toggle_compact(&mut self) {
+            self.0.lock().compact_toggles += 1;
+        }
+        fn set_preview(&mut self, preview: Option<String>) {
+            self.0.lock().previews.push(preview);
+        }
+        fn set_prefix_chip(&mut self, prefix: Option<String>) {
+            self.0.lock().prefix_chips.push(prefix);
+        }
+        fn open_preview_window(&mut self, content: String) {
+            self.0.lock().preview_windows.push(content);
+        }
+        fn clear_input(&mut self) {
+            self.0.lock().input_clears += 1;
+        }
+        fn set_actions_menu(&mut self, titles: Option<Vec<String>>) {
+            self.0.lock().actions_menus.push(titles);
+        }
+        fn primary_selection(&mut self) -> futures::future::BoxFuture<'static, String> {
+            let mut calls = self.0.lock();
+            calls.primary_selection_reads += 1;
+            Box::pin(std::future::ready(calls.primary_selection.clone()))
+        }
+        fn set_list(&mut self, list: List) {
+            let mut calls = self.0.lock();
+            calls.list_lens.push(list.len());
+            calls
+                .corrections
+                .push(list.correction.map(|input| input.contents));
+        }
+        fn append_list(&mut self, list: List) {
+            self.0.lock().list_lens.push(list.len());
+        }
+        fn set_suggestions(&mut self, suggestions: Vec<String>) {
+            self.0.lock().suggestions = suggestions;
+        }
+        fn set_selection(&mut self, index: usize) {
+            self.0.lock().selections.push(index);
+        }
+        fn update_item(&mut self, old: ListItemId, item: ListItem) {
+            self.0
+                .lock()
+                .updates
+                .push((old.local_id, item.title().to_owned()));
+        }
+        fn set_placeholder(&mut self, placeholder: String) {
+            self.0.lock().placeholders.push(placeholder);
+        }
+        fn set_contexts(&mut self, labels: Vec<String>) {
+            self.0.lock().contexts = labels;
+        }
+        fn confirm(&mut self, prompt: String) {
+            self.0.lock().confirmations.push(prompt);
+        }
+        fn open_url(&mut self, url: String) -> color_eyre::eyre::Result<()> {
+            self.0.lock().urls.push(url);
+            Ok(())
+        }
+        fn show_toast(&mut self, toast: Toast) {
+            self.0.lock().toasts.push(toast);
+        }
+        fn display_error(&mut self, title: &str, _: color_eyre::eyre::Report) {
+            self.0.lock().errors.push(title.to_owned());
+        }
+    }
+
+    fn host(config: &str) -> (HostInner, Arc<Mutex<Calls>>) {
+        static HOSTS: AtomicUsize = AtomicUsize::new(0);
+
+        let calls = Arc::new(Mutex::new(Calls::default()));
+        // each test host keeps its plugins' data apart from the user's
+        // and from other tests
+        let data_dir = std::env::temp_dir().join(format!(
+            "covey-test-data-{}-{}",
+            std::process::id(),
+            HOSTS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let inner = HostInner {
+            plugins: KeyedList::default(),
+            plugin_paths: crate::plugin::tests::temp_paths(&data_dir),
+            query: String::new(),
+            contexts: vec![],
+            visible: false,
+            pending_selection: None,
+            pending_writes: vec![],
+            requested_selection: None,
+            default_action: None,
+            event_socket: None,
+            shown_items: vec![],
+            shown_expansion: None,
+            next_page: None,
+            shown_len: 0,
+            hold_lists_until: None,
+            held_list: None,
+            last_list_shown: None,
+            form: None,
+            correction: None,
+            prefix_chip: None,
+            actions_menu: None,
+            refresh: None,
+            refreshing: None,
+            primary_selection: None,
+            confirmation: None,
+            in_flight: watch::channel(()).0,
+            activation: watch::channel(()).0,
+            deferred_processes: vec![],
+            selected: None,
+            query_limiter: QueryLimiter::default(),
+            dispatched_actions: 0,
+            activated_actions: 0,
+            fe: Box::new(MockFrontend(Arc::clone(&calls))),
+            config: toml::from_str::<GlobalConfig>(config).unwrap(),
+        };
+        (inner, calls)
+    }
+
+    #[test]
+    fn clears_query_on_activate() {
+        let (mut inner, calls) = host("clear_query_on_activate = true");
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::Copy("a".into())])));
+
+        assert_eq!(chained.as_deref(), Some(""));
+        assert_eq!(calls.lock().inputs, [""]);
+    }
+
+    #[tokio::test]
+    async fn write_file_creates_and_appends() {
+        let (inner, calls) = host("");
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let dir = std::env::temp_dir().join(format!("covey-write-test-{}", std::process::id()));
+        let path = dir.join("notes/today.md");
+        let write = |contents: &str, append| Action::WriteFile {
+            path: path.clone(),
+            contents: contents.to_owned(),
+            append,
+        };
+
+        for action in [
+            write("old\n", false),
+            write("first\n", false),
+            write("second\n", true),
+        ] {
+            Box::pin(host.handle_event(Ok(PluginEvent::Run(vec![action])))).await;
+        }
+        let written = std::fs::read_to_string(&path);
+
+        // the file's directory can't be created inside a file
+        Box::pin(
+            host.handle_event(Ok(PluginEvent::Run(vec![Action::WriteFile {
+                path: path.join("nested"),
+                contents: String::new(),
+                append: false,
+            }]))),
+        )
+        .await;
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(written.unwrap(), "first\nsecond\n");
+        assert!(matches!(&*calls.lock().toasts, [Toast::Failure(_)]));
+    }
+
+    #[test]
+    fn close_is_run_last() {
+        let actions = close_last(vec![
+            Action::Close,
+            Action::Copy("a".into()),
+            Action::OpenUrl("b".into()),
+        ]);
+        assert!(matches!(
+            &actions[..],
+            [Action::Copy(a), Action::OpenUrl(b), Action::Close] if a == "a" && b == "b"
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn actions_are_run_with_execution_policy() {
+        let (inner, calls) = host("");
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let dir = std::env::temp_dir().join(format!("covey-execution-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let run = |name: &str| {
+            let path = dir.join(name);
+            vec![
+                Action::Close,
+                Action::RunShell(format!("sleep 0.2; printf a >> '{}'", path.display())),
+                Action::WriteFile {
+                    path,
+                    contents: "b".to_owned(),
+                    append: true,
+                },
+            ]
+        };
+
+        Box::pin(host.handle_event(Ok(PluginEvent::RunSequentially(run("sequential"))))).await;
+        let sequential = std::fs::read_to_string(dir.join("sequential"));
+        Box::pin(host.handle_event(Ok(PluginEvent::Run(run("parallel"))))).await;
+        let parallel = std::fs::read_to_string(dir.join("parallel"));
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        _ = std::fs::remove_dir_all(&dir);
+
+        // the file is only written once the command has exited
+        assert_eq!(sequential.unwrap(), "ab");
+        // the command is still sleeping
+        assert_eq!(parallel.unwrap(), "b");
+        assert_eq!(calls.lock().closes, 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn activating_replaces_deferred_commands() {
+        let id = format!("covey-replace-activation-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let written = bin_dir.join("written");
+
+        Box::pin(host.query("hello".to_owned())).await;
+        let item = host.inner.lock().shown_items[0].id();
+        // run in parallel, so activating doesn't wait for it
+        let slow = format!("sh:sleep 0.3; touch '{}'", written.display());
+        Box::pin(host.activate(item.clone(), slow)).await;
+        Box::pin(host.activate(item, "activate".to_owned())).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let was_written = written.exists();
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(!was_written, "the sleeping command was not killed");
+        assert_eq!(calls.lock().inputs.last().unwrap(), "activate");
+    }
+
+    #[tokio::test]
+    async fn replaced_activations_drop_their_response() {
+        let id = format!("covey-replaced-activation-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.query("hello".to_owned())).await;
+        let item = host.inner.lock().shown_items[0].id();
+        let slow = tokio::spawn(host.activate(item.clone(), "slow-copy".to_owned()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Box::pin(host.activate(item, "activate".to_owned())).await;
+        slow.await.unwrap();
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(calls.lock().copies.is_empty());
+        assert_eq!(calls.lock().inputs.last().unwrap(), "activate");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn expired_results_are_refreshed_while_visible() {
+        let id = format!("covey-refresh-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        host.set_visible(true).await;
+        // the plugin is started before pausing time, so that waiting for
+        // its process doesn't time out
+        Box::pin(host.query("tick".to_owned())).await;
+        tokio::time::pause();
+        assert_eq!(calls.lock().list_lens.len(), 1);
+
+        // refreshed every 50ms
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        until_lists_shown(&calls, 2).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        until_lists_shown(&calls, 3).await;
+
+        host.set_visible(false).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(calls.lock().list_lens.len(), 3);
+
+        host.set_visible(true).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        until_lists_shown(&calls, 4).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+    }
+
+    /// Yields until `len` lists have been shown, which keeps paused time
+    /// from advancing while waiting for the plugin.
+    async fn until_lists_shown(calls: &Mutex<Calls>, len: usize) {
+        let start = std::time::Instant::now();
+        while calls.lock().list_lens.len() < len {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "only {} lists were shown",
+                calls.lock().list_lens.len()
+            );
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn entering_prefix_sets_placeholder() {
+        let id = format!("covey-placeholder-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"e \"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.query("e ".to_owned())).await;
+        Box::pin(host.query("e hello".to_owned())).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        // the placeholder is cleared once something is typed
+        assert_eq!(calls.lock().placeholders, ["Echo something", ""]);
+    }
+
+    #[test]
+    fn keeps_query_on_activate_by_default() {
+        let (mut inner, calls) = host("");
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::Copy("a".into())])));
+
+        assert_eq!(chained, None);
+        assert!(calls.lock().inputs.is_empty());
+    }
+
+    #[test]
+    fn keeps_query_on_activate() {
+        let (mut inner, calls) = host("clear_query_on_activate = false");
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::Copy("a".into())])));
+
+        assert_eq!(chained, None);
+        assert!(calls.lock().inputs.is_empty());
+    }
+
+    #[test]
+    fn set_input_is_not_cleared() {
+        let (mut inner, calls) = host("");
+        let input = Input {
+            contents: "new query".to_owned(),
+            selection: (0, 0),
+            ..Input::default()
+        };
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::SetInput(input)])));
+
+        assert_eq!(chained.as_deref(), Some("new query"));
+        assert_eq!(calls.lock().inputs, ["new query"]);
+    }
+
+    #[test]
+    fn focus_entry_keeps_query() {
+        let (mut inner, calls) = host("");
+        inner.query = "fire".to_owned();
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![
+            Action::Copy("firefox".into()),
+            Action::FocusEntry { select_all: true },
+        ])));
+
+        assert_eq!(chained, None);
+        let calls = calls.lock();
+        assert_eq!(calls.focuses, [true]);
+        assert!(calls.inputs.is_empty());
+    }
+
+    #[test]
+    fn clear_input_empties_without_querying() {
+        let (mut inner, calls) = host("");
+        inner.query = "buy milk".to_owned();
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::ClearInput])));
+
+        assert_eq!(chained, None);
+        assert_eq!(inner.query, "");
+        let calls = calls.lock();
+        assert_eq!(calls.input_clears, 1);
+        assert!(calls.inputs.is_empty());
+        assert_eq!(calls.list_lens, [0]);
+        assert_eq!(calls.closes, 0);
+    }
+
+    #[test]
+    fn preview_windows_keep_menu_open() {
+        let (mut inner, calls) = host("");
+        inner.query = "notes".to_owned();
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![
+            Action::OpenPreviewWindow {
+                content: "first".to_owned(),
+            },
+            Action::OpenPreviewWindow {
+                content: "second".to_owned(),
+            },
+        ])));
+
+        assert_eq!(chained, None);
+        let calls = calls.lock();
+        assert_eq!(calls.preview_windows, ["first", "second"]);
+        assert_eq!(calls.closes, 0);
+        assert!(calls.inputs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn passthrough_queries_fallback() {
+        let queried = Mutex::new(vec![]);
+        let handled = first_handled(["prefixed", "fallback"], |plugin| {
+            queried.lock().push(plugin);
+            async move {
+                Ok(if plugin == "prefixed" {
+                    QueryOutcome::Passthrough
+                } else {
+                    QueryOutcome::Handled(List::default())
+                })
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(handled.map(|(plugin, _)| plugin), Some("fallback"));
+        assert_eq!(*queried.lock(), ["prefixed", "fallback"]);
+    }
+
+    #[tokio::test]
+    async fn empty_list_is_handled() {
+        let handled = first_handled(["prefixed", "fallback"], |_| async {
+            Ok(QueryOutcome::Handled(List::default()))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(handled.map(|(plugin, _)| plugin), Some("prefixed"));
+    }
+
+    #[tokio::test]
+    async fn set_input_populates_frontend() {
+        let (inner, calls) = host("");
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.set_input(Input::new("git ")).await;
+
+        assert_eq!(calls.lock().inputs, ["git "]);
+    }
+
+    #[tokio::test]
+    async fn selecting_suggestion_sets_input() {
+        let (inner, calls) = host("");
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.handle_event(Ok(PluginEvent::SetList {
+            list: List::default(),
+            placeholder: String::new(),
+            index: 1,
+        }))
+        .await;
+        // suggestions for an old query are ignored
+        host.handle_event(Ok(PluginEvent::SetSuggestions {
+            suggestions: vec!["stale".to_owned()],
+            index: 0,
+        }))
+        .await;
+        host.handle_event(Ok(PluginEvent::SetSuggestions {
+            suggestions: vec!["yay-git".to_owned(), "yay-bin".to_owned()],
+            index: 1,
+        }))
+        .await;
+        assert_eq!(calls.lock().suggestions, ["yay-git", "yay-bin"]);
+
+        let suggestion = calls.lock().suggestions[0].clone();
+        host.set_input(Input::new(suggestion)).await;
+        assert_eq!(calls.lock().inputs, ["yay-git"]);
+    }
+
+    #[tokio::test]
+    async fn transform_replaces_query() {
+        let shell = Shell::default();
+        assert_eq!(
+            transform_query(&shell, "sed s/gti/git/", "gti status".to_owned()).await,
+            "git status"
+        );
+        assert_eq!(
+            transform_query(&shell, "exit 1", "gti status".to_owned()).await,
+            "gti status"
+        );
+    }
+
+    #[test]
+    fn activation_feedback() {
+        let (mut inner, calls) = host("activation_feedback = \"flash\"");
+        inner.activation_feedback();
+        assert_eq!(calls.lock().flashes, 1);
+
+        let (mut inner, calls) = host("");
+        inner.activation_feedback();
+        assert_eq!(calls.lock().flashes, 0);
+
+        // missing sound files are ignored
+        let (mut inner, _) = host("activation_feedback.sound = \"/does/not/exist.wav\"");
+        inner.activation_feedback();
+    }
+
+    #[tokio::test]
+    async fn action_results_are_shown() {
+        let (mut inner, calls) = host("");
+        inner.handle_event(Ok(PluginEvent::Run(vec![
+            Action::Copy("hello".to_owned()),
+            Action::RunCommand("covey-not-a-program".to_owned(), vec![]),
+        ])));
+
+        assert_eq!(
+            calls.lock().toasts,
+            [
+                Toast::Success("Copied".to_owned()),
+                Toast::Failure("Error running command".to_owned()),
+            ]
+        );
+        // the failure is only shown once
+        assert!(calls.lock().errors.is_empty());
+    }
+
+    #[test]
+    fn push_and_pop_contexts() {
+        let (mut inner, calls) = host("");
+        let push = |label: &str, query: &str| {
+            PluginEvent::Run(vec![Action::PushContext {
+                label: label.to_owned(),
+                input: Input::new(query),
+            }])
+        };
+
+        inner.query = "f ".to_owned();
+        assert_eq!(
+            inner
+                .handle_event(Ok(push("Documents", "f ~/Documents/")))
+                .as_deref(),
+            Some("f ~/Documents/")
+        );
+        inner.query = "f ~/Documents/notes".to_owned();
+        inner.handle_event(Ok(push("notes", "f ~/Documents/notes/")));
+        assert_eq!(calls.lock().contexts, ["Documents", "notes"]);
+
+        assert_eq!(inner.pop_context().as_deref(), Some("f ~/Documents/notes"));
+        assert_eq!(calls.lock().contexts, ["Documents"]);
+        assert_eq!(inner.pop_context().as_deref(), Some("f "));
+        assert!(calls.lock().contexts.is_empty());
+        assert_eq!(
+            calls.lock().inputs,
+            [
+                "f ~/Documents/",
+                "f ~/Documents/notes/",
+                "f ~/Documents/notes",
+                "f "
+            ]
+        );
+
+        // nothing left to pop
+        assert_eq!(inner.pop_context(), None);
+    }
+
+    #[tokio::test]
+    async fn notifies_every_plugin() {
+        let plugins = ["a", "b", "c"].map(|id| {
+            Plugin::with_manifest(
+                toml::from_str(&format!("id = {id:?}\nprefix = \"\"")).unwrap(),
+                toml::from_str(&format!("name = {id:?}")).unwrap(),
+            )
+        });
+        let notified = Mutex::new(vec![]);
+
+        notify_all(plugins, |plugin| {
+            notified.lock().push(plugin.id().as_str().to_owned());
+            async move {
+                if plugin.id().as_str() == "b" {
+                    color_eyre::eyre::bail!("failed")
+                }
+                Ok(())
+            }
+        })
+        .await;
+
+        // failures don't stop other plugins from being notified
+        assert_eq!(*notified.lock(), ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_queries() {
+        let limiter = QueryLimiter::new(NonZeroU16::new(1));
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        let query = || {
+            limiter.run(|| false, async {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            })
+        };
+        let results = futures::future::join3(query(), query(), query()).await;
+
+        assert_eq!(results, (Some(()), Some(()), Some(())));
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelled_queries_do_not_run() {
+        let limiter = QueryLimiter::new(NonZeroU16::new(1));
+        assert_eq!(limiter.run(|| true, async { 1 }).await, None);
+        assert_eq!(limiter.run(|| false, async { 1 }).await, Some(1));
+    }
+
+    #[test]
+    fn set_input_selects_item() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"files\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Files\"").unwrap(),
+        );
+        let list = |len| List {
+            items: (0..len)
+                .map(|_| ListItem::new(plugin.clone(), proto::ListItem::default()))
+                .collect(),
+            style: None,
+            default_actions: vec![],
+            next_page: None,
+            header: None,
+            correction: None,
+            refresh: None,
+        };
+        let set_list = |list, index| PluginEvent::SetList {
+            list,
+            placeholder: String::new(),
+            index,
+        };
+        let set_input = |select_item| {
+            PluginEvent::Run(vec![Action::SetInput(Input {
+                select_item: Some(select_item),
+                ..Input::new("docs/")
+            })])
+        };
+
+        let query = inner.handle_event(Ok(set_input(2))).unwrap();
+        let index = inner.dispatch_query(&query);
+        inner.handle_event(Ok(set_list(list(5), index)));
+        assert_eq!(calls.lock().selections, [2]);
+
+        // clamped to the end of the list
+        let query = inner.handle_event(Ok(set_input(10))).unwrap();
+        let index = inner.dispatch_query(&query);
+        // lists in the same frame would be held
+        std::thread::sleep(LIST_FRAME_INTERVAL);
+        inner.handle_event(Ok(set_list(list(3), index)));
+        assert_eq!(calls.lock().selections, [2, 2]);
+
+        // only applied to the results of that query
+        let index = inner.dispatch_query("other");
+        std::thread::sleep(LIST_FRAME_INTERVAL);
+        inner.handle_event(Ok(set_list(list(3), index)));
+        assert_eq!(calls.lock().list_lens, [5, 3, 3]);
+        assert_eq!(calls.lock().selections, [2, 2]);
+    }
+
+    #[test]
+    fn rapid_lists_are_coalesced() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"stream\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Stream\"").unwrap(),
+        );
+        let list = |len| List {
+            items: (0..len)
+                .map(|_| ListItem::new(plugin.clone(), proto::ListItem::default()))
+                .collect(),
+            ..Default::default()
+        };
+
+        for len in 1..=100 {
+            let index = inner.dispatch_query("q");
+            inner.handle_event(Ok(PluginEvent::SetList {
+                list: list(len),
+                placeholder: String::new(),
+                index,
+            }));
+        }
+        // the first list is shown straight away, and the rest are held
+        assert_eq!(calls.lock().list_lens, [1]);
+        assert!(inner.list_hold_deadline().is_some());
+
+        std::thread::sleep(LIST_FRAME_INTERVAL);
+        assert!(inner.list_hold_deadline().is_none());
+        inner.show_held_list();
+        // only the newest list is shown once the frame has passed
+        assert_eq!(calls.lock().list_lens, [1, 100]);
+    }
+
+    #[tokio::test]
+    async fn default_action_replaces_first_activation() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let item = |id| {
+            ListItem::new(
+                plugin.clone(),
+                proto::ListItem {
+                    id,
+                    ..Default::default()
+                },
+            )
+        };
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item(1), item(2)],
+                style: None,
+                default_actions: vec![Action::SetInput(Input::new("lucky"))],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        // other commands and items activate the plugin as usual
+        assert!(inner
+            .default_actions_for(&item(1).id(), "alt-activate")
+            .is_none());
+        assert!(inner
+            .default_actions_for(&item(2).id(), "activate")
+            .is_none());
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.activate(item(1).id(), "activate".to_owned()).await;
+        assert_eq!(calls.lock().inputs, ["lucky"]);
+    }
+
+    #[tokio::test]
+    async fn correction_is_applied() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"s \"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let response = proto::QueryResponse {
+            items: vec![proto::ListItem::default()],
+            correction: Some(proto::Input {
+                query: "hello".to_owned(),
+                range_lb: 5,
+                range_ub: 5,
+                select_item: None,
+            }),
+            ..Default::default()
+        };
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List::from_proto(&plugin, "helo", response),
+            placeholder: String::new(),
+            index: 1,
+        }));
+        assert_eq!(calls.lock().corrections, [Some("s hello".to_owned())]);
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        Box::pin(host.apply_correction().unwrap()).await;
+        assert_eq!(calls.lock().inputs, ["s hello"]);
+        // only applied once
+        assert!(host.apply_correction().is_none());
+    }
+
+    #[tokio::test]
+    async fn middle_click_runs_configured_command() {
+        let (mut inner, calls) = host("[mouse_buttons]\nmiddle = \"activate\"");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let item = ListItem::new(plugin, proto::ListItem::default());
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item.clone()],
+                style: None,
+                default_actions: vec![Action::SetInput(Input::new("clicked"))],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        assert!(host.click(item.id(), MouseButton::Back).is_none());
+        host.click(item.id(), MouseButton::Middle).unwrap().await;
+        assert_eq!(calls.lock().inputs, ["clicked"]);
+    }
+
+    #[tokio::test]
+    async fn shortcut_activates_first_item_with_it() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let item = |id, shortcut: &str| {
+            ListItem::new(
+                plugin.clone(),
+                proto::ListItem {
+                    id,
+                    shortcut: Some(shortcut.to_owned()),
+                    ..Default::default()
+                },
+            )
+        };
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item(1, "1"), item(2, "1"), item(3, "2")],
+                style: None,
+                default_actions: vec![Action::SetInput(Input::new("first"))],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        assert_eq!(inner.item_for_shortcut("1"), Some(item(1, "1").id()));
+        assert_eq!(inner.item_for_shortcut("2"), Some(item(3, "2").id()));
+        assert_eq!(inner.item_for_shortcut("3"), None);
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.activate_shortcut("1").unwrap().await;
+        assert_eq!(calls.lock().inputs, ["first"]);
+    }
+
+    #[tokio::test]
+    async fn activation_waits_for_confirmation() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"files\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Files\"").unwrap(),
+        );
+        let item = ListItem::new(
+            plugin,
+            proto::ListItem {
+                id: 1,
+                title: "notes.txt".to_owned(),
+                requires_confirmation: Some(true),
+                ..Default::default()
+            },
+        );
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item.clone()],
+                // activating the plugin needs a running plugin
+                default_actions: vec![Action::SetInput(Input::new("deleted"))],
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        let declined = tokio::spawn(host.activate(item.id(), "activate".to_owned()));
+        assert_eq!(
+            calls.lock().confirmations,
+            ["Are you sure you want to activate \"notes.txt\"?"]
+        );
+        host.confirm(false);
+        declined.await.unwrap();
+        assert!(calls.lock().inputs.is_empty());
+
+        let confirmed = tokio::spawn(host.activate(item.id(), "activate".to_owned()));
+        assert!(calls.lock().inputs.is_empty());
+        host.confirm(true);
+        confirmed.await.unwrap();
+        assert_eq!(calls.lock().inputs, ["deleted"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn selected_item_is_previewed() {
+        let id = format!("covey-preview-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let plugin = inner.plugins.get(id.as_str()).unwrap().clone();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let item = |local_id| ListItemId {
+            plugin: plugin.clone(),
+            local_id,
+        };
+
+        // the first item is unselected before its preview is requested
+        let first = tokio::spawn(host.select(item(1)));
+        host.select(item(2)).await;
+        first.await.unwrap();
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert_eq!(calls.lock().previews, [Some("preview of 2".to_owned())]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn actions_menu_activates_chosen_command() {
+        let id = format!("covey-actions-menu-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.query("hello".to_owned())).await;
+        let item = host.inner.lock().shown_items[0].id();
+        assert!(host.open_actions_menu(&item));
+        host.filter_actions_menu("alt");
+        assert!(host.select_action(1).is_none());
+        Box::pin(host.select_action(0).unwrap()).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let calls = calls.lock();
+        let titles = |titles: &[&str]| Some(titles.iter().map(|&t| t.to_owned()).collect());
+        assert_eq!(
+            calls.actions_menus,
+            [
+                titles(&["Activate", "Alt activate", "Complete"]),
+                titles(&["Alt activate"]),
+                None,
+            ]
+        );
+        assert_eq!(calls.inputs, ["alt-activate"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn primary_selection_is_sent_to_capable_plugins() {
+        let id = format!("covey-selection-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        "selected text".clone_into(&mut calls.lock().primary_selection);
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        host.set_visible(true).await;
+        Box::pin(host.query("a".to_owned())).await;
+        tokio::time::sleep(LIST_FRAME_INTERVAL).await;
+        Box::pin(host.query("b".to_owned())).await;
+        let titles: Vec<_> = {
+            let inner = host.inner.lock();
+            inner
+                .shown_items
+                .iter()
+                .map(|item| item.title().to_owned())
+                .collect()
+        };
+        assert_eq!(titles, ["b", "selected text"]);
+        // read once per show
+        assert_eq!(calls.lock().primary_selection_reads, 1);
+
+        host.set_visible(false).await;
+        host.set_visible(true).await;
+        Box::pin(host.query("c".to_owned())).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+        assert_eq!(calls.lock().primary_selection_reads, 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn form_is_filled_in_and_submitted() {
+        use crate::event::{Form, FormField};
+
+        let id = format!("covey-form-test-{}", std::process::id());
+        let bin_dir = crate::plugin::tests::echo_plugin_command(&id).await;
+        let (mut inner, calls) = host(&format!(
+            "[[plugins]]\nid = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ));
+        inner.plugin_paths = crate::plugin::tests::temp_paths(&bin_dir);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let plugin = inner.plugins.get(id.as_str()).unwrap().clone();
+        inner.query = "new issue".to_owned();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let field = |label: &str| FormField {
+            label: label.to_owned(),
+            placeholder: String::new(),
+        };
+        let form_item = || host.inner.lock().shown_items[0].id();
+
+        Box::pin(
+            host.handle_event(Ok(PluginEvent::Run(vec![Action::ShowForm {
+                plugin,
+                form: Form {
+                    id: "issue".to_owned(),
+                    fields: vec![field("Title"), field("Body")],
+                },
+            }]))),
+        )
+        .await;
+        assert_eq!(calls.lock().contexts, ["Title"]);
+
+        Box::pin(host.query("It broke".to_owned())).await;
+        Box::pin(host.activate(form_item(), "activate".to_owned())).await;
+        assert_eq!(calls.lock().contexts, ["Body"]);
+
+        tokio::time::sleep(LIST_FRAME_INTERVAL).await;
+        Box::pin(host.query("Steps to reproduce".to_owned())).await;
+        Box::pin(host.activate(form_item(), "activate".to_owned())).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(calls.lock().contexts.is_empty());
+        assert_eq!(
+            calls.lock().inputs,
+            ["", "", "issue: It broke, Steps to reproduce"]
+        );
+    }
+
+    #[tokio::test]
+    async fn escape_cancels_form() {
+        use crate::event::{Form, FormField};
+
+        let (inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"x\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"X\"").unwrap(),
+        );
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.inner.lock().query = "before".to_owned();
+        Box::pin(
+            host.handle_event(Ok(PluginEvent::Run(vec![Action::ShowForm {
+                plugin,
+                form: Form {
+                    id: "form".to_owned(),
+                    fields: vec![FormField {
+                        label: "Name".to_owned(),
+                        placeholder: "Ferris".to_owned(),
+                    }],
+                },
+            }]))),
+        )
+        .await;
+        assert!(host.inner.lock().form.is_some());
+
+        Box::pin(host.escape()).await;
+        assert!(host.inner.lock().form.is_none());
+        assert!(calls.lock().contexts.is_empty());
+        assert_eq!(calls.lock().inputs, ["", "before"]);
+    }
+
+    #[tokio::test]
+    async fn backspace_removes_prefix_chip() {
+        let (mut inner, calls) = host(
+            "[[plugins]]\nid = \"g\"\nprefix = \"g \"\ncommand = \"true\"\nscript = true\nprefix_chip = true\n\
+             [[plugins]]\nid = \"h\"\nprefix = \"h \"\ncommand = \"true\"\nscript = true",
+        );
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.query("g hello".to_owned())).await;
+        Box::pin(host.query("g hello world".to_owned())).await;
+        assert_eq!(calls.lock().prefix_chips, [Some("g ".to_owned())]);
+
+        Box::pin(host.remove_prefix().unwrap()).await;
+        assert_eq!(calls.lock().prefix_chips, [Some("g ".to_owned()), None]);
+        assert_eq!(calls.lock().inputs, ["hello world"]);
+        assert!(host.remove_prefix().is_none());
+
+        // only shown for plugins configured to
+        Box::pin(host.query("h hello".to_owned())).await;
+        assert_eq!(calls.lock().prefix_chips.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn debounced_plugins_wait_for_query_to_settle() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = std::env::temp_dir().join(format!("covey-debounce-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("log");
+        let log = dir.join("queries");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$2\" >> {}\necho '{{\"title\": \"x\"}}'\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (mut inner, _) = host(&format!(
+            "[[plugins]]\nid = \"web\"\nprefix = \"w \"\ncommand = \"{0}\"\nscript = true\ndebounce_ms = 300\n\
+             [[plugins]]\nid = \"local\"\nprefix = \"l \"\ncommand = \"{0}\"\nscript = true",
+            script.display()
+        ));
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let queries = || std::fs::read_to_string(&log).unwrap_or_default();
+
+        let start = std::time::Instant::now();
+        Box::pin(host.query("l local".to_owned())).await;
+        assert!(start.elapsed() < Duration::from_millis(300));
+        assert_eq!(queries(), "local\n");
+
+        let first = tokio::spawn(host.query("w a".to_owned()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queries(), "local\n", "not queried before the delay");
+        Box::pin(host.query("w ab".to_owned())).await;
+        first.await.unwrap();
+        let queries = queries();
+        _ = std::fs::remove_dir_all(&dir);
+
+        // the replaced query was never sent
+        assert_eq!(queries, "local\nab\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn activating_cancels_loading_results() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = std::env::temp_dir().join(format!("covey-cancel-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("slow");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nsleep 5\necho '{\"title\": \"late\"}'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let (mut inner, calls) = host(&format!(
+            // clearing the query would query the script again
+            "clear_query_on_activate = false\n[[plugins]]\nid = \"slow\"\nprefix = \"\"\ncommand = \"{}\"\nscript = true",
+            script.display()
+        ));
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"x\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"X\"").unwrap(),
+        );
+        let item = ListItem::new(plugin, proto::ListItem::default());
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item.clone()],
+                default_actions: vec![Action::Close],
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        let query = tokio::spawn(host.query("s".to_owned()));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        host.activate(item.id(), "activate".to_owned()).await;
+        let finished = tokio::time::timeout(Duration::from_secs(2), query).await;
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert!(finished.is_ok(), "query should stop once activated");
+        // only the list that the item was activated from was shown
+        assert_eq!(calls.lock().list_lens, [1]);
+        assert!(host
+            .inner
+            .lock()
+            .shown_items
+            .iter()
+            .all(|item| item.title() != "late"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn query_pattern_skips_plugins() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = std::env::temp_dir().join(format!("covey-pattern-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str| {
+            let path = dir.join(name);
+            std::fs::write(
+                &path,
+                format!(
+                    "#!/bin/sh\necho \"{name}\" >> '{}'\necho '{{\"title\": \"{name}\"}}'\n",
+                    dir.join("runs").display()
+                ),
+            )
+            .unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let (calculator, search) = (script("calculator"), script("search"));
+
+        let (mut inner, _) = host(&format!(
+            "[[plugins]]\nid = \"calculator\"\nprefix = \"\"\ncommand = \"{}\"\nscript = true\nquery_pattern = '^\\d+$'\n\
+             [[plugins]]\nid = \"search\"\nprefix = \"\"\ncommand = \"{}\"\nscript = true",
+            calculator.display(),
+            search.display(),
+        ));
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let shown = || host.inner.lock().shown_items[0].title().to_owned();
+
+        Box::pin(host.query("12".to_owned())).await;
+        let numeric = shown();
+        Box::pin(host.query("abc".to_owned())).await;
+        let alphabetic = shown();
+        let runs = std::fs::read_to_string(dir.join("runs"));
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(numeric, "calculator");
+        assert_eq!(alphabetic, "search");
+        // the calculator was never run for the alphabetic query
+        assert_eq!(runs.unwrap(), "calculator\nsearch\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn empty_query_is_sent_to_plugins_that_handle_it() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = std::env::temp_dir().join(format!("covey-empty-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = |name: &str| {
+            let path = dir.join(name);
+            std::fs::write(
+                &path,
+                format!(
+                    "#!/bin/sh\necho \"{name} '$2'\" >> '{}'\necho '{{\"title\": \"{name}\"}}'\n",
+                    dir.join("runs").display()
+                ),
+            )
+            .unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let (search, popular) = (script("search"), script("popular"));
+
+        let (mut inner, _) = host(&format!(
+            "[[plugins]]\nid = \"search\"\nprefix = \"\"\ncommand = \"{}\"\nscript = true\nhandles_empty_query = false\n\
+             [[plugins]]\nid = \"popular\"\nprefix = \"\"\ncommand = \"{}\"\nscript = true",
+            search.display(),
+            popular.display(),
+        ));
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        let shown = || host.inner.lock().shown_items[0].title().to_owned();
+
+        Box::pin(host.query(String::new())).await;
+        let empty = shown();
+        Box::pin(host.query("abc".to_owned())).await;
+        let searched = shown();
+        let runs = std::fs::read_to_string(dir.join("runs"));
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(empty, "popular");
+        assert_eq!(searched, "search");
+        // the search plugin was never run for the empty query
+        assert_eq!(runs.unwrap(), "popular ''\nsearch 'abc'\n");
+    }
+
+    #[tokio::test]
+    async fn builtin_commands_are_searched_and_run() {
+        let (mut inner, calls) = host("commands_prefix = \">\"");
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.query(">reload".to_owned())).await;
+        let reload = host.inner.lock().shown_items[0].clone();
+        assert_eq!(reload.title(), "Reload config");
+        let (actions, _) = reload
+            .plugin()
+            .activate(reload.id().local_id, "activate".to_owned())
+            .await
+            .unwrap();
+        assert!(matches!(
+            actions[..],
+            [Action::Builtin(BuiltinCommand::ReloadConfig)]
+        ));
+
+        Box::pin(host.query(">compact".to_owned())).await;
+        let compact = host.inner.lock().shown_items[0].id();
+        host.activate(compact, "activate".to_owned()).await;
+        assert_eq!(calls.lock().compact_toggles, 1);
+    }
+
+    #[tokio::test]
+    async fn escape_clears_query_before_closing() {
+        let (mut inner, calls) = host("escape = \"clear\"");
+        inner.query = "fire".to_owned();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.escape()).await;
+        assert_eq!(calls.lock().inputs, [""]);
+        assert_eq!(calls.lock().closes, 0);
+
+        Box::pin(host.escape()).await;
+        assert_eq!(calls.lock().inputs, [""]);
+        assert_eq!(calls.lock().closes, 1);
+    }
+
+    #[tokio::test]
+    async fn escape_closes_by_default() {
+        let (mut inner, calls) = host("");
+        inner.query = "fire".to_owned();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        Box::pin(host.escape()).await;
+        assert!(calls.lock().inputs.is_empty());
+        assert_eq!(calls.lock().closes, 1);
+    }
+
+    #[tokio::test]
+    async fn description_action_is_run() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"apps\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Apps\"").unwrap(),
+        );
+        let with_action = ListItem::new(
+            plugin.clone(),
+            proto::ListItem {
+                id: 1,
+                description_action: Some(Box::new(proto::Action {
+                    action: Some(proto::action::Action::SetInput(proto::Input {
+                        query: "website".to_owned(),
+                        ..Default::default()
+                    })),
+                })),
+                ..Default::default()
+            },
+        );
+        let plain = ListItem::new(
+            plugin,
+            proto::ListItem {
+                id: 2,
+                ..Default::default()
+            },
+        );
+        assert!(with_action.has_description_action());
+        assert!(!plain.has_description_action());
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![with_action.clone(), plain.clone()],
+                style: None,
+                default_actions: vec![],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        assert!(host.activate_description(&plain.id()).is_none());
+        host.activate_description(&with_action.id()).unwrap().await;
+        assert_eq!(calls.lock().inputs, ["website"]);
+    }
+
+    #[tokio::test]
+    async fn inline_action_skips_plugin() {
+        let (mut inner, calls) = host("");
+        // the plugin has no command, so asking it would fail
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"links\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Links\"").unwrap(),
+        );
+        let item = ListItem::new(
+            plugin,
+            proto::ListItem {
+                id: 1,
+                inline_action: Some(Box::new(proto::Action {
+                    action: Some(proto::action::Action::Copy(
+                        "https://example.com".to_owned(),
+                    )),
+                })),
+                ..Default::default()
+            },
+        );
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item.clone()],
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        host.activate(item.id(), "activate".to_owned()).await;
+        assert!(matches!(
+            &calls.lock().toasts[..],
+            [Toast::Success(message)] if message == "Copied"
+        ));
+
+        // other commands still ask the plugin
+        host.activate(item.id(), "alt-activate".to_owned()).await;
+        assert_eq!(calls.lock().toasts.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn copy_field_copies_metadata_of_item() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"files\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Files\"").unwrap(),
+        );
+        let copy_field = |field: &str| {
+            Some(Box::new(proto::Action {
+                action: Some(proto::action::Action::CopyField(field.to_owned())),
+            }))
+        };
+        let item = ListItem::new(
+            plugin.clone(),
+            proto::ListItem {
+                id: 1,
+                title: "notes.txt".to_owned(),
+                metadata: [("size".to_owned(), "12 KB".to_owned())].into(),
+                inline_action: copy_field("size"),
+                ..Default::default()
+            },
+        );
+        let missing = ListItem::new(
+            plugin,
+            proto::ListItem {
+                id: 2,
+                inline_action: copy_field("owner"),
+                ..Default::default()
+            },
+        );
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item.clone(), missing.clone()],
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        host.activate(item.id(), "activate".to_owned()).await;
+        assert_eq!(calls.lock().copies, ["12 KB"]);
+
+        host.activate(missing.id(), "activate".to_owned()).await;
+        assert_eq!(calls.lock().copies.len(), 1);
+        assert!(matches!(
+            calls.lock().toasts.last(),
+            Some(Toast::Failure(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn enter_without_results_runs_fallback() {
+        let (mut inner, calls) =
+            host("no_results_action.open-url = \"https://duckduckgo.com/?q={query}\"");
+        inner.query = "rust & go".to_owned();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        host.activate_without_results().unwrap().await;
+        assert_eq!(
+            calls.lock().urls,
+            ["https://duckduckgo.com/?q=rust%20%26%20go"]
+        );
+
+        // results are activated instead
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let item = ListItem::new(plugin, proto::ListItem::default());
+        host.inner.lock().handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item],
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        assert!(host.activate_without_results().is_none());
+    }
+
+    #[test]
+    fn no_results_shell_command_gets_raw_query() {
+        let action =
+            no_results_action(&NoResultsAction::RunShell("{query}".to_owned()), "ls -la ~");
+        assert!(matches!(action, Action::RunShell(command) if command == "ls -la ~"));
+    }
+
+    #[test]
+    fn only_pinned_items_are_reordered() {
+        let (mut inner, _) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"apps\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Apps\"").unwrap(),
+        );
+        let item = |id, pinned| {
+            ListItem::new(
+                plugin.clone(),
+                proto::ListItem {
+                    id,
+                    pinned: Some(pinned),
+                    ..Default::default()
+                },
+            )
+        };
+        let items = [item(1, true), item(2, true), item(3, false)];
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: items.to_vec(),
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        assert!(host
+            .reorder_pins(vec![items[1].id(), items[0].id()])
+            .is_some());
+        assert!(host
+            .reorder_pins(vec![items[2].id(), items[0].id()])
+            .is_none());
+        assert!(host.reorder_pins(vec![]).is_none());
+    }
+
+    #[test]
+    fn reload_keeps_unchanged_plugins() {
+        let plugins = "[[plugins]]\nid = \"a\"\nprefix = \"\"\ncommand = \"sh\"\n\
+            [[plugins]]\nid = \"b\"\nprefix = \"b \"\ncommand = \"sh\"";
+        let (mut inner, _) = host(plugins);
+        inner.plugins =
+            Host::load_plugins(&inner.config, &inner.plugin_paths, &KeyedList::default());
+        let old = inner.plugins.clone();
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+
+        let changed = format!(
+            "max_displayed_results = 10\n{}",
+            plugins.replace("\"b \"", "\"bb \"")
+        );
+        host.apply_config(toml::from_str(&changed).unwrap());
+
+        let inner = host.inner.lock();
+        assert_eq!(inner.config.max_displayed_results, 10);
+        let plugin = |plugins: &KeyedList<Plugin>, id| plugins.get(id).unwrap().clone();
+        assert!(plugin(&inner.plugins, "a").is_same_instance(&plugin(&old, "a")));
+        assert!(!plugin(&inner.plugins, "b").is_same_instance(&plugin(&old, "b")));
+        assert_eq!(plugin(&inner.plugins, "b").prefix(), "bb ");
+    }
+
+    #[test]
+    fn aliases_expand_first_word() {
+        let (mut inner, calls) = host("[aliases]\ngh = \"@github\"\nhub = \"gh\"");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"github\"\nprefix = \"@github \"").unwrap(),
+            toml::from_str("name = \"GitHub\"").unwrap(),
+        );
+
+        let expanded = inner.expand_alias("gh covey".to_owned());
+        assert_eq!(expanded, "@github covey");
+        assert_eq!(expanded.strip_prefix(plugin.prefix()), Some("covey"));
+        assert_eq!(inner.expand_alias("gh ".to_owned()), "@github ");
+        // only whole words that have been finished are expanded
+        assert_eq!(inner.expand_alias("gh".to_owned()), "gh");
+        assert_eq!(inner.expand_alias("ghost ".to_owned()), "ghost ");
+        // expansions aren't expanded again
+        assert_eq!(inner.expand_alias("hub x".to_owned()), "gh x");
+        // the input is left alone unless configured
+        assert!(calls.lock().inputs.is_empty());
+    }
+
+    #[test]
+    fn shown_alias_is_not_expanded_again() {
+        let (mut inner, calls) =
+            host("show_expanded_aliases = true\n[aliases]\ngh = \"hub\"\nhub = \"gh\"");
+        assert_eq!(inner.expand_alias("gh x".to_owned()), "hub x");
+        assert_eq!(calls.lock().inputs, ["hub x"]);
+
+        // the frontend queries the expansion that it was given
+        assert_eq!(inner.expand_alias("hub x".to_owned()), "hub x");
+        assert_eq!(calls.lock().inputs, ["hub x"]);
+        // but the same text typed later is expanded
+        assert_eq!(inner.expand_alias("hub x".to_owned()), "gh x");
+    }
+
+    #[test]
+    fn shown_results_are_capped() {
+        let (mut inner, calls) = host("max_displayed_results = 100");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let items = (0..500)
+            .map(|id| {
+                ListItem::new(
+                    plugin.clone(),
+                    proto::ListItem {
+                        id,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items,
+                style: None,
+                default_actions: vec![],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        assert_eq!(calls.lock().list_lens, [100]);
+        assert_eq!(inner.shown_items.len(), 100);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lists_within_delay_are_shown_once() {
+        let (mut inner, calls) = host("results_delay_ms = 20");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"x\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"X\"").unwrap(),
+        );
+        let list = |len| List {
+            items: (0..len)
+                .map(|id| {
+                    ListItem::new(
+                        plugin.clone(),
+                        proto::ListItem {
+                            id,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let set_list = |list, index| PluginEvent::SetList {
+            list,
+            placeholder: String::new(),
+            index,
+        };
+
+        let first = inner.dispatch_query("f");
+        inner.handle_event(Ok(set_list(list(1), first)));
+        let second = inner.dispatch_query("fi");
+        inner.handle_event(Ok(set_list(list(2), second)));
+        assert!(calls.lock().list_lens.is_empty());
+
+        inner.show_held_list();
+        inner.show_held_list();
+        assert_eq!(calls.lock().list_lens, [2]);
+
+        // lists after the delay are shown immediately
+        tokio::time::advance(Duration::from_millis(20)).await;
+        let third = inner.dispatch_query("fir");
+        tokio::time::advance(Duration::from_millis(20)).await;
+        inner.handle_event(Ok(set_list(list(3), third)));
+        assert_eq!(calls.lock().list_lens, [2, 3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pages_of_held_lists_are_kept() {
+        let (mut inner, calls) = host("results_delay_ms = 20");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"x\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"X\"").unwrap(),
+        );
+        let list = |len| List {
+            items: (0..len)
+                .map(|_| ListItem::new(plugin.clone(), proto::ListItem::default()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let first = inner.dispatch_query("f");
+        tokio::time::advance(Duration::from_millis(20)).await;
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: list(1),
+            placeholder: String::new(),
+            index: first,
+        }));
+        assert_eq!(calls.lock().list_lens, [1]);
+
+        let second = inner.dispatch_query("fi");
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: list(2),
+            placeholder: String::new(),
+            index: second,
+        }));
+        inner.handle_event(Ok(PluginEvent::AppendList {
+            list: list(3),
+            index: second,
+        }));
+        // the visible list is still the first one
+        assert_eq!(inner.activated_actions, first);
+        assert_eq!(inner.shown_items.len(), 1);
+        assert_eq!(calls.lock().list_lens, [1]);
+
+        tokio::time::advance(Duration::from_millis(20)).await;
+        inner.show_held_list();
+        assert_eq!(inner.activated_actions, second);
+        assert_eq!(calls.lock().list_lens, [1, 5]);
+        assert_eq!(inner.shown_items.len(), 5);
+    }
+
+    #[test]
+    fn stale_responses_are_discarded() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"x\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"X\"").unwrap(),
+        );
+        let list = |len| List {
+            items: (0..len)
+                .map(|id| {
+                    ListItem::new(
+                        plugin.clone(),
+                        proto::ListItem {
+                            id,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        let first = inner.dispatch_query("f");
+        let second = inner.dispatch_query("fi");
+        assert!(first < second);
+
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: list(2),
+            placeholder: String::new(),
+            index: second,
+        }));
+        // the response to the first query arrives late
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: list(1),
+            placeholder: String::new(),
+            index: first,
+        }));
+        inner.handle_event(Ok(PluginEvent::SetSuggestions {
+            suggestions: vec!["foo".to_owned()],
+            index: first,
+        }));
+
+        assert_eq!(calls.lock().list_lens, [2]);
+        assert!(calls.lock().suggestions.is_empty());
+        assert_eq!(inner.shown_items.len(), 2);
+    }
+
+    #[test]
+    fn exact_match_is_selected() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"commands\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Commands\"").unwrap(),
+        );
+        let item = |id, title: &str, exact_match| {
+            ListItem::new(
+                plugin.clone(),
+                proto::ListItem {
+                    id,
+                    title: title.to_owned(),
+                    exact_match: Some(exact_match),
+                    ..Default::default()
+                },
+            )
+        };
+        let set_list = |items, index| PluginEvent::SetList {
+            list: List {
+                items,
+                ..Default::default()
+            },
+            placeholder: String::new(),
+            index,
+        };
+
+        // the query is "reload"
+        inner.handle_event(Ok(set_list(
+            vec![
+                item(1, "reload-window", false),
+                item(2, "reload", true),
+                item(3, "reload-plugins", false),
+            ],
+            1,
+        )));
+        assert_eq!(calls.lock().selections, [1]);
+
+        // nothing is selected without an exact match
+        inner.handle_event(Ok(set_list(vec![item(1, "reload-window", false)], 2)));
+        assert_eq!(calls.lock().selections, [1]);
+    }
+
+    #[test]
+    fn pages_are_appended_to_shown_list() {
+        let (mut inner, calls) = host("max_displayed_results = 8");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"search\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Search\"").unwrap(),
+        );
+        let page = |ids: std::ops::Range<u64>| List {
+            items: ids
+                .map(|id| {
+                    ListItem::new(
+                        plugin.clone(),
+                        proto::ListItem {
+                            id,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            next_page: Some(NextPage {
+                plugin: plugin.clone(),
+                query: String::new(),
+                token: "next".to_owned(),
+            }),
+            ..Default::default()
+        };
+
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: page(0..5),
+            placeholder: String::new(),
+            index: 1,
+        }));
+        assert!(inner.next_page.is_some());
+
+        // a page of a list that was replaced
+        inner.handle_event(Ok(PluginEvent::AppendList {
+            list: page(10..15),
+            index: 0,
+        }));
+        assert_eq!(calls.lock().list_lens, [5]);
+
+        inner.handle_event(Ok(PluginEvent::AppendList {
+            list: page(5..10),
+            index: 1,
+        }));
+        assert_eq!(calls.lock().list_lens, [5, 3]);
+        assert_eq!(inner.shown_items.len(), 8);
+        // no more results fit
+        assert!(inner.next_page.is_none());
+    }
+
+    #[test]
+    fn middle_click_defaults_to_alt_activate() {
+        let (inner, _) = host("");
+        assert_eq!(
+            inner.command_for_button(MouseButton::Middle).as_deref(),
+            Some("alt-activate")
+        );
+        assert_eq!(inner.command_for_button(MouseButton::Forward), None);
+    }
+
+    #[tokio::test]
+    async fn activation_is_sent_to_event_socket() {
+        let (mut inner, _) = host("");
+        let (socket, buffer) = EventSocket::to_buffer();
+        inner.event_socket = Some(socket);
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"apps\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Apps\"").unwrap(),
+        );
+        let firefox = ListItem::new(
+            plugin,
+            proto::ListItem {
+                title: "Firefox".to_owned(),
+                ..Default::default()
+            },
+        );
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![firefox.clone()],
+                style: None,
+                // activating the plugin needs a running plugin
+                default_actions: vec![Action::Close],
+                next_page: None,
+                header: None,
+                correction: None,
+                refresh: None,
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        let host = Host {
+            inner: Arc::new(Mutex::new(inner)),
+        };
+        host.set_visible(true).await;
+        host.activate(firefox.id(), "activate".to_owned()).await;
+        if let Some(socket) = &host.inner.lock().event_socket {
+            socket.flush();
+        }
+
+        assert_eq!(
+            String::from_utf8(buffer.lock().clone()).unwrap(),
+            "{\"event\":\"shown\"}\n{\"event\":\"activated\",\"title\":\"Firefox\"}\n"
+        );
+    }
+
+    #[test]
+    fn update_item_replaces_row() {
+        let (mut inner, calls) = host("");
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"mail\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Mail\"").unwrap(),
+        );
+        let item = |id, title: &str, key: &str| {
+            ListItem::new(
+                plugin.clone(),
+                proto::ListItem {
+                    id,
+                    title: title.to_owned(),
+                    key: Some(key.to_owned()),
+                    ..Default::default()
+                },
+            )
+        };
+        inner.handle_event(Ok(PluginEvent::SetList {
+            list: List {
+                items: vec![item(1, "Hello", "a"), item(2, "Invoice", "b")],
+                ..List::default()
+            },
+            placeholder: String::new(),
+            index: 1,
+        }));
+
+        let chained = inner.handle_event(Ok(PluginEvent::Run(vec![Action::UpdateItem {
+            key: "b".to_owned(),
+            item: item(3, "★ Invoice", "b"),
+        }])));
+
+        // the list stays open
+        assert_eq!(chained, None);
+        assert!(calls.lock().inputs.is_empty());
+        assert_eq!(calls.lock().updates, [(2, "★ Invoice".to_owned())]);
+        let titles: Vec<_> = inner.shown_items.iter().map(ListItem::title).collect();
+        assert_eq!(titles, ["Hello", "★ Invoice"]);
+    }
 }
@@ -1,14 +1,22 @@
+mod builtin;
 mod event;
+mod external;
 mod host;
+pub mod linkify;
 mod plugin;
 mod proto;
+mod script;
+mod secret;
 mod spawn;
+pub mod time;
 
 use std::{path::PathBuf, sync::LazyLock};
 
-pub use event::{Icon, Input, List, ListItem, ListItemId, ListStyle};
-pub use host::Host;
-pub use plugin::Plugin;
+use futures::future::BoxFuture;
+
+pub use event::{Icon, Input, List, ListItem, ListItemId, ListStyle, TextSpan, Toast};
+pub use host::{Host, MouseButton};
+pub use plugin::{Capability, Plugin, RequestKind};
 
 pub static CONFIG_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
     dirs::config_dir()
@@ -18,6 +26,14 @@ pub static CONFIG_DIR: LazyLock<PathBuf> = LazyLock::new(|| {
 pub static CONFIG_PATH: LazyLock<PathBuf> = LazyLock::new(|| CONFIG_DIR.join("config.toml"));
 pub static DATA_DIR: LazyLock<PathBuf> =
     LazyLock::new(|| dirs::data_dir().expect("data dir must exist").join("covey"));
+/// Socket that external tools can connect to for events, if the
+/// `event_socket` config is enabled.
+pub static EVENT_SOCKET_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    dirs::runtime_dir().map_or_else(
+        || DATA_DIR.join("events.sock"),
+        |dir| dir.join("covey-events.sock"),
+    )
+});
 
 /// A controller for the UI.
 ///
@@ -30,14 +46,130 @@ pub trait Frontend: Send + 'static {
     fn close(&mut self);
 
     /// Copy a string to the clipboard.
-    fn copy(&mut self, str: String);
+    ///
+    /// # Errors
+    /// Returns an error if the clipboard could not be written to.
+    fn copy(&mut self, str: String) -> color_eyre::eyre::Result<()>;
 
     /// Set the UI input to the provided input.
     fn set_input(&mut self, input: Input);
 
+    /// Empty the input, without querying the empty input.
+    ///
+    /// The list is emptied separately with [`Frontend::set_list`]. Sets
+    /// the input to an empty string by default.
+    fn clear_input(&mut self) {
+        self.set_input(Input::default());
+    }
+
+    /// Focus the input, selecting all of its contents if `select_all`
+    /// is true.
+    fn focus_input(&mut self, select_all: bool);
+
     /// Set the UI results list to the provided list.
     fn set_list(&mut self, list: List);
 
+    /// Add more results to the end of the list, after
+    /// [`Host::load_more`] is called.
+    fn append_list(&mut self, list: List);
+
+    /// Set the suggested queries, which are shown separately from
+    /// the list.
+    ///
+    /// Selecting a suggestion should call [`Host::set_input`] with it.
+    fn set_suggestions(&mut self, suggestions: Vec<String>);
+
+    /// Select the list item at this index.
+    ///
+    /// This is only called right after [`Frontend::set_list`], with an
+    /// index that is in the list.
+    fn set_selection(&mut self, index: usize);
+
+    /// Replace the shown item with the ID `old` with a new item.
+    ///
+    /// The selection and the rest of the list should not change.
+    fn update_item(&mut self, old: ListItemId, item: ListItem);
+
+    /// Set the placeholder text of the input.
+    ///
+    /// This is shown after the input contents, as the query may be a
+    /// non-empty plugin prefix. An empty string should show the default
+    /// placeholder.
+    fn set_placeholder(&mut self, placeholder: String);
+
+    /// Show the prefix of the active plugin as a chip before the input,
+    /// or remove the chip if it is [`None`].
+    ///
+    /// While a chip is shown, the input should not contain the prefix,
+    /// but queries should still include it. Pressing backspace at the
+    /// start of the input should call [`Host::remove_prefix`].
+    ///
+    /// Does nothing by default.
+    fn set_prefix_chip(&mut self, _prefix: Option<String>) {}
+
+    /// Set the breadcrumbs of the contexts that have been navigated into,
+    /// from outermost to innermost.
+    ///
+    /// This is empty when not in any context.
+    fn set_contexts(&mut self, labels: Vec<String>);
+
+    /// Ask the user to confirm activating an item, with this question.
+    ///
+    /// The answer should be given with [`Host::confirm`]. The item is
+    /// not activated until then.
+    fn confirm(&mut self, prompt: String);
+
+    /// Open a URL with the system's default handler.
+    ///
+    /// # Errors
+    /// Returns an error if the URL could not be opened.
+    fn open_url(&mut self, url: String) -> color_eyre::eyre::Result<()>;
+
+    /// Briefly flash the window to show that an item was activated.
+    ///
+    /// This is only called if the user enabled it. Does nothing by default.
+    fn flash(&mut self) {}
+
+    /// Show the full content of the selected item next to the list,
+    /// or hide the preview if it is [`None`].
+    ///
+    /// Does nothing by default.
+    fn set_preview(&mut self, _preview: Option<String>) {}
+
+    /// Show the content in a new window, without closing the menu.
+    ///
+    /// Each call should open another window, so that several results
+    /// can be peeked at together. Does nothing by default.
+    fn open_preview_window(&mut self, _content: String) {}
+
+    /// Show the titles of an item's commands in a menu, best match
+    /// first, or hide the menu if it is [`None`].
+    ///
+    /// The chosen command should be given with [`Host::select_action`].
+    /// Does nothing by default.
+    fn set_actions_menu(&mut self, _titles: Option<Vec<String>>) {}
+
+    /// Switch between compact and normal list rows.
+    ///
+    /// Does nothing by default.
+    fn toggle_compact(&mut self) {}
+
+    /// Briefly show whether an action worked, like "Copied".
+    ///
+    /// Failures are also shown with [`Frontend::display_error`], so this
+    /// only needs a short message. Does nothing by default.
+    fn show_toast(&mut self, _toast: Toast) {}
+
+    /// Read the text selected in other apps, for plugins that use it.
+    ///
+    /// The returned future is awaited without holding the host's lock, and
+    /// should resolve to an empty string if nothing is selected. By default,
+    /// this reads the primary selection with `wl-paste` or `xclip`, or the
+    /// clipboard on macOS.
+    fn primary_selection(&mut self) -> BoxFuture<'static, String> {
+        Box::pin(spawn::primary_selection())
+    }
+
     // TODO: refactor this lib to have a custom error type
     fn display_error(&mut self, title: &str, error: color_eyre::eyre::Report);
 }
@@ -0,0 +1,94 @@
+//! Finding URLs in text to show as clickable links.
+
+/// A section of text that is either plain text or a URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Url(&'a str),
+}
+
+/// Splits some text into plain text and URLs.
+///
+/// Only `http://` and `https://` URLs are detected. A URL continues until
+/// the next whitespace, excluding any trailing punctuation.
+///
+/// Empty text segments are not included.
+pub fn segments(mut text: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+
+    while let Some(start) = find_url_start(text) {
+        let rest = &text[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let url = rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"']);
+
+        if start > 0 {
+            segments.push(Segment::Text(&text[..start]));
+        }
+        segments.push(Segment::Url(url));
+        text = &rest[url.len()..];
+    }
+
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+
+    segments
+}
+
+/// Finds the byte index of the start of the next URL, if there is one.
+fn find_url_start(text: &str) -> Option<usize> {
+    ["https://", "http://"]
+        .into_iter()
+        .filter_map(|scheme| {
+            text.match_indices(scheme)
+                // must have something after the scheme
+                .find(|(i, _)| {
+                    text[i + scheme.len()..]
+                        .chars()
+                        .next()
+                        .is_some_and(|c| !c.is_whitespace())
+                })
+                .map(|(i, _)| i)
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{segments, Segment};
+
+    #[test]
+    fn no_urls() {
+        assert_eq!(
+            segments("just some text"),
+            [Segment::Text("just some text")]
+        );
+        assert_eq!(
+            segments("http:// is not a url"),
+            [Segment::Text("http:// is not a url")]
+        );
+        assert!(segments("").is_empty());
+    }
+
+    #[test]
+    fn multiple_urls() {
+        assert_eq!(
+            segments("see https://example.com/a?b=c, or (http://foo.org)."),
+            [
+                Segment::Text("see "),
+                Segment::Url("https://example.com/a?b=c"),
+                Segment::Text(", or ("),
+                Segment::Url("http://foo.org"),
+                Segment::Text(")."),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_url() {
+        assert_eq!(
+            segments("https://example.com"),
+            [Segment::Url("https://example.com")]
+        );
+    }
+}
@@ -1,15 +1,141 @@
 use core::fmt;
-use std::{hash::Hash, path::PathBuf, sync::Arc};
+use std::{
+    env,
+    ffi::OsString,
+    future::Future,
+    hash::Hash,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use color_eyre::eyre::{ContextCompat, Result};
+use color_eyre::eyre::{bail, eyre, Context as _, ContextCompat, Result};
 use covey_config::{
-    config::PluginConfig,
-    keyed_list::{Key, Keyed},
-    manifest::PluginManifest,
+    config::{FrecencyStrategy, GlobalConfig, MatchAlgorithm, MatchScoring, PluginConfig},
+    keyed_list::{Key, Keyed, KeyedList},
+    manifest::{PluginConfigSchema, PluginManifest},
+    protocol::{DEFAULT_MAX_MESSAGE_BYTES, MAX_MESSAGE_BYTES_VAR, PROTOCOL_VERSION},
 };
 use tokio::fs;
 
-use crate::{event::Action, proto, Input, List, DATA_DIR};
+use crate::{
+    builtin::BuiltinCommand,
+    event::{Action, ExecutionPolicy, Form, Icon, List, ListItem, QueryOutcome},
+    proto, Input, DATA_DIR,
+};
+
+/// Parts of the global config that are used by every plugin.
+#[derive(Debug, Clone, PartialEq)]
+struct GlobalOptions {
+    /// Timeout used if the plugin's config doesn't have one.
+    timeout_ms: Option<u32>,
+    /// Whether the plugin should record settled queries.
+    log_queries: bool,
+    /// Whether the plugin should record copied text.
+    clipboard_history: bool,
+    /// Minimum fuzzy match score of ranked items.
+    min_score: Option<f32>,
+    /// Bonuses and penalties of fuzzy matching.
+    match_scoring: MatchScoring,
+    /// Algorithm of fuzzy matching.
+    match_algorithm: MatchAlgorithm,
+    /// How to rank items by their usage history.
+    frecency: FrecencyStrategy,
+    /// Whether to show recent items on an empty query.
+    show_recent: bool,
+    /// Number of activations that the plugin should keep.
+    max_history_entries: Option<u32>,
+    /// Maximum size of a message to or from the plugin.
+    max_message_bytes: u32,
+}
+
+impl Default for GlobalOptions {
+    fn default() -> Self {
+        Self {
+            timeout_ms: None,
+            log_queries: false,
+            clipboard_history: false,
+            min_score: None,
+            match_scoring: MatchScoring::default(),
+            match_algorithm: MatchAlgorithm::default(),
+            frecency: FrecencyStrategy::default(),
+            show_recent: false,
+            max_history_entries: None,
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+        }
+    }
+}
+
+impl GlobalOptions {
+    fn from_config(config: &GlobalConfig) -> Self {
+        Self {
+            timeout_ms: config.timeout_ms,
+            log_queries: config.log_queries,
+            clipboard_history: config.clipboard_history,
+            min_score: config.min_score,
+            match_scoring: config.match_scoring.clone(),
+            match_algorithm: config.match_algorithm,
+            frecency: config.frecency,
+            show_recent: config.show_recent,
+            max_history_entries: config.max_history_entries,
+            max_message_bytes: config
+                .max_message_bytes
+                .unwrap_or(DEFAULT_MAX_MESSAGE_BYTES),
+        }
+    }
+}
+
+/// Where plugins are looked up and keep their data.
+#[derive(Debug, Clone)]
+pub(crate) struct PluginPaths {
+    /// Directory containing the data directory of every plugin.
+    plugins_dir: PathBuf,
+    /// Directories that plugin commands are searched in, formatted like
+    /// the `PATH` environment variable.
+    search_path: Option<OsString>,
+}
+
+impl Default for PluginPaths {
+    fn default() -> Self {
+        Self {
+            plugins_dir: DATA_DIR.join("plugins"),
+            search_path: env::var_os("PATH"),
+        }
+    }
+}
+
+impl PluginPaths {
+    fn data_directory(&self, plugin_name: &str) -> PathBuf {
+        self.plugins_dir.join(plugin_name)
+    }
+
+    fn binary(&self, plugin_name: &str) -> PathBuf {
+        self.data_directory(plugin_name).join(plugin_name)
+    }
+
+    fn manifest(&self, plugin_name: &str) -> PathBuf {
+        self.data_directory(plugin_name).join("manifest.toml")
+    }
+
+    fn database(&self, plugin_name: &str) -> PathBuf {
+        self.data_directory(plugin_name).join("data.db")
+    }
+}
+
+/// Timeout of requests to a plugin if none is configured.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time that a plugin has to start if none is configured.
+///
+/// Starting can take much longer than a request, so this doesn't fall
+/// back to the configured request timeouts.
+const DEFAULT_SPAWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variable that tells a plugin how many threads to use.
+const WORKER_THREADS_VAR: &str = "COVEY_WORKER_THREADS";
+
+/// ID of the plugin that lists covey's own commands.
+const BUILTIN_ID: &str = "covey";
 
 /// A ref-counted reference to a plugin instance.
 ///
@@ -26,13 +152,78 @@ pub struct Plugin {
 }
 
 impl Plugin {
-    /// Initialises a plugin from it's configuration.
-    pub(crate) fn new(config: PluginConfig) -> Result<Self> {
+    /// Initialises a plugin from it's configuration, keeping its data
+    /// in `paths`.
+    ///
+    /// The global timeout is used if the plugin's configuration
+    /// doesn't specify a timeout.
+    pub(crate) fn with_paths(
+        config: PluginConfig,
+        global: &GlobalConfig,
+        paths: PluginPaths,
+    ) -> Result<Self> {
         Ok(Self {
-            plugin: Arc::new(implementation::LazyPlugin::new(config)?),
+            plugin: Arc::new(implementation::LazyPlugin::new(
+                config,
+                GlobalOptions::from_config(global),
+                paths,
+            )?),
         })
     }
 
+    /// The plugin that lists covey's own commands, queried with this
+    /// prefix.
+    pub(crate) fn builtin(prefix: String) -> Self {
+        let config = PluginConfig {
+            id: Key::new(BUILTIN_ID),
+            prefix,
+            config: serde_json::Map::new(),
+            commands: std::collections::HashMap::new(),
+            timeout_ms: None,
+            timeouts: covey_config::config::RequestTimeouts::default(),
+            debounce_ms: None,
+            command: None,
+            script: false,
+            worker_threads: None,
+            limits: covey_config::config::ResourceLimits::default(),
+            query_pattern: None,
+            prefix_chip: false,
+            track_activations: None,
+            handles_empty_query: None,
+        };
+        Self {
+            plugin: Arc::new(implementation::LazyPlugin::builtin(
+                config,
+                PluginManifest::new("Covey"),
+            )),
+        }
+    }
+
+    /// Whether this plugin was created with the same configuration,
+    /// so it doesn't need to be recreated.
+    pub(crate) fn is_configured_by(&self, config: &PluginConfig, global: &GlobalConfig) -> bool {
+        self.plugin.config == *config && self.plugin.global == GlobalOptions::from_config(global)
+    }
+
+    /// Whether both are the same instance of a plugin, rather than
+    /// just having the same ID.
+    #[cfg(test)]
+    pub(crate) fn is_same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.plugin, &other.plugin)
+    }
+
+    /// Constructs a plugin without reading its manifest from disk.
+    #[cfg(test)]
+    pub(crate) fn with_manifest(config: PluginConfig, manifest: PluginManifest) -> Self {
+        Self {
+            plugin: Arc::new(implementation::LazyPlugin::with_manifest(
+                config,
+                manifest,
+                GlobalOptions::default(),
+            )),
+        }
+    }
+
     pub fn id(&self) -> &Key {
         &self.plugin.config.id
     }
@@ -41,56 +232,295 @@ impl Plugin {
         &self.plugin.config.prefix
     }
 
+    /// Whether the prefix is shown as a chip while this plugin is active.
+    pub fn has_prefix_chip(&self) -> bool {
+        self.plugin.config.prefix_chip && !self.prefix().is_empty()
+    }
+
+    /// Whether the plugin should record activations of its items.
+    pub fn tracks_activations(&self) -> bool {
+        self.plugin.tracks_activations()
+    }
+
+    /// Whether the query (without the prefix) matches the plugin's query
+    /// pattern, so it should be sent to the plugin.
+    ///
+    /// Plugins without a pattern can handle every query. Empty queries
+    /// are skipped if the plugin doesn't handle them.
+    pub(crate) fn can_handle(&self, query: &str) -> bool {
+        if query.is_empty() && !self.plugin.handles_empty_query() {
+            return false;
+        }
+        self.plugin
+            .query_pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(query))
+    }
+
     /// Returns the path to the provided plugin's directory.
     ///
     /// This is in `<data folder>/covey/plugins/<plugin name>`, for example,
     /// `~/.local/share/covey/plugins/my-plugin-name`.
     pub fn data_directory_path(&self) -> PathBuf {
-        data_directory_path(self.id().as_str())
+        self.plugin.paths.data_directory(self.id().as_str())
     }
 
     pub fn binary_path(&self) -> PathBuf {
-        binary_path(self.id().as_str())
+        self.plugin.paths.binary(self.id().as_str())
     }
 
     pub fn manifest_path(&self) -> PathBuf {
-        manifest_path(self.id().as_str())
+        self.plugin.paths.manifest(self.id().as_str())
     }
 
     pub fn database_path(&self) -> PathBuf {
-        database_path(self.id().as_str())
+        self.plugin.paths.database(self.id().as_str())
     }
 
     pub fn manifest(&self) -> &PluginManifest {
         &self.plugin.manifest
     }
 
-    pub(crate) async fn query(&self, query: impl Into<String>) -> Result<List> {
-        Ok(List::from_proto(
-            self,
-            self.plugin
-                .get_and_init()
-                .await?
-                .call_query(query.into())
-                .await?,
-        ))
+    /// Name of the plugin to show to the user.
+    ///
+    /// This is provided by the plugin once it has been initialised,
+    /// falling back to the name in the manifest.
+    pub fn display_name(&self) -> &str {
+        self.metadata()
+            .and_then(|m| m.display_name.as_deref())
+            .unwrap_or(&self.manifest().name)
+    }
+
+    /// Icon provided by the plugin.
+    ///
+    /// This is [`None`] if the plugin has not been initialised yet or
+    /// does not provide an icon.
+    pub fn icon(&self) -> Option<Icon> {
+        self.metadata().and_then(|m| m.icon.clone())
+    }
+
+    /// Description provided by the plugin, falling back to the
+    /// description in the manifest.
+    pub fn description(&self) -> Option<&str> {
+        self.metadata()
+            .and_then(|m| m.description.as_deref())
+            .or(self.manifest().description.as_deref())
+    }
+
+    /// Author provided by the plugin, falling back to the authors
+    /// listed in the manifest.
+    pub fn author(&self) -> Option<String> {
+        self.metadata().and_then(|m| m.author.clone()).or_else(|| {
+            let authors = &self.manifest().authors;
+            (!authors.is_empty()).then(|| authors.join(", "))
+        })
+    }
+
+    /// Options that the user can configure, provided by the plugin,
+    /// falling back to the schema in the manifest.
+    pub fn settings_schema(&self) -> &KeyedList<PluginConfigSchema> {
+        self.metadata()
+            .and_then(|m| m.settings_schema.as_ref())
+            .unwrap_or(&self.manifest().schema)
+    }
+
+    fn metadata(&self) -> Option<&Metadata> {
+        self.plugin.metadata.get()
+    }
+
+    /// Text to show in the search bar when this plugin is active
+    /// but the query is empty.
+    ///
+    /// This is empty if the plugin has not been initialised yet, or
+    /// the plugin does not provide a placeholder.
+    pub fn placeholder(&self) -> &str {
+        self.plugin.placeholder.get().map_or("", String::as_str)
+    }
+
+    /// Maximum time that this plugin can take to respond to a request.
+    ///
+    /// In order of precedence, this is the first of:
+    /// 1. The plugin's timeout for this kind of request.
+    /// 2. The plugin's timeout.
+    /// 3. The global timeout.
+    /// 4. A default of 10 seconds.
+    pub fn timeout(&self, kind: RequestKind) -> Duration {
+        request_timeout(&self.plugin.config, &self.plugin.global, kind)
+    }
+
+    /// Time to wait for the query to stop changing before querying the
+    /// plugin. This is zero unless it is configured.
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.plugin.config.debounce_ms.unwrap_or(0).into())
+    }
+
+    /// Runs a request, failing if it takes longer than the timeout.
+    async fn with_timeout<T>(
+        &self,
+        kind: RequestKind,
+        request: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let timeout = self.timeout(kind);
+        tokio::time::timeout(timeout, request).await.map_err(|_| {
+            eyre!(
+                "plugin {:?} took longer than {}ms to respond to a {kind:?} request",
+                self.id(),
+                timeout.as_millis()
+            )
+        })?
+    }
+
+    /// Whether the plugin supports an optional capability.
+    ///
+    /// This will start the plugin if it has not been started yet.
+    pub async fn supports(&self, capability: Capability) -> Result<bool> {
+        Ok(self
+            .plugin
+            .get_and_init()
+            .await?
+            .capabilities
+            .contains(&capability))
+    }
+
+    /// Notifies the plugin that the menu was shown.
+    ///
+    /// Does nothing if the plugin hasn't been initialised.
+    pub(crate) async fn on_show(&self) -> Result<()> {
+        if let Some(plugin) = self.plugin.get_if_initialised() {
+            plugin.call_on_show().await?;
+        }
+        Ok(())
+    }
+
+    /// Notifies the plugin that the menu was hidden.
+    ///
+    /// Does nothing if the plugin hasn't been initialised.
+    pub(crate) async fn on_hide(&self) -> Result<()> {
+        if let Some(plugin) = self.plugin.get_if_initialised() {
+            plugin.call_on_hide().await?;
+        }
+        Ok(())
+    }
+
+    /// Queries the plugin, with the ID of the query that this is a part of.
+    ///
+    /// The primary selection should only be given to plugins that support
+    /// [`Capability::PrimarySelection`].
+    pub(crate) async fn query(
+        &self,
+        query: impl Into<String>,
+        request_id: u64,
+        primary_selection: Option<String>,
+    ) -> Result<QueryOutcome> {
+        let query = query.into();
+        let plugin = self.plugin.get_and_init().await?;
+        let response = self
+            .with_timeout(
+                RequestKind::Query,
+                plugin.call_query(query.clone(), request_id, primary_selection),
+            )
+            .await?;
+        Ok(QueryOutcome::from_proto(self, &query, response))
+    }
+
+    /// Gets the next page of results of a query, with a token from the
+    /// previous page.
+    pub(crate) async fn query_page(&self, query: String, token: String) -> Result<List> {
+        let plugin = self.plugin.get_and_init().await?;
+        let response = self
+            .with_timeout(
+                RequestKind::Query,
+                plugin.call_query_page(query.clone(), token),
+            )
+            .await?;
+        Ok(List::from_proto(self, &query, response))
+    }
+
+    /// Gets the queries that the plugin suggests for this query, with
+    /// the plugin's prefix added.
+    pub(crate) async fn suggestions(&self, query: String) -> Result<Vec<String>> {
+        let plugin = self.plugin.get_and_init().await?;
+        let suggestions = self
+            .with_timeout(RequestKind::Query, plugin.call_suggestions(query))
+            .await?;
+        Ok(suggestions
+            .into_iter()
+            .map(|suggestion| format!("{}{suggestion}", self.prefix()))
+            .collect())
+    }
+
+    /// Gets the full content of an item to show while it is selected.
+    ///
+    /// Returns [`None`] if the plugin doesn't preview the item.
+    pub(crate) async fn preview(&self, selection_id: u64) -> Result<Option<String>> {
+        let plugin = self.plugin.get_and_init().await?;
+        self.with_timeout(RequestKind::Query, plugin.call_preview(selection_id))
+            .await
+    }
+
+    /// Stores a new order of the plugin's pinned items.
+    pub(crate) async fn reorder_pins(&self, selection_ids: Vec<u64>) -> Result<()> {
+        let plugin = self.plugin.get_and_init().await?;
+        self.with_timeout(
+            RequestKind::Activate,
+            plugin.call_reorder_pins(selection_ids),
+        )
+        .await
     }
 
     pub(crate) async fn activate(
         &self,
         selection_id: u64,
         command_name: String,
-    ) -> Result<Vec<Action>> {
-        Ok(self.map_proto_actions(
-            self.plugin
-                .get_and_init()
-                .await?
-                .call_activate(selection_id, command_name)
-                .await?,
-        ))
+    ) -> Result<(Vec<Action>, ExecutionPolicy)> {
+        let plugin = self.plugin.get_and_init().await?;
+        if plugin.is_builtin() {
+            let command = BuiltinCommand::from_id(selection_id)?;
+            return Ok((vec![Action::Builtin(command)], ExecutionPolicy::Parallel));
+        }
+        let response = self
+            .with_timeout(
+                RequestKind::Activate,
+                plugin.call_activate(selection_id, command_name),
+            )
+            .await?;
+        let execution = ExecutionPolicy::from_proto(response.execution());
+        Ok((self.map_proto_actions(response.actions), execution))
+    }
+
+    /// Gives the values of a form shown with [`Action::ShowForm`] to the
+    /// plugin.
+    pub(crate) async fn submit_form(
+        &self,
+        id: String,
+        values: Vec<String>,
+    ) -> Result<(Vec<Action>, ExecutionPolicy)> {
+        let plugin = self.plugin.get_and_init().await?;
+        let response = self
+            .with_timeout(RequestKind::Activate, plugin.call_submit_form(id, values))
+            .await?;
+        let execution = ExecutionPolicy::from_proto(response.execution());
+        Ok((self.map_proto_actions(response.actions), execution))
     }
 
-    fn map_proto_actions(&self, actions: Vec<proto::Action>) -> Vec<Action> {
+    /// The path of a file in the plugin's data directory.
+    ///
+    /// # Errors
+    /// If the path is absolute, leaves the data directory or isn't a file.
+    fn data_file_path(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path);
+        let is_inside = !relative.is_absolute()
+            && relative.file_name().is_some()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_inside {
+            bail!("{path:?} is not a file in the plugin's data directory");
+        }
+        Ok(self.data_directory_path().join(relative))
+    }
+
+    pub(crate) fn map_proto_actions(&self, actions: Vec<proto::Action>) -> Vec<Action> {
         use proto::action::Action as PAction;
 
         actions
@@ -109,6 +539,41 @@ impl Plugin {
                     PAction::RunShell(str) => Action::RunShell(str),
                     PAction::Copy(str) => Action::Copy(str),
                     PAction::SetInput(input) => Action::SetInput(Input::from_proto(self, input)),
+                    PAction::OpenUrl(url) => Action::OpenUrl(url),
+                    PAction::TypeText(text) => Action::TypeText(text),
+                    PAction::PushContext(proto::Context { label, input }) => Action::PushContext {
+                        label,
+                        input: Input::from_proto(self, input),
+                    },
+                    PAction::UpdateItem(update) => Action::UpdateItem {
+                        key: update.key,
+                        item: ListItem::new(self.clone(), *update.item),
+                    },
+                    PAction::FocusEntry(proto::FocusEntry { select_all }) => {
+                        Action::FocusEntry { select_all }
+                    }
+                    PAction::OpenPreviewWindow(content) => Action::OpenPreviewWindow { content },
+                    PAction::ClearInput(()) => Action::ClearInput,
+                    PAction::CopyField(field) => Action::CopyField(field),
+                    PAction::Form(form) => Action::ShowForm {
+                        plugin: self.clone(),
+                        form: Form::from_proto(form),
+                    },
+                    PAction::WriteFile(proto::WriteFile {
+                        path,
+                        contents,
+                        append,
+                    }) => match self.data_file_path(&path) {
+                        Ok(path) => Action::WriteFile {
+                            path,
+                            contents,
+                            append,
+                        },
+                        Err(e) => {
+                            tracing::error!("plugin {self:?} wrote to an invalid path: {e:#}");
+                            return None;
+                        }
+                    },
                 })
             })
             .collect()
@@ -162,39 +627,181 @@ impl Keyed for Plugin {
 // Do not implement serde traits. Can be serialized as a string but it can't
 // be properly deserialized.
 
-fn data_directory_path(plugin_name: &str) -> PathBuf {
-    DATA_DIR.join("plugins").join(plugin_name)
+/// A kind of request that can be sent to a plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+    Query,
+    Activate,
+    /// Starting the plugin's process, until it prints its port.
+    Spawn,
+}
+
+/// Information that a plugin provides about itself.
+///
+/// Anything not provided falls back to the manifest.
+#[derive(Debug, Clone, Default)]
+struct Metadata {
+    display_name: Option<String>,
+    icon: Option<Icon>,
+    description: Option<String>,
+    author: Option<String>,
+    settings_schema: Option<KeyedList<PluginConfigSchema>>,
+}
+
+impl Metadata {
+    fn from_proto(proto: proto::MetadataResponse) -> Self {
+        use proto::metadata_response::Icon as Proto;
+
+        Self {
+            display_name: proto.display_name,
+            icon: proto.icon.and_then(|icon| match icon {
+                Proto::Name(name) => Some(Icon::Name(name)),
+                Proto::Text(text) => Some(Icon::Text(text)),
+                Proto::Bytes(bytes) => Icon::from_bytes(bytes),
+            }),
+            description: proto.description,
+            author: proto.author,
+            settings_schema: None,
+        }
+    }
+
+    /// Sets the schema from the plugin, unless it is empty.
+    fn set_settings_schema(&mut self, proto: proto::SettingsSchemaResponse) -> Result<()> {
+        if proto.fields.is_empty() {
+            return Ok(());
+        }
+        let fields = proto
+            .fields
+            .into_iter()
+            .map(|field| {
+                let r#type: serde_json::Value = serde_json::from_str(&field.type_json)
+                    .context(format!("invalid type of setting {:?}", field.id))?;
+                let schema = serde_json::json!({
+                    "id": field.id,
+                    "title": field.title,
+                    "description": field.description,
+                    "type": r#type,
+                });
+                serde_json::from_value(schema)
+                    .context(format!("invalid type of setting {:?}", field.id))
+            })
+            .collect::<Result<Vec<PluginConfigSchema>>>()?;
+        self.settings_schema = Some(KeyedList::new(fields)?);
+        Ok(())
+    }
 }
 
-fn binary_path(plugin_name: &str) -> PathBuf {
-    data_directory_path(plugin_name).join(plugin_name)
+/// An optional feature that a plugin declares in its handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Streaming,
+    Subscribe,
+    PrimarySelection,
+    Logging,
 }
 
-fn manifest_path(plugin_name: &str) -> PathBuf {
-    data_directory_path(plugin_name).join("manifest.toml")
+impl Capability {
+    fn from_proto(proto: proto::Capability) -> Self {
+        match proto {
+            proto::Capability::Streaming => Self::Streaming,
+            proto::Capability::Subscribe => Self::Subscribe,
+            proto::Capability::PrimarySelection => Self::PrimarySelection,
+            proto::Capability::Logging => Self::Logging,
+        }
+    }
+
+    /// Converts the capabilities from a handshake, ignoring any that
+    /// are unknown to this version of covey.
+    fn from_proto_list(capabilities: &[i32]) -> Vec<Self> {
+        capabilities
+            .iter()
+            .filter_map(|&c| proto::Capability::try_from(c).ok())
+            .map(Self::from_proto)
+            .collect()
+    }
 }
 
-fn database_path(plugin_name: &str) -> PathBuf {
-    data_directory_path(plugin_name).join("data.db")
+/// See [`Plugin::timeout`].
+fn request_timeout(config: &PluginConfig, global: &GlobalOptions, kind: RequestKind) -> Duration {
+    let request_timeout = match kind {
+        RequestKind::Query => config.timeouts.query_ms,
+        RequestKind::Activate => config.timeouts.activate_ms,
+        RequestKind::Spawn => {
+            return config
+                .timeouts
+                .spawn_ms
+                .map_or(DEFAULT_SPAWN_TIMEOUT, |ms| Duration::from_millis(ms.into()));
+        }
+    };
+
+    [request_timeout, config.timeout_ms, global.timeout_ms]
+        .into_iter()
+        .flatten()
+        .next()
+        .map_or(DEFAULT_TIMEOUT, |ms| Duration::from_millis(ms.into()))
+}
+
+/// The name that plugins parse a frecency strategy from.
+fn frecency_name(strategy: FrecencyStrategy) -> &'static str {
+    match strategy {
+        FrecencyStrategy::Classic => "classic",
+        FrecencyStrategy::ExponentialDecay => "exponential-decay",
+        FrecencyStrategy::Frequency => "frequency",
+        FrecencyStrategy::Recency => "recency",
+    }
+}
+
+/// The name that plugins parse a fuzzy matching algorithm from.
+fn algorithm_name(algorithm: MatchAlgorithm) -> &'static str {
+    match algorithm {
+        MatchAlgorithm::Skim => "skim",
+        MatchAlgorithm::Subsequence => "subsequence",
+        MatchAlgorithm::JaroWinkler => "jaro-winkler",
+    }
+}
+
+fn check_protocol_version(plugin_version: u32) -> Result<()> {
+    if plugin_version != PROTOCOL_VERSION {
+        bail!(
+            "plugin uses protocol version {plugin_version} but covey uses version \
+            {PROTOCOL_VERSION}: the plugin and covey must be updated to compatible versions"
+        );
+    }
+    Ok(())
+}
+
+/// Finds the binary to run for a plugin: either its configured command or
+/// the binary in its data directory.
+fn resolve_binary(config: &PluginConfig, paths: &PluginPaths) -> Result<PathBuf> {
+    match &config.command {
+        Some(command) => {
+            crate::spawn::find_program(command, paths.search_path.as_deref()).context(format!(
+                "command `{command}` of plugin {} could not be found",
+                config.id.as_str()
+            ))
+        }
+        None => Ok(paths.binary(config.id.as_str())),
+    }
 }
 
 /// Gets the connection URL for a given plugin.
 ///
 /// The database file will be created first.
-async fn sqlite_connection_url(plugin_name: &str) -> Result<String> {
+async fn sqlite_connection_url(paths: &PluginPaths, plugin_name: &str) -> Result<String> {
     // make the file
-    fs::create_dir_all(data_directory_path(plugin_name)).await?;
+    fs::create_dir_all(paths.data_directory(plugin_name)).await?;
     fs::OpenOptions::new()
         .create(true)
         .write(true)
         .truncate(false)
-        .open(database_path(plugin_name))
+        .open(paths.database(plugin_name))
         .await?;
 
     // https://docs.rs/sqlx/latest/sqlx/sqlite/struct.SqliteConnectOptions.html
     let connection_string = format!(
         "sqlite://{}",
-        database_path(plugin_name)
+        paths
+            .database(plugin_name)
             .to_str()
             .context("plugin data path must be a UTF-8 string")?
     );
@@ -203,22 +810,41 @@ async fn sqlite_connection_url(plugin_name: &str) -> Result<String> {
 }
 
 mod implementation {
-    use std::{path::PathBuf, process::Stdio};
+    use std::{
+        num::NonZeroU16,
+        path::PathBuf,
+        process::{ExitStatus, Stdio},
+        sync::OnceLock,
+        time::Duration,
+    };
 
-    use color_eyre::eyre::{Context as _, Result};
-    use covey_config::{config::PluginConfig, manifest::PluginManifest};
+    use az::SaturatingAs as _;
+    use color_eyre::eyre::{bail, eyre, Context as _, Report, Result};
+    use covey_config::{
+        config::{PluginConfig, ResourceLimits},
+        manifest::PluginManifest,
+    };
+    use regex::Regex;
     use tokio::{
         io::{AsyncBufReadExt as _, BufReader},
-        process::Command,
+        process::{Child, Command},
         sync::{Mutex, OnceCell},
     };
     use tonic::{transport::Channel, Request};
-    use tracing::info;
+    use tracing::{debug, info, warn};
 
     use super::{
-        binary_path, manifest_path,
+        algorithm_name, check_protocol_version, frecency_name,
         proto::{self, plugin_client::PluginClient},
-        sqlite_connection_url,
+        request_timeout, resolve_binary, sqlite_connection_url, Capability, GlobalOptions,
+        Metadata, PluginPaths, RequestKind, MAX_MESSAGE_BYTES_VAR, PROTOCOL_VERSION,
+        WORKER_THREADS_VAR,
+    };
+    use crate::{
+        builtin,
+        script::Script,
+        secret::{self, Keyring},
+        spawn,
     };
 
     /// A plugin that is not initialised until [`Self::get_and_init`] is called.
@@ -228,25 +854,101 @@ mod implementation {
         cell: OnceCell<PluginInner>,
         // making the manifest sync makes it easier to use in settings
         called_initialise: Mutex<bool>,
+        /// Set once the plugin has been initialised.
+        pub(super) placeholder: OnceLock<String>,
+        /// Set once the plugin has been initialised.
+        pub(super) metadata: OnceLock<Metadata>,
         pub(super) manifest: PluginManifest,
         pub(super) config: PluginConfig,
+        pub(super) global: GlobalOptions,
+        pub(super) paths: PluginPaths,
+        /// From the config, or else the manifest.
+        pub(super) query_pattern: Option<Regex>,
     }
 
     impl LazyPlugin {
-        pub(super) fn new(config: PluginConfig) -> Result<Self> {
+        pub(super) fn new(
+            config: PluginConfig,
+            global: GlobalOptions,
+            paths: PluginPaths,
+        ) -> Result<Self> {
             let id = &config.id;
-            let path = manifest_path(id.as_str());
-            let toml = std::fs::read_to_string(path)
-                .context(format!("error opening manifest file of {}", id.as_str()))?;
-            let manifest: PluginManifest = toml::from_str(&toml)
-                .context(format!("error reading manifest of {}", id.as_str()))?;
+            // check that the command exists now, rather than when the
+            // plugin is first queried
+            resolve_binary(&config, &paths)?;
+
+            let path = paths.manifest(id.as_str());
+            let manifest = if config.command.is_some() && !path.exists() {
+                // plugins installed as a command may not have a manifest
+                PluginManifest::new(id.as_str())
+            } else {
+                let toml = std::fs::read_to_string(path)
+                    .context(format!("error opening manifest file of {}", id.as_str()))?;
+                toml::from_str(&toml)
+                    .context(format!("error reading manifest of {}", id.as_str()))?
+            };
 
             Ok(Self {
+                paths,
+                ..Self::with_manifest(config, manifest, global)
+            })
+        }
+
+        pub(super) fn with_manifest(
+            config: PluginConfig,
+            manifest: PluginManifest,
+            global: GlobalOptions,
+        ) -> Self {
+            let query_pattern = config
+                .query_pattern
+                .as_ref()
+                .or(manifest.query_pattern.as_ref())
+                .and_then(|pattern| match Regex::new(pattern) {
+                    Ok(regex) => Some(regex),
+                    Err(e) => {
+                        warn!(
+                            "ignoring invalid query pattern of {}: {e}",
+                            config.id.as_str()
+                        );
+                        None
+                    }
+                });
+            Self {
                 cell: OnceCell::new(),
                 called_initialise: Mutex::new(false),
+                placeholder: OnceLock::new(),
+                metadata: OnceLock::new(),
                 manifest,
                 config,
-            })
+                global,
+                paths: PluginPaths::default(),
+                query_pattern,
+            }
+        }
+
+        /// From the config, or else the manifest.
+        pub(super) fn tracks_activations(&self) -> bool {
+            self.config
+                .track_activations
+                .or(self.manifest.track_activations)
+                .unwrap_or(true)
+        }
+
+        /// From the config, or else the manifest.
+        pub(super) fn handles_empty_query(&self) -> bool {
+            self.config
+                .handles_empty_query
+                .or(self.manifest.handles_empty_query)
+                .unwrap_or(true)
+        }
+
+        /// The plugin that lists covey's own commands, which doesn't
+        /// need to be started.
+        pub(super) fn builtin(config: PluginConfig, manifest: PluginManifest) -> Self {
+            Self {
+                cell: OnceCell::new_with(Some(PluginInner::builtin())),
+                ..Self::with_manifest(config, manifest, GlobalOptions::default())
+            }
         }
 
         /// Gets access to a plugin and ensures it is initialised.
@@ -260,37 +962,154 @@ mod implementation {
             // otherwise, blocks any other accesses until initialisation
             // either succeeds or fails.
             let mut initialise_guard = self.called_initialise.lock().await;
+            let Connection::Server(client) = &inner.connection else {
+                // scripts and built-in commands don't have anything
+                // to initialise
+                if !*initialise_guard {
+                    _ = self.placeholder.set(String::new());
+                    _ = self.metadata.set(Metadata::default());
+                    *initialise_guard = true;
+                }
+                return Ok(inner);
+            };
             if !*initialise_guard {
-                let db_url = sqlite_connection_url(self.config.id.as_str()).await?;
-                let config_json = serde_json::to_string(&self.config.config)?;
+                let db_url = sqlite_connection_url(&self.paths, self.config.id.as_str()).await?;
+                let config = self.config.config.clone();
+                // the keyring may block, so don't run it on the async runtime
+                let config =
+                    tokio::task::spawn_blocking(move || secret::resolve(&config, &Keyring))
+                        .await?
+                        .context("failed to read secrets in plugin config")?;
+                let config_json = serde_json::to_string(&config)?;
 
-                inner
-                    .plugin
+                let response = client
                     .clone()
                     .initialise(Request::new(proto::InitialiseRequest {
                         json: config_json,
                         sqlite_url: db_url,
+                        log_queries: Some(self.global.log_queries),
+                        min_score: self.global.min_score,
+                        show_recent: Some(self.global.show_recent),
+                        max_history_entries: self.global.max_history_entries,
+                        track_activations: Some(self.tracks_activations()),
+                        match_scoring: Some(proto::MatchScoring {
+                            consecutive_bonus: self.global.match_scoring.consecutive_bonus,
+                            word_start_bonus: self.global.match_scoring.word_start_bonus,
+                            gap_penalty: self.global.match_scoring.gap_penalty,
+                        }),
+                        clipboard_history: Some(self.global.clipboard_history),
+                        frecency: Some(frecency_name(self.global.frecency).to_owned()),
+                        match_algorithm: Some(
+                            algorithm_name(self.global.match_algorithm).to_owned(),
+                        ),
                     }))
                     .await
-                    .context("plugin initialisation function failed")?;
+                    .context("plugin initialisation function failed")?
+                    .into_inner();
+                _ = self
+                    .placeholder
+                    .set(response.placeholder.unwrap_or_default());
+
+                // metadata is only cosmetic, so don't fail if it's missing
+                let mut metadata = match client.clone().metadata(Request::new(())).await {
+                    Ok(response) => Metadata::from_proto(response.into_inner()),
+                    Err(status) => {
+                        warn!(
+                            "failed to get metadata of plugin {:?}: {status}",
+                            self.config.id
+                        );
+                        Metadata::default()
+                    }
+                };
+                let schema = client
+                    .clone()
+                    .settings_schema(Request::new(()))
+                    .await
+                    .map_err(Report::from)
+                    .and_then(|response| metadata.set_settings_schema(response.into_inner()));
+                if let Err(e) = schema {
+                    warn!(
+                        "failed to get settings schema of plugin {:?}: {e:#}",
+                        self.config.id
+                    );
+                }
+                if inner.capabilities.contains(&Capability::Logging) {
+                    tokio::spawn(forward_logs(
+                        client.clone(),
+                        self.config.id.as_str().to_owned(),
+                    ));
+                }
+                _ = self.metadata.set(metadata);
                 *initialise_guard = true;
             }
 
             Ok(inner)
         }
 
+        /// Gets access to the plugin only if it has already been initialised.
+        pub(super) fn get_if_initialised(&self) -> Option<&PluginInner> {
+            // the metadata is set at the end of initialisation
+            self.metadata.get()?;
+            self.cell.get()
+        }
+
         async fn get_without_init(&self) -> Result<&PluginInner> {
             self.cell
                 .get_or_try_init(|| async {
                     info!("initialising plugin {:?}", self.config.id);
-                    let bin_path = binary_path(self.config.id.as_str());
-                    PluginInner::new(bin_path).await
+                    let bin_path = resolve_binary(&self.config, &self.paths)?;
+                    if self.config.script {
+                        return Ok(PluginInner::script(bin_path));
+                    }
+                    let spawn_timeout =
+                        request_timeout(&self.config, &self.global, RequestKind::Spawn);
+                    PluginInner::new(
+                        bin_path,
+                        self.global.max_message_bytes,
+                        self.config.worker_threads,
+                        spawn_timeout,
+                        &self.config.limits,
+                    )
+                    .await
                 })
                 .await
                 .context(format!("failed to initialise plugin {:?}", self.config.id))
         }
     }
 
+    /// Writes the messages that the plugin logs to covey's logs, until
+    /// the plugin exits.
+    async fn forward_logs(mut client: PluginClient<Channel>, id: String) {
+        let mut logs = match client.logs(Request::new(())).await {
+            Ok(response) => response.into_inner(),
+            Err(status) => {
+                warn!("failed to read logs of plugin {id:?}: {status}");
+                return;
+            }
+        };
+        loop {
+            match logs.message().await {
+                Ok(Some(message)) => log_plugin_message(&id, &message),
+                Ok(None) => return,
+                Err(status) => {
+                    debug!("stopped reading logs of plugin {id:?}: {status}");
+                    return;
+                }
+            }
+        }
+    }
+
+    fn log_plugin_message(plugin: &str, message: &proto::LogMessage) {
+        let text = &message.message;
+        match message.level() {
+            proto::LogLevel::Trace => tracing::trace!(plugin, "{text}"),
+            proto::LogLevel::Debug => tracing::debug!(plugin, "{text}"),
+            proto::LogLevel::Info => tracing::info!(plugin, "{text}"),
+            proto::LogLevel::Warn => tracing::warn!(plugin, "{text}"),
+            proto::LogLevel::Error => tracing::error!(plugin, "{text}"),
+        }
+    }
+
     /// Internals of a plugin.
     ///
     /// Simple wrapper that handles some request-response conversions.
@@ -298,15 +1117,71 @@ mod implementation {
     /// This should only be returned to the [`super::Plugin`] in an
     /// initialised state.
     pub(super) struct PluginInner {
-        plugin: PluginClient<Channel>,
+        connection: Connection,
+        /// Only exists for plugin servers.
+        process: Option<parking_lot::Mutex<Child>>,
+        pub(super) capabilities: Vec<Capability>,
+        max_message_bytes: u32,
+    }
+
+    /// How requests are sent to a plugin.
+    enum Connection {
+        Server(PluginClient<Channel>),
+        Script(Script),
+        /// Covey's own commands, see [`crate::builtin`].
+        Builtin,
     }
 
     impl PluginInner {
+        /// A plugin that runs a script for every request.
+        fn script(path: PathBuf) -> Self {
+            Self {
+                connection: Connection::Script(Script::new(path)),
+                process: None,
+                capabilities: vec![],
+                max_message_bytes: u32::MAX,
+            }
+        }
+
+        /// The plugin that lists covey's own commands.
+        fn builtin() -> Self {
+            Self {
+                connection: Connection::Builtin,
+                process: None,
+                capabilities: vec![],
+                max_message_bytes: u32::MAX,
+            }
+        }
+
+        /// Whether this lists covey's own commands, which are run by
+        /// covey instead of returning actions.
+        pub(super) fn is_builtin(&self) -> bool {
+            matches!(self.connection, Connection::Builtin)
+        }
+
         /// Starts the plugin binary but does not call initialise.
-        async fn new(bin_path: PathBuf) -> Result<Self> {
+        ///
+        /// The process is killed if it doesn't print its port within
+        /// `spawn_timeout`, or if it can't be used for any other reason.
+        async fn new(
+            bin_path: PathBuf,
+            max_message_bytes: u32,
+            worker_threads: Option<NonZeroU16>,
+            spawn_timeout: Duration,
+            limits: &ResourceLimits,
+        ) -> Result<Self> {
             // run process and read first line
-            let mut process = Command::new(bin_path)
+            let mut command = Command::new(bin_path);
+            command.env(MAX_MESSAGE_BYTES_VAR, max_message_bytes.to_string());
+            if let Some(threads) = worker_threads {
+                command.env(WORKER_THREADS_VAR, threads.to_string());
+            }
+            spawn::limit_resources(&mut command, limits);
+            // the plugin is unusable if this returns an error, so don't
+            // leave it running
+            let mut process = command
                 .stdout(Stdio::piped())
+                .kill_on_drop(true)
                 .spawn()
                 .context("failed to spawn plugin server")?;
 
@@ -314,47 +1189,970 @@ mod implementation {
             let mut stdout = BufReader::new(stdout);
 
             let mut first_line = String::new();
-            stdout.read_line(&mut first_line).await.context(
+            let read = tokio::time::timeout(spawn_timeout, stdout.read_line(&mut first_line)).await;
+            let Ok(read) = read else {
+                bail!(
+                    "plugin did not print its port within {}ms",
+                    spawn_timeout.as_millis()
+                );
+            };
+            let read = read.context(
                 "failed to read port or error from plugin: plugin should print to stdout",
             )?;
+            if read == 0 {
+                let status = process.wait().await?;
+                let hint = if *limits == ResourceLimits::default() {
+                    ""
+                } else {
+                    ": it may have gone over its resource limits"
+                };
+                bail!("plugin exited with {status} before printing its port{hint}");
+            }
 
             let port: u16 = first_line
                 .trim()
                 .parse()
                 .context("plugin should print it's connected port number to stdout")?;
 
-            let client = PluginClient::connect(format!("http://[::1]:{port}"))
+            let mut client = PluginClient::connect(format!("http://[::1]:{port}"))
+                .await
+                .context(format!("failed to connect to plugin server on port {port}"))?
+                // requests are small, so only responses need to be limited
+                .max_decoding_message_size(max_message_bytes.saturating_as());
+
+            let handshake = client
+                .handshake(Request::new(proto::HandshakeRequest {
+                    protocol_version: PROTOCOL_VERSION,
+                }))
                 .await
-                .context(format!("failed to connect to plugin server on port {port}"))?;
+                .map_err(|status| {
+                    if status.code() == tonic::Code::Unimplemented {
+                        eyre!(
+                            "plugin does not support the handshake: \
+                            it was likely built for an older version of covey"
+                        )
+                    } else {
+                        eyre!(status)
+                    }
+                })
+                .context("plugin handshake failed")?
+                .into_inner();
+            check_protocol_version(handshake.protocol_version)?;
 
             info!("finished initialising plugin binary");
-            Ok(Self { plugin: client })
+            Ok(Self {
+                connection: Connection::Server(client),
+                process: Some(parking_lot::Mutex::new(process)),
+                capabilities: Capability::from_proto_list(&handshake.capabilities),
+                max_message_bytes,
+            })
+        }
+
+        pub(super) async fn call_query(
+            &self,
+            query: String,
+            request_id: u64,
+            primary_selection: Option<String>,
+        ) -> Result<proto::QueryResponse> {
+            let client = match &self.connection {
+                Connection::Server(client) => client,
+                Connection::Script(script) => return script.query(query).await,
+                Connection::Builtin => return Ok(builtin::query(&query)),
+            };
+            Ok(client
+                .clone()
+                .query(Request::new(proto::QueryRequest {
+                    query,
+                    request_id: Some(request_id),
+                    primary_selection,
+                }))
+                .await
+                .map_err(|status| self.query_error(status))?
+                .into_inner())
+        }
+
+        pub(super) async fn call_query_page(
+            &self,
+            query: String,
+            token: String,
+        ) -> Result<proto::QueryResponse> {
+            let Connection::Server(client) = &self.connection else {
+                // scripts and built-in commands return all of their
+                // results at once
+                return Ok(proto::QueryResponse::default());
+            };
+            if !self.capabilities.contains(&Capability::Streaming) {
+                return Ok(proto::QueryResponse::default());
+            }
+            Ok(client
+                .clone()
+                .query_page(Request::new(proto::QueryPageRequest { query, token }))
+                .await
+                .map_err(|status| self.query_error(status))?
+                .into_inner())
+        }
+
+        fn query_error(&self, status: tonic::Status) -> Report {
+            if let Some(exit) = self.exit_status() {
+                return eyre!("plugin exited with {exit}");
+            }
+            // the plugin or covey can fail to encode or decode the
+            // response, depending on which limit is smaller
+            if status.code() == tonic::Code::OutOfRange
+                && status.message().contains("message length too large")
+            {
+                eyre!(
+                    "plugin returned too many results: the response is larger \
+                    than the limit of {} bytes",
+                    self.max_message_bytes
+                )
+            } else {
+                eyre!(status)
+            }
+        }
+
+        /// How the plugin's process failed, if it is no longer running.
+        ///
+        /// Successful exits are ignored, since the process may have only
+        /// started a server that is still running.
+        fn exit_status(&self) -> Option<ExitStatus> {
+            let status = self.process.as_ref()?.lock().try_wait().ok()??;
+            (!status.success()).then_some(status)
         }
 
-        pub(super) async fn call_query(&self, query: String) -> Result<proto::QueryResponse> {
-            Ok(self
-                .plugin
+        pub(super) async fn call_suggestions(&self, query: String) -> Result<Vec<String>> {
+            let Connection::Server(client) = &self.connection else {
+                return Ok(vec![]);
+            };
+            match client
                 .clone()
-                .query(Request::new(proto::QueryRequest { query }))
+                .suggestions(Request::new(proto::QueryRequest {
+                    query,
+                    request_id: None,
+                    primary_selection: None,
+                }))
+                .await
+            {
+                Ok(response) => Ok(response.into_inner().suggestions),
+                // plugins built before suggestions existed
+                Err(status) if status.code() == tonic::Code::Unimplemented => Ok(vec![]),
+                Err(status) => Err(status.into()),
+            }
+        }
+
+        pub(super) async fn call_preview(&self, selection_id: u64) -> Result<Option<String>> {
+            let Connection::Server(client) = &self.connection else {
+                return Ok(None);
+            };
+            match client
+                .clone()
+                .preview(Request::new(proto::PreviewRequest { selection_id }))
+                .await
+            {
+                Ok(response) => Ok(response.into_inner().text),
+                // plugins built before previews existed
+                Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+                Err(status) => Err(status.into()),
+            }
+        }
+
+        pub(super) async fn call_submit_form(
+            &self,
+            id: String,
+            values: Vec<String>,
+        ) -> Result<proto::ActivationResponse> {
+            let Connection::Server(client) = &self.connection else {
+                bail!("only plugin servers can show forms");
+            };
+            Ok(client
+                .clone()
+                .submit_form(Request::new(proto::SubmitFormRequest { id, values }))
                 .await?
                 .into_inner())
         }
 
+        pub(super) async fn call_reorder_pins(&self, selection_ids: Vec<u64>) -> Result<()> {
+            let Connection::Server(client) = &self.connection else {
+                bail!("only plugin servers can pin items");
+            };
+            client
+                .clone()
+                .reorder_pins(Request::new(proto::ReorderPinsRequest { selection_ids }))
+                .await?;
+            Ok(())
+        }
+
+        pub(super) async fn call_on_show(&self) -> Result<()> {
+            let Connection::Server(client) = &self.connection else {
+                return Ok(());
+            };
+            if self.capabilities.contains(&Capability::Subscribe) {
+                client.clone().on_show(Request::new(())).await?;
+            }
+            Ok(())
+        }
+
+        pub(super) async fn call_on_hide(&self) -> Result<()> {
+            let Connection::Server(client) = &self.connection else {
+                return Ok(());
+            };
+            if self.capabilities.contains(&Capability::Subscribe) {
+                client.clone().on_hide(Request::new(())).await?;
+            }
+            Ok(())
+        }
+
         pub(super) async fn call_activate(
             &self,
             selection_id: u64,
             command_name: String,
-        ) -> Result<Vec<proto::Action>> {
-            Ok(self
-                .plugin
+        ) -> Result<proto::ActivationResponse> {
+            let client = match &self.connection {
+                Connection::Server(client) => client,
+                Connection::Script(script) => {
+                    let actions = script.activate(selection_id, &command_name).await?;
+                    return Ok(proto::ActivationResponse {
+                        actions,
+                        execution: None,
+                    });
+                }
+                Connection::Builtin => bail!("built-in commands are run by the host"),
+            };
+            Ok(client
                 .clone()
                 .activate(Request::new(proto::ActivationRequest {
                     selection_id,
                     command_name,
                 }))
                 .await?
-                .into_inner()
-                .actions)
+                .into_inner())
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    };
+
+    use covey_config::config::{GlobalConfig, PluginConfig};
+    use tonic::{
+        transport::{server::TcpIncoming, Server},
+        Request, Response, Status,
+    };
+
+    use super::{
+        check_protocol_version, implementation::LazyPlugin, proto, Capability, GlobalOptions,
+        Metadata, Plugin, PluginPaths, RequestKind, DEFAULT_SPAWN_TIMEOUT, DEFAULT_TIMEOUT,
+        PROTOCOL_VERSION,
+    };
+    use crate::event::{Action, Icon, QueryOutcome};
+
+    #[test]
+    fn incompatible_protocol_version() {
+        assert!(check_protocol_version(PROTOCOL_VERSION).is_ok());
+
+        let err = check_protocol_version(PROTOCOL_VERSION + 1).unwrap_err();
+        assert!(err.to_string().contains("protocol version"));
+    }
+
+    #[test]
+    fn unknown_capabilities_are_ignored() {
+        let capabilities =
+            Capability::from_proto_list(&[proto::Capability::Subscribe.into(), 1000]);
+        assert_eq!(capabilities, [Capability::Subscribe]);
+        assert!(!capabilities.contains(&Capability::Streaming));
+    }
+
+    #[test]
+    fn metadata_overrides_manifest() {
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"pkgs\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"pkgs\"\ndescription = \"Packages\"\nauthors = [\"a\", \"b\"]")
+                .unwrap(),
+        );
+        assert_eq!(plugin.display_name(), "pkgs");
+        assert_eq!(plugin.description(), Some("Packages"));
+        assert_eq!(plugin.author().as_deref(), Some("a, b"));
+        assert!(plugin.icon().is_none());
+
+        _ = plugin
+            .plugin
+            .metadata
+            .set(Metadata::from_proto(proto::MetadataResponse {
+                display_name: Some("Package Search".to_owned()),
+                icon: Some(proto::metadata_response::Icon::Name("package".to_owned())),
+                description: None,
+                author: Some("c".to_owned()),
+            }));
+        assert_eq!(plugin.display_name(), "Package Search");
+        assert_eq!(plugin.description(), Some("Packages"));
+        assert_eq!(plugin.author().as_deref(), Some("c"));
+        assert!(matches!(plugin.icon(), Some(Icon::Name(name)) if name == "package"));
+    }
+
+    #[test]
+    fn files_are_written_in_data_directory() {
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"notes\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Notes\"").unwrap(),
+        );
+        let write = |path: &str| proto::Action {
+            action: Some(proto::action::Action::WriteFile(proto::WriteFile {
+                path: path.to_owned(),
+                contents: String::new(),
+                append: false,
+            })),
+        };
+
+        let actions = plugin.map_proto_actions(vec![write("notes/today.md"), write("./a.md")]);
+        let paths: Vec<_> = actions
+            .into_iter()
+            .map(|action| match action {
+                Action::WriteFile { path, .. } => path,
+                action => panic!("unexpected action {action:?}"),
+            })
+            .collect();
+        let dir = plugin.data_directory_path();
+        assert_eq!(paths, [dir.join("notes/today.md"), dir.join("./a.md")]);
+    }
+
+    #[test]
+    fn files_outside_data_directory_are_not_written() {
+        let plugin = Plugin::with_manifest(
+            toml::from_str("id = \"notes\"\nprefix = \"\"").unwrap(),
+            toml::from_str("name = \"Notes\"").unwrap(),
+        );
+        for path in ["/etc/passwd", "../other/data.db", "notes/../../x", ""] {
+            let action = proto::Action {
+                action: Some(proto::action::Action::WriteFile(proto::WriteFile {
+                    path: path.to_owned(),
+                    contents: String::new(),
+                    append: false,
+                })),
+            };
+            assert!(
+                plugin.map_proto_actions(vec![action]).is_empty(),
+                "{path:?} was allowed"
+            );
+        }
+    }
+
+    fn plugin_with_timeouts(plugin: &str, global_timeout_ms: Option<u32>) -> Plugin {
+        Plugin {
+            plugin: Arc::new(LazyPlugin::with_manifest(
+                toml::from_str(&format!("id = \"pkgs\"\nprefix = \"\"\n{plugin}")).unwrap(),
+                toml::from_str("name = \"pkgs\"").unwrap(),
+                GlobalOptions {
+                    timeout_ms: global_timeout_ms,
+                    ..GlobalOptions::default()
+                },
+            )),
+        }
+    }
+
+    #[test]
+    fn timeout_precedence() {
+        let query = RequestKind::Query;
+        let activate = RequestKind::Activate;
+
+        let plugin = plugin_with_timeouts("", None);
+        assert_eq!(plugin.timeout(query), DEFAULT_TIMEOUT);
+
+        let plugin = plugin_with_timeouts("", Some(300));
+        assert_eq!(plugin.timeout(query), Duration::from_millis(300));
+
+        let plugin = plugin_with_timeouts("timeout_ms = 200", Some(300));
+        assert_eq!(plugin.timeout(query), Duration::from_millis(200));
+
+        let plugin = plugin_with_timeouts("timeout_ms = 200\ntimeouts.query_ms = 100", Some(300));
+        assert_eq!(plugin.timeout(query), Duration::from_millis(100));
+        assert_eq!(plugin.timeout(activate), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn spawn_timeout_ignores_request_timeouts() {
+        let spawn = RequestKind::Spawn;
+
+        let plugin = plugin_with_timeouts("", None);
+        assert_eq!(plugin.timeout(spawn), DEFAULT_SPAWN_TIMEOUT);
+
+        let plugin = plugin_with_timeouts(
+            "timeout_ms = 200
+timeouts.query_ms = 100",
+            Some(300),
+        );
+        assert_eq!(plugin.timeout(spawn), DEFAULT_SPAWN_TIMEOUT);
+
+        let plugin = plugin_with_timeouts(
+            "timeout_ms = 200
+timeouts.spawn_ms = 50",
+            Some(300),
+        );
+        assert_eq!(plugin.timeout(spawn), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn track_activations_precedence() {
+        let plugin = |config: &str, manifest: &str| Plugin {
+            plugin: Arc::new(LazyPlugin::with_manifest(
+                toml::from_str(&format!("id = \"pass\"\nprefix = \"\"\n{config}")).unwrap(),
+                toml::from_str(&format!("name = \"pass\"\n{manifest}")).unwrap(),
+                GlobalOptions::default(),
+            )),
+        };
+
+        assert!(plugin("", "").tracks_activations());
+        assert!(!plugin("", "track-activations = false").tracks_activations());
+        assert!(
+            plugin("track_activations = true", "track-activations = false").tracks_activations()
+        );
+        assert!(!plugin("track_activations = false", "").tracks_activations());
+    }
+
+    /// A plugin server that answers every query with a single item, and
+    /// another with the primary selection if there is one.
+    ///
+    /// The names of the requests that need a capability are recorded.
+    #[derive(Clone, Default)]
+    struct EchoPlugin {
+        /// Capabilities that are left out of the handshake.
+        missing_capabilities: Vec<proto::Capability>,
+        requests: Arc<parking_lot::Mutex<Vec<&'static str>>>,
+    }
+
+    #[tonic::async_trait]
+    impl proto::plugin_server::Plugin for EchoPlugin {
+        async fn handshake(
+            &self,
+            _: Request<proto::HandshakeRequest>,
+        ) -> Result<Response<proto::HandshakeResponse>, Status> {
+            Ok(Response::new(proto::HandshakeResponse {
+                protocol_version: PROTOCOL_VERSION,
+                capabilities: [
+                    proto::Capability::Streaming,
+                    proto::Capability::Subscribe,
+                    proto::Capability::PrimarySelection,
+                    proto::Capability::Logging,
+                ]
+                .into_iter()
+                .filter(|capability| !self.missing_capabilities.contains(capability))
+                .map(Into::into)
+                .collect(),
+            }))
+        }
+
+        async fn initialise(
+            &self,
+            _: Request<proto::InitialiseRequest>,
+        ) -> Result<Response<proto::InitialiseResponse>, Status> {
+            Ok(Response::new(proto::InitialiseResponse {
+                placeholder: Some("Echo something".to_owned()),
+            }))
         }
+
+        async fn metadata(
+            &self,
+            _: Request<()>,
+        ) -> Result<Response<proto::MetadataResponse>, Status> {
+            Ok(Response::new(proto::MetadataResponse::default()))
+        }
+
+        async fn settings_schema(
+            &self,
+            _: Request<()>,
+        ) -> Result<Response<proto::SettingsSchemaResponse>, Status> {
+            Ok(Response::new(proto::SettingsSchemaResponse {
+                fields: vec![
+                    proto::SettingsField {
+                        id: "greeting".to_owned(),
+                        title: "Greeting".to_owned(),
+                        description: Some("Said before every result".to_owned()),
+                        type_json: r#"{"text":{"default":"hello"}}"#.to_owned(),
+                    },
+                    proto::SettingsField {
+                        id: "repeat".to_owned(),
+                        title: "Repeat".to_owned(),
+                        description: None,
+                        type_json: r#""int""#.to_owned(),
+                    },
+                ],
+            }))
+        }
+
+        async fn query(
+            &self,
+            request: Request<proto::QueryRequest>,
+        ) -> Result<Response<proto::QueryResponse>, Status> {
+            let request = request.into_inner();
+            let mut items = vec![proto::ListItem {
+                title: request.query,
+                description: request
+                    .request_id
+                    .map_or_else(String::new, |id| id.to_string()),
+                available_commands: vec![
+                    "activate".to_owned(),
+                    "alt-activate".to_owned(),
+                    "complete".to_owned(),
+                ],
+                ..Default::default()
+            }];
+            // the selection is echoed as a second item
+            items.extend(
+                request
+                    .primary_selection
+                    .filter(|selection| !selection.is_empty())
+                    .map(|selection| proto::ListItem {
+                        title: selection,
+                        ..Default::default()
+                    }),
+            );
+            // the results of "tick" expire so that they are refreshed
+            let refresh_ms = (items[0].title == "tick").then_some(50);
+            Ok(Response::new(proto::QueryResponse {
+                items,
+                refresh_ms,
+                ..Default::default()
+            }))
+        }
+
+        /// Sets the input to the command name.
+        ///
+        /// A `slow-copy` command is copied after a delay, and the rest of
+        /// a command starting with `sh:` is run in the shell.
+        async fn activate(
+            &self,
+            request: Request<proto::ActivationRequest>,
+        ) -> Result<Response<proto::ActivationResponse>, Status> {
+            let command_name = request.into_inner().command_name;
+            let action = if command_name == "slow-copy" {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                proto::action::Action::Copy(command_name)
+            } else if let Some(shell) = command_name.strip_prefix("sh:") {
+                proto::action::Action::RunShell(shell.to_owned())
+            } else {
+                proto::action::Action::SetInput(proto::Input {
+                    range_lb: 0,
+                    range_ub: 0,
+                    query: command_name,
+                    select_item: None,
+                })
+            };
+            Ok(Response::new(proto::ActivationResponse {
+                actions: vec![proto::Action {
+                    action: Some(action),
+                }],
+                execution: None,
+            }))
+        }
+
+        async fn suggestions(
+            &self,
+            _: Request<proto::QueryRequest>,
+        ) -> Result<Response<proto::SuggestionsResponse>, Status> {
+            Err(Status::unimplemented("suggestions"))
+        }
+
+        /// Echoes the query and token, with one more page after the first.
+        async fn query_page(
+            &self,
+            request: Request<proto::QueryPageRequest>,
+        ) -> Result<Response<proto::QueryResponse>, Status> {
+            self.requests.lock().push("query_page");
+            let proto::QueryPageRequest { query, token } = request.into_inner();
+            Ok(Response::new(proto::QueryResponse {
+                items: vec![proto::ListItem {
+                    title: format!("{query} {token}"),
+                    ..Default::default()
+                }],
+                next_page_token: (token == "1").then(|| "2".to_owned()),
+                ..Default::default()
+            }))
+        }
+
+        async fn on_show(&self, _: Request<()>) -> Result<Response<()>, Status> {
+            self.requests.lock().push("on_show");
+            Ok(Response::new(()))
+        }
+
+        async fn on_hide(&self, _: Request<()>) -> Result<Response<()>, Status> {
+            self.requests.lock().push("on_hide");
+            Ok(Response::new(()))
+        }
+
+        async fn preview(
+            &self,
+            request: Request<proto::PreviewRequest>,
+        ) -> Result<Response<proto::PreviewResponse>, Status> {
+            let id = request.into_inner().selection_id;
+            Ok(Response::new(proto::PreviewResponse {
+                text: Some(format!("preview of {id}")),
+            }))
+        }
+
+        /// Sets the input to the form's ID and values.
+        async fn submit_form(
+            &self,
+            request: Request<proto::SubmitFormRequest>,
+        ) -> Result<Response<proto::ActivationResponse>, Status> {
+            let request = request.into_inner();
+            let query = format!("{}: {}", request.id, request.values.join(", "));
+            Ok(Response::new(proto::ActivationResponse {
+                actions: vec![proto::Action {
+                    action: Some(proto::action::Action::SetInput(proto::Input {
+                        range_lb: 0,
+                        range_ub: 0,
+                        query,
+                        select_item: None,
+                    })),
+                }],
+                execution: None,
+            }))
+        }
+
+        async fn reorder_pins(
+            &self,
+            _: Request<proto::ReorderPinsRequest>,
+        ) -> Result<Response<()>, Status> {
+            Ok(Response::new(()))
+        }
+
+        type LogsStream =
+            futures::stream::Iter<std::vec::IntoIter<Result<proto::LogMessage, Status>>>;
+
+        /// Logs a warning once when it starts.
+        async fn logs(&self, _: Request<()>) -> Result<Response<Self::LogsStream>, Status> {
+            Ok(Response::new(futures::stream::iter(vec![Ok(
+                proto::LogMessage {
+                    level: proto::LogLevel::Warn.into(),
+                    message: "echo plugin started".to_owned(),
+                },
+            )])))
+        }
+    }
+
+    /// Starts an [`EchoPlugin`] server, returning a directory with a
+    /// command named `id` that runs the plugin.
+    #[cfg(unix)]
+    pub(crate) async fn echo_plugin_command(id: &str) -> PathBuf {
+        echo_plugin_command_with(id, EchoPlugin::default()).await
+    }
+
+    /// Paths that keep the data of plugins in `dir`, so that tests
+    /// don't touch the user's data.
+    pub(crate) fn temp_paths(dir: &Path) -> PluginPaths {
+        PluginPaths {
+            plugins_dir: dir.join("plugins"),
+            ..PluginPaths::default()
+        }
+    }
+
+    /// [`echo_plugin_command`], with a plugin that can be inspected.
+    #[cfg(unix)]
+    async fn echo_plugin_command_with(id: &str, plugin: EchoPlugin) -> PathBuf {
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(proto::plugin_server::PluginServer::new(plugin))
+                .serve_with_incoming(incoming),
+        );
+
+        // the plugin command only needs to print the port of the server
+        plugin_command(id, &format!("echo {port}"))
+    }
+
+    /// Creates a directory with a command named `id` that runs the shell
+    /// `script`.
+    #[cfg(unix)]
+    fn plugin_command(id: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let bin_dir = std::env::temp_dir().join(id);
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let command = bin_dir.join(id);
+        std::fs::write(&command, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+        bin_dir
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn command_on_path_is_run() {
+        let id = format!("covey-path-test-{}", std::process::id());
+        let bin_dir = echo_plugin_command(&id).await;
+        let paths = PluginPaths {
+            search_path: Some(bin_dir.clone().into_os_string()),
+            ..temp_paths(&bin_dir)
+        };
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let missing: PluginConfig =
+            toml::from_str("id = \"missing\"\nprefix = \"\"\ncommand = \"covey-not-a-plugin\"")
+                .unwrap();
+        let err = Plugin::with_paths(missing, &global, paths.clone()).unwrap_err();
+        assert!(format!("{err:#}").contains("covey-not-a-plugin was not found in the PATH"));
+
+        let config: PluginConfig =
+            toml::from_str(&format!("id = \"{id}\"\nprefix = \"\"\ncommand = \"{id}\"")).unwrap();
+        let plugin = Plugin::with_paths(config, &global, paths).unwrap();
+        let outcome = plugin.query("hello", 7, None).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let Ok(QueryOutcome::Handled(list)) = outcome else {
+            panic!("plugin should answer the query: {outcome:?}");
+        };
+        assert_eq!(list.items[0].title(), "hello");
+        // the plugin echoes the request ID
+        assert_eq!(list.items[0].description(), "7");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn plugin_logs_are_forwarded() {
+        let logs = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = Arc::clone(&logs);
+                move || LogWriter(Arc::clone(&logs))
+            })
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let id = format!("covey-logs-test-{}", std::process::id());
+        let bin_dir = echo_plugin_command(&id).await;
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        plugin.query("hello", 1, None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let logs = String::from_utf8(logs.lock().clone()).unwrap();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("echo plugin started"))
+            .unwrap_or_else(|| panic!("plugin message was not logged:\n{logs}"));
+        assert!(line.starts_with(" WARN"), "{line}");
+        assert!(line.contains(&format!("plugin=\"{id}\"")), "{line}");
+    }
+
+    /// Writes logs into a shared buffer.
+    struct LogWriter(Arc<parking_lot::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn spawn_times_out_without_port() {
+        let id = format!("covey-spawn-test-{}", std::process::id());
+        let bin_dir = plugin_command(&id, "sleep 10");
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"\ntimeouts.spawn_ms = 100",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let start = std::time::Instant::now();
+        let outcome = plugin.query("hello", 1, None).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let err = format!("{:#}", outcome.unwrap_err());
+        assert!(
+            err.contains("plugin did not print its port within 100ms"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unusable_plugins_are_killed() {
+        let id = format!("covey-unusable-test-{}", std::process::id());
+        let bin_dir = plugin_command(
+            &id,
+            "echo not-a-port\nsleep 0.5\ntouch \"$(dirname \"$0\")/still-running\"",
+        );
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let outcome = plugin.query("hello", 1, None).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let still_running = bin_dir.join("still-running").exists();
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let err = format!("{:#}", outcome.unwrap_err());
+        assert!(err.contains("port number"), "unexpected error: {err}");
+        assert!(!still_running);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn memory_limit_stops_plugin() {
+        let id = format!("covey-limit-test-{}", std::process::id());
+        let bin_dir = plugin_command(
+            &id,
+            "big=$(head -c 200000000 /dev/zero | tr '\\0' a)\necho 1",
+        );
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"\nlimits.memory_mb = 32",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let outcome = plugin.query("hello", 1, None).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let err = format!("{:#}", outcome.unwrap_err());
+        assert!(
+            err.contains("before printing its port: it may have gone over its resource limits"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn oversized_responses_are_reported() {
+        let id = format!("covey-size-test-{}", std::process::id());
+        let bin_dir = echo_plugin_command(&id).await;
+
+        let global: GlobalConfig = toml::from_str("max_message_bytes = 100").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let small = plugin.query("hello", 1, None).await;
+        let large = plugin.query("a".repeat(200), 2, None).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(small.is_ok());
+        let err = format!("{:#}", large.unwrap_err());
+        assert!(
+            err.contains("plugin returned too many results"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn next_page_is_requested_with_query() {
+        let id = format!("covey-page-test-{}", std::process::id());
+        let bin_dir = echo_plugin_command(&id).await;
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let first = plugin.query_page("hello".to_owned(), "1".to_owned()).await;
+        let last = plugin.query_page("hello".to_owned(), "2".to_owned()).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        let first = first.unwrap();
+        assert_eq!(first.items[0].title(), "hello 1");
+        let next_page = first.next_page.unwrap();
+        assert_eq!(
+            (next_page.query.as_str(), next_page.token.as_str()),
+            ("hello", "2")
+        );
+        assert!(last.unwrap().next_page.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn requests_are_only_sent_with_their_capability() {
+        let id = format!("covey-capability-test-{}", std::process::id());
+        let echo = EchoPlugin {
+            missing_capabilities: vec![proto::Capability::Streaming, proto::Capability::Subscribe],
+            ..EchoPlugin::default()
+        };
+        let bin_dir = echo_plugin_command_with(&id, echo.clone()).await;
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        let streaming = plugin.supports(Capability::Streaming).await;
+        let page = plugin.query_page("hello".to_owned(), "1".to_owned()).await;
+        let shown = plugin.on_show().await;
+        let hidden = plugin.on_hide().await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(!streaming.unwrap());
+        assert!(page.unwrap().items.is_empty());
+        assert!(shown.is_ok() && hidden.is_ok());
+        assert!(echo.requests.lock().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn settings_schema_is_fetched() {
+        use covey_config::manifest::SchemaType;
+
+        let id = format!("covey-schema-test-{}", std::process::id());
+        let bin_dir = echo_plugin_command(&id).await;
+
+        let global: GlobalConfig = toml::from_str("").unwrap();
+        let config: PluginConfig = toml::from_str(&format!(
+            "id = \"{id}\"\nprefix = \"\"\ncommand = \"{}\"",
+            bin_dir.join(&id).display()
+        ))
+        .unwrap();
+        let plugin = Plugin::with_paths(config, &global, temp_paths(&bin_dir)).unwrap();
+        // the manifest's schema is used until the plugin is initialised
+        assert!(plugin.settings_schema().iter().next().is_none());
+        let outcome = plugin.query("hello", 1, None).await;
+        _ = std::fs::remove_dir_all(&bin_dir);
+
+        assert!(outcome.is_ok());
+        let schema = plugin.settings_schema();
+        let greeting = schema.get("greeting").unwrap();
+        assert_eq!(greeting.title, "Greeting");
+        assert_eq!(
+            greeting.description.as_deref(),
+            Some("Said before every result")
+        );
+        assert!(
+            matches!(&greeting.r#type, SchemaType::Text(text) if text.default.as_deref() == Some("hello"))
+        );
+        assert!(matches!(
+            schema.get("repeat").unwrap().r#type,
+            SchemaType::Int(_)
+        ));
     }
 }
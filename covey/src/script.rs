@@ -0,0 +1,299 @@
+//! Plugins that are simple scripts, instead of plugin servers.
+//!
+//! A script is run with `--` and the query as arguments, and prints one
+//! JSON item per line:
+//!
+//! ```json
+//! {"title": "Firefox", "description": "Web browser", "icon": "firefox"}
+//! ```
+//!
+//! When an item is activated, the script is run again with `--activate`
+//! and the item's line, and prints one JSON action per line:
+//!
+//! ```json
+//! {"action": "run-command", "command": "firefox", "args": []}
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Stdio,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use az::SaturatingAs as _;
+use color_eyre::eyre::{bail, eyre, Context as _, Result};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::proto;
+
+/// An item printed by a script.
+///
+/// Any other fields are ignored, but are still given back to the script
+/// when the item is activated.
+#[derive(Debug, Deserialize)]
+struct ScriptItem {
+    title: String,
+    #[serde(default)]
+    description: String,
+    /// Name of an icon from the system.
+    #[serde(default)]
+    icon: Option<String>,
+}
+
+/// An action printed by a script when an item is activated.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+enum ScriptAction {
+    Close,
+    RunCommand {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    RunShell {
+        command: String,
+    },
+    Copy {
+        text: String,
+    },
+    SetInput {
+        query: String,
+    },
+    OpenUrl {
+        url: String,
+    },
+    TypeText {
+        text: String,
+    },
+    WriteFile {
+        path: String,
+        contents: String,
+        #[serde(default)]
+        append: bool,
+    },
+    OpenPreviewWindow {
+        content: String,
+    },
+    ClearInput,
+}
+
+impl ScriptAction {
+    fn into_proto(self) -> proto::Action {
+        use proto::action::Action;
+
+        let action = match self {
+            Self::Close => Action::Close(()),
+            Self::RunCommand { command, args } => {
+                Action::RunCommand(proto::Command { cmd: command, args })
+            }
+            Self::RunShell { command } => Action::RunShell(command),
+            Self::Copy { text } => Action::Copy(text),
+            Self::SetInput { query } => {
+                // put the cursor at the end
+                let len = query.chars().count().saturating_as();
+                Action::SetInput(proto::Input {
+                    query,
+                    range_lb: len,
+                    range_ub: len,
+                    select_item: None,
+                })
+            }
+            Self::OpenUrl { url } => Action::OpenUrl(url),
+            Self::TypeText { text } => Action::TypeText(text),
+            Self::WriteFile {
+                path,
+                contents,
+                append,
+            } => Action::WriteFile(proto::WriteFile {
+                path,
+                contents,
+                append,
+            }),
+            Self::OpenPreviewWindow { content } => Action::OpenPreviewWindow(content),
+            Self::ClearInput => Action::ClearInput(()),
+        };
+        proto::Action {
+            action: Some(action),
+        }
+    }
+}
+
+/// A script that is run for every query and activation.
+pub(crate) struct Script {
+    path: PathBuf,
+    /// Lines of the items of the latest query, by their ID.
+    items: Mutex<HashMap<u64, String>>,
+    /// IDs are unique across queries, so that an item from an old list
+    /// can't activate a different item.
+    next_id: AtomicU64,
+}
+
+impl Script {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            items: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) async fn query(&self, query: String) -> Result<proto::QueryResponse> {
+        // a query like `--activate` must not look like an option
+        let output = self.run(&["--".to_owned(), query]).await?;
+
+        let mut lines = HashMap::new();
+        let mut items = vec![];
+        for line in output.lines().filter(|line| !line.trim().is_empty()) {
+            let item: ScriptItem = serde_json::from_str(line)
+                .context(format!("script printed an invalid item: {line}"))?;
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            lines.insert(id, line.to_owned());
+            items.push(proto::ListItem {
+                id,
+                title: item.title,
+                description: item.description,
+                icon: item.icon.map(proto::list_item::Icon::Name),
+                available_commands: vec!["activate".to_owned()],
+                ..Default::default()
+            });
+        }
+        *self.items.lock() = lines;
+
+        Ok(proto::QueryResponse {
+            items,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) async fn activate(
+        &self,
+        selection_id: u64,
+        command_name: &str,
+    ) -> Result<Vec<proto::Action>> {
+        if command_name != "activate" {
+            bail!("scripts can only be activated with the activate command")
+        }
+        let line = self
+            .items
+            .lock()
+            .get(&selection_id)
+            .cloned()
+            .ok_or_else(|| eyre!("item is no longer shown"))?;
+        let output = self.run(&["--activate".to_owned(), line]).await?;
+
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let action: ScriptAction = serde_json::from_str(line)
+                    .context(format!("script printed an invalid action: {line}"))?;
+                Ok(action.into_proto())
+            })
+            .collect()
+    }
+
+    /// Runs the script, returning what it printed.
+    async fn run(&self, args: &[String]) -> Result<String> {
+        let output = Command::new(&self.path)
+            .args(args)
+            .stdin(Stdio::null())
+            // stop the script if its results are no longer needed
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context(format!("failed to run script {}", self.path.display()))?;
+
+        if !output.status.success() {
+            bail!(
+                "script failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        String::from_utf8(output.stdout).context("script printed invalid UTF-8")
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use super::Script;
+    use crate::proto;
+
+    #[tokio::test]
+    async fn items_and_actions_are_read() {
+        let dir = std::env::temp_dir().join(format!("covey-script-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("script");
+        std::fs::write(
+            &path,
+            r#"#!/bin/sh
+if [ "$1" = --activate ]; then
+    echo '{"action": "copy", "text": "copied"}'
+    echo '{"action": "close"}'
+else
+    echo "{\"title\": \"$2\", \"extra\": 1}"
+    echo
+    echo '{"title": "second", "description": "two", "icon": "folder"}'
+fi
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let script = Script::new(path);
+        let response = script.query("first".to_owned()).await;
+        let actions = match &response {
+            Ok(response) => script.activate(response.items[0].id, "activate").await,
+            Err(_) => Ok(vec![]),
+        };
+        _ = std::fs::remove_dir_all(&dir);
+
+        let items = response.unwrap().items;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "first");
+        assert_eq!(items[1].title, "second");
+        assert_eq!(items[1].description, "two");
+        assert_eq!(
+            items[1].icon,
+            Some(proto::list_item::Icon::Name("folder".to_owned()))
+        );
+
+        let actions: Vec<_> = actions.unwrap().into_iter().map(|a| a.action).collect();
+        assert_eq!(
+            actions,
+            [
+                Some(proto::action::Action::Copy("copied".to_owned())),
+                Some(proto::action::Action::Close(())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn queries_are_not_options() {
+        let dir = std::env::temp_dir().join(format!("covey-script-args-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("script");
+        std::fs::write(
+            &path,
+            r#"#!/bin/sh
+if [ "$1" = --activate ]; then
+    echo '{"action": "close"}'
+else
+    echo "{\"title\": \"$1 $2\"}"
+fi
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let response = Script::new(path).query("--activate".to_owned()).await;
+        _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(response.unwrap().items[0].title, "-- --activate");
+    }
+}
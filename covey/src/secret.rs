@@ -0,0 +1,154 @@
+//! Secrets referenced in a plugin's configuration.
+//!
+//! Instead of storing a secret like an API key in plain text, the plugin
+//! configuration can reference an entry in the OS keyring:
+//!
+//! ```toml
+//! [plugins.config]
+//! api-key = { keyring = "service/user" }
+//! ```
+//!
+//! These references are replaced with the secret before the configuration
+//! is sent to the plugin.
+
+use color_eyre::eyre::{bail, eyre, Context, Result};
+use serde_json::{Map, Value};
+
+/// Somewhere that secrets can be read from.
+pub(crate) trait SecretStore {
+    fn get(&self, service: &str, user: &str) -> Result<String>;
+}
+
+/// The OS keyring.
+pub(crate) struct Keyring;
+
+impl SecretStore for Keyring {
+    fn get(&self, service: &str, user: &str) -> Result<String> {
+        Ok(keyring::Entry::new(service, user)?.get_password()?)
+    }
+}
+
+/// Replaces every `{ keyring = "service/user" }` table in the
+/// configuration with the secret stored in that entry.
+///
+/// # Errors
+/// Returns an error if a referenced entry could not be read.
+pub(crate) fn resolve(
+    config: &Map<String, Value>,
+    store: &impl SecretStore,
+) -> Result<Map<String, Value>> {
+    config
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), resolve_value(value, key, store)?)))
+        .collect()
+}
+
+fn resolve_value(value: &Value, path: &str, store: &impl SecretStore) -> Result<Value> {
+    Ok(match value {
+        Value::Object(map) => {
+            if let Some(reference) = keyring_reference(map) {
+                let Some((service, user)) = reference.rsplit_once('/') else {
+                    bail!(
+                        "keyring reference {reference:?} in `{path}` \
+                        must be in the form \"service/user\""
+                    );
+                };
+                Value::String(store.get(service, user).wrap_err_with(|| {
+                    eyre!("failed to read keyring entry {reference:?} referenced in `{path}`")
+                })?)
+            } else {
+                Value::Object(
+                    map.iter()
+                        .map(|(key, value)| {
+                            Ok((
+                                key.clone(),
+                                resolve_value(value, &format!("{path}.{key}"), store)?,
+                            ))
+                        })
+                        .collect::<Result<_>>()?,
+                )
+            }
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| resolve_value(value, &format!("{path}[{i}]"), store))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// Returns the reference if the table is exactly `{ keyring = "..." }`.
+fn keyring_reference(map: &Map<String, Value>) -> Option<&str> {
+    match map.iter().next() {
+        Some((key, Value::String(reference))) if map.len() == 1 && key == "keyring" => {
+            Some(reference)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use color_eyre::eyre::{ContextCompat, Result};
+    use serde_json::{json, Value};
+
+    use super::{resolve, SecretStore};
+
+    struct MockKeyring(HashMap<(&'static str, &'static str), &'static str>);
+
+    impl SecretStore for MockKeyring {
+        fn get(&self, service: &str, user: &str) -> Result<String> {
+            self.0
+                .get(&(service, user))
+                .map(|s| (*s).to_owned())
+                .context("no matching entry found")
+        }
+    }
+
+    fn config(value: Value) -> serde_json::Map<String, Value> {
+        let Value::Object(map) = value else {
+            panic!("config should be an object")
+        };
+        map
+    }
+
+    #[test]
+    fn resolves_keyring_reference() {
+        let store = MockKeyring(HashMap::from([(("github", "me"), "hunter2")]));
+        let resolved = resolve(
+            &config(json!({
+                "api-key": { "keyring": "github/me" },
+                "nested": { "list": [{ "keyring": "github/me" }] },
+                "other": { "keyring": "not a reference", "extra": 1 },
+            })),
+            &store,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Value::Object(resolved),
+            json!({
+                "api-key": "hunter2",
+                "nested": { "list": ["hunter2"] },
+                "other": { "keyring": "not a reference", "extra": 1 },
+            })
+        );
+    }
+
+    #[test]
+    fn missing_entry_errors() {
+        let store = MockKeyring(HashMap::new());
+        let err = resolve(
+            &config(json!({ "api-key": { "keyring": "github/me" } })),
+            &store,
+        )
+        .unwrap_err();
+
+        assert!(format!("{err:#}").contains("\"github/me\" referenced in `api-key`"));
+    }
+}
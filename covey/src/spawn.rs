@@ -1,9 +1,21 @@
 //! Quick utilities for spawning processes
 
-use std::{ffi::OsStr, process::Stdio};
+use std::{
+    env,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    thread,
+    time::Duration,
+};
 
-use color_eyre::eyre::Result;
-use tokio::process::Child;
+use color_eyre::eyre::{bail, Context, ContextCompat as _, Result};
+use covey_config::config::{ResourceLimits, Shell};
+use tokio::{
+    io::AsyncWriteExt as _,
+    process::{Child, Command},
+};
+use tracing::{debug, error};
 
 /// Spawn a process with `Stdio::null()` for stdin/out/err.
 pub(crate) fn free_null(
@@ -17,3 +29,328 @@ pub(crate) fn free_null(
         .stderr(Stdio::null())
         .spawn()?)
 }
+
+/// Runs a command with the user's shell in the background.
+pub(crate) fn shell(shell: &Shell, cmd: &str) -> Result<Child> {
+    free_null(
+        &shell.program,
+        shell.args.iter().map(String::as_str).chain([cmd]),
+    )
+}
+
+/// Applies the limits to the process once it is spawned.
+///
+/// Going over the memory limit makes allocations fail, and going over
+/// the CPU time limit kills the process.
+#[cfg(target_os = "linux")]
+pub(crate) fn limit_resources(command: &mut Command, limits: &ResourceLimits) {
+    const MEGABYTE: u64 = 1024 * 1024;
+
+    let limits = [
+        (
+            libc::RLIMIT_AS,
+            limits.memory_mb.map(|mb| u64::from(mb) * MEGABYTE),
+        ),
+        (libc::RLIMIT_CPU, limits.cpu_seconds.map(u64::from)),
+    ];
+    if limits.iter().all(|(_, limit)| limit.is_none()) {
+        return;
+    }
+
+    // SAFETY: only setrlimit is called after forking, which is
+    // async-signal-safe and doesn't allocate.
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, limit) in limits {
+                let Some(limit) = limit else { continue };
+                let rlimit = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                if libc::setrlimit(resource, &raw const rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn limit_resources(_command: &mut Command, limits: &ResourceLimits) {
+    if *limits != ResourceLimits::default() {
+        tracing::warn!("resource limits are only supported on Linux: ignoring them");
+    }
+}
+
+/// Finds the path to a program, either directly or in the `search_path`,
+/// which is formatted like the `PATH` environment variable.
+///
+/// # Errors
+/// Returns an error if the program does not exist.
+pub(crate) fn find_program(program: &str, search_path: Option<&OsStr>) -> Result<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path
+            .is_file()
+            .then(|| path.to_owned())
+            .context(format!("{program} does not exist"));
+    }
+
+    let extensions: &[&str] = if cfg!(windows) {
+        &["exe", "cmd", "bat"]
+    } else {
+        &[]
+    };
+    search_path
+        .into_iter()
+        .flat_map(env::split_paths)
+        .flat_map(|dir| {
+            let file = dir.join(program);
+            let mut candidates = vec![file.clone()];
+            candidates.extend(extensions.iter().map(|ext| file.with_extension(ext)));
+            candidates
+        })
+        .find(|file| file.is_file())
+        .context(format!("{program} was not found in the PATH"))
+}
+
+/// Runs a command with the user's shell with `input` on stdin, returning
+/// its stdout without the trailing newline.
+///
+/// # Errors
+/// Returns an error if the command fails, exits unsuccessfully, does not
+/// output valid UTF-8 or takes longer than the `timeout`.
+pub(crate) async fn pipe_through(
+    shell: &Shell,
+    cmd: &str,
+    input: &str,
+    timeout: Duration,
+) -> Result<String> {
+    let run = async {
+        let mut child = tokio::process::Command::new(&shell.program)
+            .args(&shell.args)
+            .arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().expect("stdin should be captured");
+        stdin.write_all(input.as_bytes()).await?;
+        drop(stdin);
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            bail!("command exited with {}", output.status);
+        }
+
+        let mut stdout = String::from_utf8(output.stdout)?;
+        if stdout.ends_with('\n') {
+            stdout.pop();
+        }
+        Ok(stdout)
+    };
+
+    tokio::time::timeout(timeout, run)
+        .await
+        .context("command timed out")?
+        .context(format!("failed to run command `{cmd}`"))
+}
+
+/// Starts playing a sound file in the background.
+///
+/// This uses `afplay` on macOS and `paplay` elsewhere.
+///
+/// # Errors
+/// Returns an error if the file doesn't exist or the player could not
+/// be started.
+pub(crate) fn play_sound(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        bail!("sound file {} does not exist", path.display());
+    }
+
+    let player = if cfg!(target_os = "macos") {
+        "afplay"
+    } else {
+        "paplay"
+    };
+    free_null(player, [path]).context(format!("failed to run {player}"))?;
+    Ok(())
+}
+
+/// Time to wait for the window to hide and focus to return to the
+/// previous application before typing.
+const HIDE_DELAY: Duration = Duration::from_millis(150);
+
+/// Types some text into the focused application by simulating key presses,
+/// waiting `key_delay` between each key.
+///
+/// Typing starts in the background after a short delay, so that covey can
+/// be hidden first.
+///
+/// Only Linux is supported, with `wtype` on Wayland or `xdotool` on X11.
+///
+/// # Errors
+/// Returns an error if the platform is not supported. Errors from running
+/// the command are logged.
+pub(crate) fn type_text(text: &str, key_delay: Duration) -> Result<()> {
+    let mut cmd = type_text_command(text, key_delay, env::var_os("WAYLAND_DISPLAY").is_some())?;
+
+    thread::spawn(move || {
+        thread::sleep(HIDE_DELAY);
+        if let Err(e) = cmd.stdin(Stdio::null()).stdout(Stdio::null()).status() {
+            error!("failed to type text with {:?}: {e}", cmd.get_program());
+        }
+    });
+
+    Ok(())
+}
+
+fn type_text_command(
+    text: &str,
+    key_delay: Duration,
+    wayland: bool,
+) -> Result<std::process::Command> {
+    if !cfg!(target_os = "linux") {
+        bail!("typing text is only supported on Linux");
+    }
+
+    let delay = key_delay.as_millis().to_string();
+    let mut cmd;
+    if wayland {
+        cmd = std::process::Command::new("wtype");
+        cmd.args(["-d", &delay, "--", text]);
+    } else {
+        cmd = std::process::Command::new("xdotool");
+        cmd.args(["type", "--delay", &delay, "--", text]);
+    }
+    Ok(cmd)
+}
+
+/// Reads the text selected in other apps, or an empty string if nothing
+/// is selected.
+///
+/// On Linux, this is the primary selection from `wl-paste` on Wayland or
+/// `xclip` on X11, falling back to the clipboard if the primary selection
+/// can't be read. The clipboard is read with `pbpaste` on macOS. Errors
+/// are logged.
+pub(crate) async fn primary_selection() -> String {
+    let commands = primary_selection_commands(env::var_os("WAYLAND_DISPLAY").is_some());
+    for args in commands {
+        match tokio::process::Command::new(args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+        {
+            Ok(output) if output.status.success() => {
+                return String::from_utf8_lossy(&output.stdout).into_owned();
+            }
+            Ok(output) => debug!("{} exited with {}", args[0], output.status),
+            Err(e) => debug!("failed to run {}: {e}", args[0]),
+        }
+    }
+    // nothing is selected, or there is no way to read it
+    String::new()
+}
+
+/// Commands to try in order to read the selection.
+fn primary_selection_commands(wayland: bool) -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else if wayland {
+        &[
+            &["wl-paste", "--primary", "--no-newline"],
+            &["wl-paste", "--no-newline"],
+        ]
+    } else {
+        &[
+            &["xclip", "-o", "-selection", "primary"],
+            &["xclip", "-o", "-selection", "clipboard"],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use covey_config::config::Shell;
+
+    use super::{find_program, pipe_through, shell};
+
+    #[tokio::test]
+    async fn pipes_through_command() {
+        let output = pipe_through(
+            &Shell::default(),
+            "tr a-z A-Z",
+            "git status",
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output, "GIT STATUS");
+
+        // the command is passed as the last argument, which is `$0`
+        let configured = Shell {
+            program: "sh".to_owned(),
+            args: vec!["-c".to_owned(), "printf 'custom %s' \"$0\"".to_owned()],
+        };
+        let output = pipe_through(&configured, "tr", "", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(output, "custom tr");
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let result =
+            pipe_through(&Shell::default(), "sleep 5", "", Duration::from_millis(50)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn runs_with_configured_shell() {
+        let out = std::env::temp_dir().join(format!("covey-shell-test-{}", std::process::id()));
+        // the command is passed as the last argument, which is `$0`
+        let configured = Shell {
+            program: "sh".to_owned(),
+            args: vec![
+                "-c".to_owned(),
+                format!("printf 'custom %s' \"$0\" > '{}'", out.display()),
+            ],
+        };
+
+        let status = shell(&configured, "echo hi").unwrap().wait().await.unwrap();
+        assert!(status.success());
+        assert_eq!(fs::read_to_string(&out).unwrap(), "custom echo hi");
+        fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn finds_programs() {
+        let path = std::env::var_os("PATH");
+        assert!(find_program("sh", path.as_deref()).is_ok());
+        assert!(find_program("covey-definitely-not-a-shell", path.as_deref()).is_err());
+        assert!(find_program("/covey/definitely/not/a/shell", path.as_deref()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_programs_in_search_path() {
+        use std::{ffi::OsStr, path::Path};
+
+        assert_eq!(
+            find_program("sh", Some(OsStr::new("/covey/not/a/dir:/bin"))).unwrap(),
+            Path::new("/bin/sh")
+        );
+        assert!(find_program("sh", Some(OsStr::new("/covey/not/a/dir"))).is_err());
+        assert!(find_program("sh", None).is_err());
+        // paths don't need the search path
+        assert!(find_program("/bin/sh", None).is_ok());
+    }
+}
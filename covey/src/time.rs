@@ -0,0 +1,67 @@
+//! Formatting of times shown to the user.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats a unix timestamp (in seconds) relative to `now`, like
+/// `5m ago` or `in 2d`.
+///
+/// Times within a minute of `now` are `just now`.
+pub fn format_relative(timestamp: i64, now: i64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const WEEK: u64 = 7 * DAY;
+    const YEAR: u64 = 365 * DAY;
+
+    let seconds = now.abs_diff(timestamp);
+    if seconds < MINUTE {
+        return "just now".to_owned();
+    }
+
+    let (unit, suffix) = [(YEAR, "y"), (WEEK, "w"), (DAY, "d"), (HOUR, "h")]
+        .into_iter()
+        .find(|&(unit, _)| seconds >= unit)
+        .unwrap_or((MINUTE, "m"));
+    let amount = format!("{}{suffix}", seconds / unit);
+
+    if timestamp <= now {
+        format!("{amount} ago")
+    } else {
+        format!("in {amount}")
+    }
+}
+
+/// Formats a unix timestamp (in seconds) relative to the current time.
+///
+/// See [`format_relative`].
+pub fn format_relative_to_now(timestamp: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| {
+            i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+        });
+    format_relative(timestamp, now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_relative;
+
+    const NOW: i64 = 1_700_000_000;
+
+    #[test]
+    fn past_times() {
+        assert_eq!(format_relative(NOW - 3600, NOW), "1h ago");
+        assert_eq!(format_relative(NOW - 30, NOW), "just now");
+        assert_eq!(format_relative(NOW - 5 * 60, NOW), "5m ago");
+        assert_eq!(format_relative(NOW - 3 * 86400, NOW), "3d ago");
+        assert_eq!(format_relative(NOW - 14 * 86400, NOW), "2w ago");
+        assert_eq!(format_relative(NOW - 800 * 86400, NOW), "2y ago");
+    }
+
+    #[test]
+    fn future_times() {
+        assert_eq!(format_relative(NOW + 2 * 3600 + 59, NOW), "in 2h");
+        assert_eq!(format_relative(NOW + 10, NOW), "just now");
+    }
+}